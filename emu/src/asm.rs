@@ -71,6 +71,22 @@ mod tests {
         asm.assemble().unwrap();
     }
 
+    #[test]
+    fn it_branches_on_zeropage_bit_to_label() {
+        let mut asm = Assembler::new();
+        asm.org(0x1000)
+            .label("foo")
+            .bbr(3, Operand::ZRel(0x42, branch("bar")))
+            .nop()
+            .label("bar")
+            .bbs(7, Operand::ZRel(0x42, branch("foo")));
+        println!("{}", asm);
+        assert_eq!(
+            asm.assemble().unwrap(),
+            vec![0x3F, 0x42, 0x01, 0xEA, 0xFF, 0x42, 0xF9]
+        );
+    }
+
     #[test]
     fn it_lists_with_out_of_range_label() {
         let mut asm = Assembler::new();
@@ -134,6 +150,14 @@ impl Assembler {
         self.push_instruction(Mnemonic::Asl, op)
     }
 
+    pub fn bbr(&mut self, bit: usize, op: Operand) -> &mut Self {
+        self.push_instruction(bit_mnemonic(&isa::BBR, "BBR", bit), op)
+    }
+
+    pub fn bbs(&mut self, bit: usize, op: Operand) -> &mut Self {
+        self.push_instruction(bit_mnemonic(&isa::BBS, "BBS", bit), op)
+    }
+
     pub fn bcc(&mut self, op: Operand) -> &mut Self {
         self.push_instruction(Mnemonic::Bcc, op)
     }
@@ -162,6 +186,10 @@ impl Assembler {
         self.push_instruction(Mnemonic::Bpl, op)
     }
 
+    pub fn bra(&mut self, op: Operand) -> &mut Self {
+        self.push_instruction(Mnemonic::Bra, op)
+    }
+
     pub fn brk(&mut self) -> &mut Self {
         self.push_instruction(Mnemonic::Brk, Operand::Impl)
     }
@@ -278,6 +306,10 @@ impl Assembler {
         self.push_instruction(Mnemonic::Plp, Operand::Impl)
     }
 
+    pub fn rmb(&mut self, bit: usize, op: Operand) -> &mut Self {
+        self.push_instruction(bit_mnemonic(&isa::RMB, "RMB", bit), op)
+    }
+
     pub fn rol(&mut self, op: Operand) -> &mut Self {
         self.push_instruction(Mnemonic::Rol, op)
     }
@@ -310,10 +342,18 @@ impl Assembler {
         self.push_instruction(Mnemonic::Sei, Operand::Impl)
     }
 
+    pub fn smb(&mut self, bit: usize, op: Operand) -> &mut Self {
+        self.push_instruction(bit_mnemonic(&isa::SMB, "SMB", bit), op)
+    }
+
     pub fn sta(&mut self, op: Operand) -> &mut Self {
         self.push_instruction(Mnemonic::Sta, op)
     }
 
+    pub fn stp(&mut self) -> &mut Self {
+        self.push_instruction(Mnemonic::Stp, Operand::Impl)
+    }
+
     pub fn stx(&mut self, op: Operand) -> &mut Self {
         self.push_instruction(Mnemonic::Stx, op)
     }
@@ -322,6 +362,10 @@ impl Assembler {
         self.push_instruction(Mnemonic::Sty, op)
     }
 
+    pub fn stz(&mut self, op: Operand) -> &mut Self {
+        self.push_instruction(Mnemonic::Stz, op)
+    }
+
     pub fn tax(&mut self) -> &mut Self {
         self.push_instruction(Mnemonic::Tax, Operand::Impl)
     }
@@ -330,6 +374,14 @@ impl Assembler {
         self.push_instruction(Mnemonic::Tay, Operand::Impl)
     }
 
+    pub fn trb(&mut self, op: Operand) -> &mut Self {
+        self.push_instruction(Mnemonic::Trb, op)
+    }
+
+    pub fn tsb(&mut self, op: Operand) -> &mut Self {
+        self.push_instruction(Mnemonic::Tsb, op)
+    }
+
     pub fn tsx(&mut self) -> &mut Self {
         self.push_instruction(Mnemonic::Tsx, Operand::Impl)
     }
@@ -346,6 +398,10 @@ impl Assembler {
         self.push_instruction(Mnemonic::Tya, Operand::Impl)
     }
 
+    pub fn wai(&mut self) -> &mut Self {
+        self.push_instruction(Mnemonic::Wai, Operand::Impl)
+    }
+
     // ----------------------------------------
 
     pub fn assemble(&self) -> Result<Vec<u8>, Error> {
@@ -435,8 +491,11 @@ impl fmt::Display for Operand {
             Imm(x) => write!(f, "${:02X}", x),
             Impl => Ok(()),
             Ind(x) => write!(f, "({})", x),
+            AbsXInd(x) => write!(f, "({},X)", x),
             XInd(x) => write!(f, "(${:02X},X)", x),
             IndY(x) => write!(f, "(${:02X}),Y", x),
+            ZInd(x) => write!(f, "(${:02X})", x),
+            ZRel(x, y) => write!(f, "${:02X},{}", x, y),
             Rel(x) => write!(f, "{}", x),
             Z(x) => write!(f, "${:02X}", x),
             ZX(x) => write!(f, "${:02X},X", x),
@@ -493,7 +552,7 @@ impl Line {
 
     fn size(&self) -> u16 {
         match self {
-            Line::Instruction(line) => line.operand.length() as u16 + 1,
+            Line::Instruction(line) => line.operand.length() + 1,
             Line::Data(line) => line.data.len().try_into().unwrap(),
         }
     }
@@ -511,7 +570,7 @@ impl Line {
             Line::Instruction(line) => {
                 let instruction = line.instruction.unwrap();
                 let mut err: Option<Error> = None;
-                let ophex = match op_value(addr, &line.operand, &labtab) {
+                let ophex = match op_value(addr, &line.operand, labtab) {
                     Ok(x) => match x {
                         OpValue::None => String::new(),
                         OpValue::U8(x) => format!("{:02X}", x),
@@ -519,7 +578,7 @@ impl Line {
                     },
                     Err(e) => {
                         err = Some(e);
-                        format!("?? ??")
+                        "?? ??".to_string()
                     }
                 };
                 let label = match &line.label {
@@ -573,7 +632,13 @@ impl Line {
 
                     let ascii: String = linechunk
                         .iter()
-                        .map(|&x| if x >= 32 && x <= 126 { x as char } else { '.' })
+                        .map(|&x| {
+                            if (32..=126).contains(&x) {
+                                x as char
+                            } else {
+                                '.'
+                            }
+                        })
                         .collect();
 
                     writeln!(f, "{:04X}  {:49} |{}|", addr, hex, ascii)?;
@@ -597,12 +662,15 @@ pub enum Operand {
     Imm(u8),
     Impl,
     Ind(Addr),
+    AbsXInd(Addr),
     XInd(u8),
     IndY(u8),
     Rel(BranchTarget),
     Z(u8),
     ZX(u8),
     ZY(u8),
+    ZInd(u8),
+    ZRel(u8, BranchTarget),
 }
 
 impl Operand {
@@ -615,12 +683,15 @@ impl Operand {
             Operand::Imm(_) => AddressMode::Immediate,
             Operand::Impl => AddressMode::Implied,
             Operand::Ind(_) => AddressMode::Indirect,
+            Operand::AbsXInd(_) => AddressMode::AbsoluteIndexedIndirect,
             Operand::XInd(_) => AddressMode::XIndirect,
             Operand::IndY(_) => AddressMode::IndirectY,
             Operand::Rel(_) => AddressMode::Relative,
             Operand::Z(_) => AddressMode::Zeropage,
             Operand::ZX(_) => AddressMode::ZeropageX,
             Operand::ZY(_) => AddressMode::ZeropageY,
+            Operand::ZInd(_) => AddressMode::ZeropageIndirect,
+            Operand::ZRel(_, _) => AddressMode::ZeropageRelative,
         }
     }
 
@@ -679,34 +750,53 @@ impl From<isa::Error> for Error {
     }
 }
 
+// The mnemonic for bit number bit of RMB, SMB, BBR or BBS, which only have bits 0-7.
+fn bit_mnemonic(mnemonics: &[Mnemonic; 8], name: &str, bit: usize) -> Mnemonic {
+    assert!(bit < 8, "{name}{bit}: bit number must be 0-7");
+    mnemonics[bit]
+}
+
 fn op_value(addr: u16, op: &Operand, labtab: &HashMap<&str, u16>) -> Result<OpValue, Error> {
     use Operand::*;
     match op {
         A | Impl => Ok(OpValue::None),
-        Abs(x) | AbsX(x) | AbsY(x) | Ind(x) => match x {
+        Abs(x) | AbsX(x) | AbsY(x) | Ind(x) | AbsXInd(x) => match x {
             Addr::Literal(x) => Ok(OpValue::U16(*x)),
             Addr::Label(x) => match labtab.get(x.as_str()) {
                 Some(addr) => Ok(OpValue::U16(*addr)),
                 None => Err(Error::LabelNotFound),
             },
         },
-        Rel(x) => match x {
-            BranchTarget::Offset(x) => Ok(OpValue::U8(*x as u8)),
-            BranchTarget::Label(x) => {
-                let target_addr = match labtab.get(x.as_str()) {
-                    Some(addr) => *addr,
-                    None => return Err(Error::LabelNotFound),
-                };
-                let rel16: i16 = target_addr.wrapping_sub(addr) as i16;
-                let rel8: i8 = match rel16.try_into() {
-                    Ok(x) => x,
-                    Err(_) => {
-                        return Err(Error::RelativeAddressOutOfRange(rel16));
-                    }
-                };
-                Ok(OpValue::U8(rel8 as u8))
-            }
-        },
-        Imm(x) | XInd(x) | IndY(x) | Z(x) | ZX(x) | ZY(x) => Ok(OpValue::U8(*x)),
+        Rel(x) => Ok(OpValue::U8(branch_offset(addr, x, labtab)?)),
+        // zeropage address in the low byte, followed by the branch offset
+        ZRel(zp, x) => Ok(OpValue::U16(
+            (branch_offset(addr, x, labtab)? as u16) << 8 | *zp as u16,
+        )),
+        Imm(x) | XInd(x) | IndY(x) | Z(x) | ZX(x) | ZY(x) | ZInd(x) => Ok(OpValue::U8(*x)),
+    }
+}
+
+// Resolve a branch target to an offset relative to addr, the address following the instruction.
+fn branch_offset(
+    addr: u16,
+    target: &BranchTarget,
+    labtab: &HashMap<&str, u16>,
+) -> Result<u8, Error> {
+    match target {
+        BranchTarget::Offset(x) => Ok(*x as u8),
+        BranchTarget::Label(x) => {
+            let target_addr = match labtab.get(x.as_str()) {
+                Some(addr) => *addr,
+                None => return Err(Error::LabelNotFound),
+            };
+            let rel16: i16 = target_addr.wrapping_sub(addr) as i16;
+            let rel8: i8 = match rel16.try_into() {
+                Ok(x) => x,
+                Err(_) => {
+                    return Err(Error::RelativeAddressOutOfRange(rel16));
+                }
+            };
+            Ok(rel8 as u8)
+        }
     }
 }
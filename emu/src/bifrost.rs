@@ -0,0 +1,206 @@
+use crate::bus::WINDOWS;
+use crate::device::Device;
+use crate::spi::Spi;
+
+// BIFRÖST decodes 8 address lines, $DE00-$DEFF.
+pub const SIZE: usize = 256;
+
+// Bifrost is the register block of the BIFRÖST FPGA (bifröst/bifrost.v), which also does the
+// address decoding, clock and boot loading.
+//
+// BIFRÖST drives the upper address lines of the 512 KiB RAM, A12-A18, from a bank register for
+// each 4 KiB window of the CPU's address space: BANK0 at $DE20 for $0000-$0FFF up to BANK15 at
+// $DE2F for $F000-$FFFF. They reset to the first 64 KiB, mapped straight through.
+//
+// It also drives 8 BLINKEN LEDs. After animating them in at power-up (blinken.v), they show
+// what LEDS_SRC at $DE01 selects: 0 the LEDS register at $DE00, 1 the data bus, 2 and 3 the
+// address bus low and high bytes, 4 the interrupt lines, and anything else nothing.
+//
+// Its SPI master, with SPI_CS at $DE10 and SPI_DATA at $DE11, is an Spi.
+pub struct Bifrost {
+    leds_reg: u8,                 // BLINKEN LED value
+    leds_src: u8,                 // what the LEDs show
+    animation: Option<Animation>, // the power-up animation, until it's done
+    spi: Spi,
+    banks: [u8; WINDOWS],
+}
+
+// The power-up animation: a light sweeping right from the left, stepping every
+// ANIMATION_PERIOD cycles.
+struct Animation {
+    leds: u8,
+    counter: u32, // cycles into the current step
+}
+
+// What the LEDs can show besides the LEDS register, as BIFRÖST sees it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Signals {
+    pub data: u8,  // the data bus
+    pub addr: u16, // the address bus
+    pub irq: u8,   // the interrupt lines, active low, as in IRQ_LINES
+}
+
+impl Bifrost {
+    pub const SRC_REG: u8 = 0x00;
+    pub const SRC_DATA: u8 = 0x01;
+    pub const SRC_ADDR_LO: u8 = 0x02;
+    pub const SRC_ADDR_HI: u8 = 0x03;
+    pub const SRC_IRQ: u8 = 0x04;
+
+    // The interrupt lines shown by SRC_IRQ, from the leftmost LED: each device's IRQ output,
+    // the UART's per-channel outputs, and the CPU's combined IRQB.
+    pub const IRQ_LINES: [&'static str; 8] = [
+        "VIA1", "VIA2", "UART", "TXAIRQ", "RXAIRQ", "TXBIRQ", "RXBIRQ", "IRQB",
+    ];
+
+    const REG_LEDS: u16 = 0x00;
+    const REG_LEDS_SRC: u16 = 0x01;
+    const REG_SPI_CS: u16 = 0x10;
+    const REG_SPI_DATA: u16 = 0x11;
+    const REG_BANK0: u16 = 0x20;
+    const REG_BANK15: u16 = Self::REG_BANK0 + WINDOWS as u16 - 1;
+    const BANK_MASK: u8 = 0x7F; // 7 bits, A12-A18
+
+    // blinken.v steps when its counter reaches bit 16, then starts again from 0
+    const ANIMATION_PERIOD: u32 = (1 << 16) + 1;
+
+    const BANK_NAMES: [&'static str; WINDOWS] = [
+        "BANK0", "BANK1", "BANK2", "BANK3", "BANK4", "BANK5", "BANK6", "BANK7", "BANK8", "BANK9",
+        "BANK10", "BANK11", "BANK12", "BANK13", "BANK14", "BANK15",
+    ];
+
+    // Each window mapped to the same address in the first 64 KiB.
+    const RESET_BANKS: [u8; WINDOWS] = {
+        let mut banks = [0; WINDOWS];
+        let mut i = 0;
+        while i < WINDOWS {
+            banks[i] = i as u8;
+            i += 1;
+        }
+        banks
+    };
+
+    pub fn new() -> Self {
+        // initial values from bifrost.v; its registers aren't reset with the 6502
+        Self {
+            leds_reg: 0b11000011,
+            leds_src: 0x00,
+            animation: Some(Animation {
+                leds: 0x00,
+                counter: 0,
+            }),
+            spi: Spi::new(),
+            banks: Self::RESET_BANKS,
+        }
+    }
+
+    // What the LEDs show, bit 7 the leftmost, given the signals LEDS_SRC can select.
+    pub fn leds(&self, signals: Signals) -> u8 {
+        if let Some(animation) = &self.animation {
+            return animation.leds;
+        }
+        match self.leds_src {
+            Self::SRC_REG => self.leds_reg,
+            Self::SRC_DATA => signals.data,
+            Self::SRC_ADDR_LO => signals.addr as u8,
+            Self::SRC_ADDR_HI => (signals.addr >> 8) as u8,
+            Self::SRC_IRQ => signals.irq,
+            _ => 0x00,
+        }
+    }
+
+    // The SPI master, e.g. to attach peripherals.
+    pub fn spi(&self) -> &Spi {
+        &self.spi
+    }
+
+    pub fn spi_mut(&mut self) -> &mut Spi {
+        &mut self.spi
+    }
+
+    // Whether the power-up animation is still showing on the LEDs.
+    pub fn animating(&self) -> bool {
+        self.animation.is_some()
+    }
+}
+
+impl Device for Bifrost {
+    fn name(&self) -> &str {
+        "BIFROST"
+    }
+
+    // Only the bank registers are reset with the 6502, so it boots from the same ROM.
+    fn reset(&mut self) {
+        self.banks = Self::RESET_BANKS;
+    }
+
+    fn step(&mut self, cycles: u8) {
+        self.spi.step(cycles);
+        let Some(animation) = &mut self.animation else {
+            return;
+        };
+        animation.counter += cycles as u32;
+        while animation.counter >= Self::ANIMATION_PERIOD {
+            animation.counter -= Self::ANIMATION_PERIOD;
+            if animation.leds == 0x01 {
+                self.animation = None;
+                return;
+            }
+            animation.leds = match animation.leds {
+                0x00 => 0x80,
+                leds => leds >> 1,
+            };
+        }
+    }
+
+    fn read(&mut self, reg: u16) -> u8 {
+        self.peek(reg)
+    }
+
+    fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            Self::REG_LEDS => self.leds_reg = data,
+            Self::REG_LEDS_SRC => self.leds_src = data,
+            Self::REG_SPI_CS => self.spi.set_cs(data),
+            Self::REG_SPI_DATA => self.spi.write_data(data),
+            Self::REG_BANK0..=Self::REG_BANK15 => {
+                self.banks[(reg - Self::REG_BANK0) as usize] = data & Self::BANK_MASK
+            }
+            _ => {}
+        }
+    }
+
+    // BIFRÖST drives the data bus for reads of any of its addresses, with $00 for those that
+    // aren't registers.
+    fn peek(&self, reg: u16) -> u8 {
+        match reg {
+            Self::REG_LEDS => self.leds_reg,
+            Self::REG_LEDS_SRC => self.leds_src,
+            Self::REG_SPI_CS => self.spi.cs(),
+            Self::REG_SPI_DATA => self.spi.data(),
+            Self::REG_BANK0..=Self::REG_BANK15 => self.banks[(reg - Self::REG_BANK0) as usize],
+            _ => 0x00,
+        }
+    }
+
+    fn name_for_read(&self, reg: u16) -> Option<&str> {
+        self.name_for_write(reg)
+    }
+
+    fn name_for_write(&self, reg: u16) -> Option<&str> {
+        match reg {
+            Self::REG_LEDS => Some("LEDS"),
+            Self::REG_LEDS_SRC => Some("LEDS_SRC"),
+            Self::REG_SPI_CS => Some("SPI_CS"),
+            Self::REG_SPI_DATA => Some("SPI_DATA"),
+            Self::REG_BANK0..=Self::REG_BANK15 => {
+                Some(Self::BANK_NAMES[(reg - Self::REG_BANK0) as usize])
+            }
+            _ => None,
+        }
+    }
+
+    fn banks(&self) -> Option<&[u8; WINDOWS]> {
+        Some(&self.banks)
+    }
+}
@@ -1,44 +1,334 @@
+use std::any::Any;
+use std::error;
 use std::fmt;
 use std::ops::RangeInclusive;
 
+use crate::bifrost;
+use crate::bifrost::{Bifrost, Signals};
+use crate::device::Device;
+use crate::sid;
+use crate::sid::Sid;
+use crate::sidlog::{self, SidWrite};
 use crate::uart;
 use crate::uart::Uart;
+use crate::via;
+use crate::via::Via;
 
 const RAM_SIZE: usize = 512 * 1024;
 
-const UART_BASE: u16 = 0xDC20;
-const UART_RANGE: RangeInclusive<u16> = UART_BASE..=(UART_BASE + (uart::SIZE as u16) - 1);
+// The CPU's 64 KiB address space is split into windows, each mapped to a bank of physical RAM.
+pub const WINDOWS: usize = 16;
+pub const WINDOW_SIZE: usize = 0x10000 / WINDOWS;
+
+// The memory map decoded by BIFRÖST (bifröst/adec.v): RAM everywhere except the I/O area.
+const IO_RANGE: RangeInclusive<u16> = 0xD000..=0xDFFF;
+const SID_RANGE: RangeInclusive<u16> = 0xD400..=0xD7FF; // 32 registers, mirrored
+const VIA1_RANGE: RangeInclusive<u16> = 0xDC00..=0xDC0F;
+const VIA2_RANGE: RangeInclusive<u16> = 0xDC10..=0xDC1F;
+const UART_RANGE: RangeInclusive<u16> = 0xDC20..=0xDC2F;
+const BIFROST_RANGE: RangeInclusive<u16> = 0xDE00..=0xDEFF;
 
 // Bus maps memory read/write to different devices based on the address.
 pub struct Bus {
-    ram: [u8; RAM_SIZE],
-    uart: Uart,
+    ram: Box<[u8]>, // on the heap, so a Bus can be moved around without overflowing the stack
+    banks: [u8; WINDOWS], // the RAM bank each window maps to
+    devices: Vec<Mapping>,
+    io: Option<RangeInclusive<u16>>, // where addresses without a device aren't RAM, but float
+    data_bus: u8,                    // the last value on the data bus, read back when floating
+    addr_bus: u16,                   // the last address on the address bus
+
+    regions: Vec<(RangeInclusive<u32>, Protection)>, // protected physical RAM, latest first
+    protected_writes: Vec<ProtectedWrite>,           // not yet taken
+
+    irq: bool, // IRQ asserted from outside the bus devices, e.g. by a test
+    nmi: bool, // NMI asserted from outside the bus devices, e.g. an NMI button
+
+    trace: Option<Vec<Access>>, // accesses recorded since tracing was enabled
+
+    cycles: u64,                       // clock cycles counted by step
+    sid_writes: Option<Vec<SidWrite>>, // captured since capturing was enabled
+}
+
+// A device and the address range it's mapped at.
+struct Mapping {
+    range: RangeInclusive<u16>,
+    registers: usize, // the device's registers repeat across the range every this many bytes
+    device: Box<dyn Device>,
+}
+
+// What an address selects: RAM, a device register, or nothing, leaving the data bus floating.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Select<'a> {
+    Ram,
+    Device { name: &'a str, reg: u16 },
+    OpenBus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BusError {
+    EmptyRange(RangeInclusive<u16>),
+    // range overlaps the range of the already mapped device named name
+    Overlap {
+        range: RangeInclusive<u16>,
+        name: String,
+        existing: RangeInclusive<u16>,
+    },
+    LoadOverflow {
+        addr: u16,
+        len: usize,
+    }, // len bytes at addr would run past $FFFF
+}
+
+// How a region of RAM treats writes from the CPU. Writes that aren't ignored silently are
+// recorded as ProtectedWrite events.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Protection {
+    ReadOnly,     // ROM: writes are dropped, and recorded
+    WriteIgnored, // writes are dropped silently
+    WriteTrap,    // writes go through, and are recorded, e.g. to catch a stray store
+}
+
+// A CPU write to a protected region.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ProtectedWrite {
+    pub addr: u16,
+    pub data: u8,
+    pub protection: Protection,
+}
+
+// A single read or write bus cycle, as recorded while tracing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub addr: u16,
+    pub data: u8,
+    pub kind: AccessKind,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
 }
 
 impl Bus {
     pub fn new() -> Self {
+        let mut bus = Self::new_ram_only();
+        bus.io = Some(IO_RANGE);
+        bus.map_mirrored(SID_RANGE, sid::SIZE, Sid::new()).unwrap();
+        bus.map(VIA1_RANGE, Via::new("VIA1")).unwrap();
+        bus.map(VIA2_RANGE, Via::new("VIA2")).unwrap();
+        bus.map(UART_RANGE, Uart::new()).unwrap();
+        bus.map(BIFROST_RANGE, Bifrost::new()).unwrap();
+        debug_assert_eq!(via::SIZE, VIA1_RANGE.len());
+        debug_assert_eq!(uart::SIZE, UART_RANGE.len());
+        debug_assert_eq!(bifrost::SIZE, BIFROST_RANGE.len());
+        bus
+    }
+
+    // A bus with RAM at every address and no devices mapped, e.g. for CPU test vectors that
+    // use the whole 64 KiB address space as memory.
+    pub fn new_ram_only() -> Self {
         Self {
-            ram: [0x00; RAM_SIZE],
-            uart: Uart::new(),
+            ram: vec![0x00; RAM_SIZE].into_boxed_slice(),
+            banks: std::array::from_fn(|i| i as u8),
+            devices: Vec::new(),
+            io: None,
+            data_bus: 0x00,
+            addr_bus: 0x0000,
+            regions: Vec::new(),
+            protected_writes: Vec::new(),
+            irq: false,
+            nmi: false,
+            trace: None,
+            cycles: 0,
+            sid_writes: None,
+        }
+    }
+
+    // Map device at range, in place of RAM. The range can't overlap any already mapped.
+    pub fn map<D: Device>(
+        &mut self,
+        range: RangeInclusive<u16>,
+        device: D,
+    ) -> Result<(), BusError> {
+        let registers = range.clone().count();
+        self.map_mirrored(range, registers, device)
+    }
+
+    // Map device at range with its registers repeated every registers bytes, for a device that
+    // decodes fewer address lines than the range spans.
+    pub fn map_mirrored<D: Device>(
+        &mut self,
+        range: RangeInclusive<u16>,
+        registers: usize,
+        device: D,
+    ) -> Result<(), BusError> {
+        if range.is_empty() {
+            return Err(BusError::EmptyRange(range));
+        }
+        assert!(registers > 0, "a device needs at least one register");
+        if let Some(m) = self
+            .devices
+            .iter()
+            .find(|m| range.start() <= m.range.end() && m.range.start() <= range.end())
+        {
+            return Err(BusError::Overlap {
+                range,
+                name: m.device.name().to_string(),
+                existing: m.range.clone(),
+            });
+        }
+        self.devices.push(Mapping {
+            range,
+            registers,
+            device: Box::new(device),
+        });
+        Ok(())
+    }
+
+    // The first mapped device of type D.
+    pub fn device<D: Device>(&self) -> Option<&D> {
+        self.devices
+            .iter()
+            .find_map(|m| (m.device.as_ref() as &dyn Any).downcast_ref())
+    }
+
+    pub fn device_mut<D: Device>(&mut self) -> Option<&mut D> {
+        self.devices
+            .iter_mut()
+            .find_map(|m| (m.device.as_mut() as &mut dyn Any).downcast_mut())
+    }
+
+    // The device mapped at addr, and addr's register offset within it.
+    fn decode(&self, addr: u16) -> Option<(usize, u16)> {
+        self.devices
+            .iter()
+            .position(|m| m.range.contains(&addr))
+            .map(|i| {
+                let m = &self.devices[i];
+                (i, ((addr - m.range.start()) as usize % m.registers) as u16)
+            })
+    }
+
+    // Whether addr is in the I/O area but no device is mapped there.
+    fn is_open(&self, addr: u16) -> bool {
+        self.io.as_ref().is_some_and(|io| io.contains(&addr))
+    }
+
+    // What addr selects, as the address decoder sees it.
+    pub fn select(&self, addr: u16) -> Select<'_> {
+        match self.decode(addr) {
+            Some((i, reg)) => Select::Device {
+                name: self.devices[i].device.name(),
+                reg,
+            },
+            None if self.is_open(addr) => Select::OpenBus,
+            None => Select::Ram,
         }
     }
 
     pub fn reset(&mut self) {
-        self.uart.reset();
+        for m in self.devices.iter_mut() {
+            m.device.reset();
+        }
+        for i in 0..self.devices.len() {
+            self.update_banks(i);
+        }
     }
 
-    pub fn step(&mut self) {
-        self.uart.step();
+    // Take the RAM banks from device i, if it controls them.
+    fn update_banks(&mut self, i: usize) {
+        if let Some(banks) = self.devices[i].device.banks() {
+            self.banks = *banks;
+        }
+    }
+
+    // The physical RAM address that addr maps to.
+    fn ram_addr(&self, addr: u16) -> usize {
+        let bank = self.banks[addr as usize / WINDOW_SIZE] as usize;
+        (bank * WINDOW_SIZE + addr as usize % WINDOW_SIZE) % RAM_SIZE
+    }
+
+    // The physical RAM address addr reaches through its window, or None if addr selects a
+    // device or nothing at all.
+    pub fn physical(&self, addr: u16) -> Option<u32> {
+        match self.select(addr) {
+            Select::Ram => Some(self.ram_addr(addr) as u32),
+            _ => None,
+        }
+    }
+
+    // Read RAM by physical address, whether or not it's mapped into a window.
+    pub fn read_physical(&self, addr: u32) -> u8 {
+        self.ram[addr as usize % RAM_SIZE]
+    }
+
+    pub fn write_physical(&mut self, addr: u32, data: u8) {
+        self.ram[addr as usize % RAM_SIZE] = data;
+    }
+
+    // Advance devices by the given number of clock cycles.
+    pub fn step(&mut self, cycles: u8) {
+        for m in self.devices.iter_mut() {
+            m.device.step(cycles);
+        }
+        self.cycles += cycles as u64;
+    }
+
+    // Advance devices by more clock cycles than step takes at once, each in a single call.
+    pub fn step_cycles(&mut self, cycles: u32) {
+        for m in self.devices.iter_mut() {
+            m.device.step_cycles(cycles);
+        }
+        self.cycles += cycles as u64;
+    }
+
+    // At the earliest, how many cycles from now a device asserts IRQ or NMI by itself, if any
+    // will.
+    pub fn next_interrupt(&self) -> Option<u32> {
+        self.devices
+            .iter()
+            .filter_map(|m| m.device.next_interrupt())
+            .min()
+    }
+
+    // Clock cycles counted by step, which time captured SID writes.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // Set the clock, e.g. to keep it in step with the system's across a reset.
+    pub fn set_cycles(&mut self, cycles: u64) {
+        self.cycles = cycles;
     }
 
     pub fn read(&mut self, addr: u16) -> u8 {
-        match addr {
-            0xD41B => fastrand::u8(0..255),
-            addr if UART_RANGE.contains(&addr) => self.uart.read((addr - UART_BASE) as u8),
-            _ => self.ram[addr as usize],
+        let data = match self.decode(addr) {
+            Some((i, reg)) => self.devices[i].device.read(reg),
+            None if self.is_open(addr) => self.data_bus,
+            None => self.ram[self.ram_addr(addr)],
+        };
+        self.data_bus = data;
+        self.addr_bus = addr;
+        self.record(addr, data, AccessKind::Read);
+        data
+    }
+
+    // Read without side effects on devices, e.g. for the monitor.
+    pub fn peek(&self, addr: u16) -> u8 {
+        match self.decode(addr) {
+            Some((i, reg)) => self.devices[i].device.peek(reg),
+            None if self.is_open(addr) => self.data_bus,
+            None => self.ram[self.ram_addr(addr)],
         }
     }
 
+    pub fn peek_u16(&self, addr: u16) -> u16 {
+        let lo = self.peek(addr) as u16;
+        let hi = self.peek(addr.wrapping_add(1)) as u16;
+        hi << 8 | lo
+    }
+
     /// Read a u16 in little-endian order from the bus, crossing page boundaries.
     pub fn read_u16(&mut self, addr: u16) -> u16 {
         let lo = self.read(addr) as u16;
@@ -47,43 +337,233 @@ impl Bus {
     }
 
     pub fn write(&mut self, addr: u16, data: u8) {
-        match addr {
-            addr if UART_RANGE.contains(&addr) => self.uart.write((addr - UART_BASE) as u8, data),
-            _ => self.ram[addr as usize] = data,
+        self.write_checked(addr, data, true);
+    }
+
+    fn write_checked(&mut self, addr: u16, data: u8, check_protection: bool) {
+        match self.decode(addr) {
+            Some((i, reg)) => {
+                if let Some(writes) = self.sid_writes.as_mut() {
+                    let device = self.devices[i].device.as_ref() as &dyn Any;
+                    if device.is::<Sid>() && (reg as usize) < sidlog::REGS {
+                        writes.push(SidWrite {
+                            cycle: self.cycles,
+                            reg: reg as u8,
+                            data,
+                        });
+                    }
+                }
+                self.devices[i].device.write(reg, data);
+                self.update_banks(i);
+            }
+            None if self.is_open(addr) => {}
+            None => match self.protection(addr).filter(|_| check_protection) {
+                None => self.ram[self.ram_addr(addr)] = data,
+                Some(Protection::WriteIgnored) => {}
+                Some(protection) => {
+                    if protection == Protection::WriteTrap {
+                        self.ram[self.ram_addr(addr)] = data;
+                    }
+                    self.protected_writes.push(ProtectedWrite {
+                        addr,
+                        data,
+                        protection,
+                    });
+                }
+            },
         };
+        self.data_bus = data;
+        self.addr_bus = addr;
+        self.record(addr, data, AccessKind::Write);
+    }
+
+    // Protect the RAM at range from CPU writes, in place of any protection already there. The
+    // range is resolved through the windows as they're mapped now, so the protection stays with
+    // the physical RAM when banks are switched.
+    pub fn protect(&mut self, range: RangeInclusive<u16>, protection: Protection) {
+        let (mut start, end) = (*range.start() as usize, *range.end() as usize);
+        while start <= end {
+            let window_end = (start | (WINDOW_SIZE - 1)).min(end);
+            let physical = self.ram_addr(start as u16) as u32;
+            let len = (window_end - start) as u32;
+            self.regions
+                .insert(0, (physical..=physical + len, protection));
+            start = window_end + 1;
+        }
+    }
+
+    // How the RAM that addr maps to is protected, if it is.
+    pub fn protection(&self, addr: u16) -> Option<Protection> {
+        let physical = self.ram_addr(addr) as u32;
+        self.regions
+            .iter()
+            .find(|(range, _)| range.contains(&physical))
+            .map(|(_, protection)| *protection)
+    }
+
+    // Take the protected writes made since last time, oldest first.
+    pub fn take_protected_writes(&mut self) -> Vec<ProtectedWrite> {
+        std::mem::take(&mut self.protected_writes)
+    }
+
+    // Capture writes to the SID's registers, timed by the clock, or stop capturing.
+    pub fn set_sid_capture(&mut self, enabled: bool) {
+        self.sid_writes = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    // Take the SID writes captured since last time, oldest first.
+    pub fn take_sid_writes(&mut self) -> Vec<SidWrite> {
+        self.sid_writes
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
     }
 
+    // Start or stop recording every read and write, e.g. to compare a CPU's bus cycles
+    // against hardware. Starting discards anything previously recorded.
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.trace = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    // Take the accesses recorded so far, leaving tracing enabled.
+    pub fn take_trace(&mut self) -> Vec<Access> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn record(&mut self, addr: u16, data: u8, kind: AccessKind) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(Access { addr, data, kind });
+        }
+    }
+
+    // Whether any source is asserting the (active low, level-triggered) IRQ line.
     pub fn is_interrupt(&self) -> bool {
-        self.uart.is_interrupt()
+        self.irq || self.devices.iter().any(|m| m.device.irq())
     }
 
-    // load is a convenience method to bulk-write data to RAM
-    pub fn load(&mut self, addr: u16, data: Vec<u8>) {
+    // What BIFRÖST's BLINKEN LEDs show, bit 7 the leftmost, or None without BIFRÖST.
+    pub fn leds(&self) -> Option<u8> {
+        // the lines are wired to the devices at these addresses, whatever they're called
+        let irq = |range: RangeInclusive<u16>| {
+            self.decode(*range.start())
+                .is_some_and(|(i, _)| self.devices[i].device.irq())
+        };
+        // the UART's per-channel lines aren't emulated, so they stay released (high)
+        let lines = [
+            irq(VIA1_RANGE),
+            irq(VIA2_RANGE),
+            irq(UART_RANGE),
+            false,
+            false,
+            false,
+            false,
+        ];
+        let mut irqs = lines.iter().fold(0, |irqs, &line| irqs << 1 | !line as u8) << 1;
+        irqs |= !self.is_interrupt() as u8;
+        let signals = Signals {
+            data: self.data_bus,
+            addr: self.addr_bus,
+            irq: irqs,
+        };
+        self.device::<Bifrost>().map(|b| b.leds(signals))
+    }
+
+    // Assert or release IRQ from outside the bus devices.
+    pub fn set_irq(&mut self, asserted: bool) {
+        self.irq = asserted;
+    }
+
+    // Whether any source is asserting the (active low, edge-triggered) NMI line.
+    pub fn is_nmi(&self) -> bool {
+        self.nmi || self.devices.iter().any(|m| m.device.nmi())
+    }
+
+    // Assert or release NMI from outside the bus devices, e.g. an NMI button.
+    pub fn set_nmi(&mut self, asserted: bool) {
+        self.nmi = asserted;
+    }
+
+    // load is a convenience method to bulk-write data to RAM, including protected RAM
+    pub fn load(&mut self, addr: u16, data: Vec<u8>) -> Result<(), BusError> {
+        if addr as usize + data.len() > 0x10000 {
+            return Err(BusError::LoadOverflow {
+                addr,
+                len: data.len(),
+            });
+        }
         for (i, byte) in data.iter().enumerate() {
-            self.write(addr + (i as u16), *byte);
+            self.write_checked(addr + (i as u16), *byte, false);
         }
+        Ok(())
     }
 
-    pub fn name_for_read(&mut self, addr: u16) -> String {
-        match addr {
-            addr if UART_RANGE.contains(&addr) => self.uart.name_for_read((addr - UART_BASE) as u8),
-            _ => format!("#${:02X}", self.read(addr)),
+    // What a read of addr would see: a device register's name, or the data in memory.
+    pub fn name_for_read(&self, addr: u16) -> String {
+        match self.decode(addr) {
+            Some((i, reg)) => {
+                let device = &self.devices[i].device;
+                match device.name_for_read(reg) {
+                    Some(name) => format!("{}:{name}", device.name()),
+                    None => format!("{}:${reg:02X}", device.name()),
+                }
+            }
+            None => format!("#${:02X}", self.peek(addr)),
         }
     }
 
-    #[allow(unused)]
+    // The name of the device register a write to addr would reach, if any.
     pub fn name_for_write(&self, addr: u16) -> String {
-        match addr {
-            addr if UART_RANGE.contains(&addr) => {
-                self.uart.name_for_write((addr - UART_BASE) as u8)
+        match self.decode(addr) {
+            Some((i, reg)) => {
+                let device = &self.devices[i].device;
+                match device.name_for_write(reg) {
+                    Some(name) => format!("{}:{name}", device.name()),
+                    None => format!("{}:${reg:02X}", device.name()),
+                }
             }
-            _ => "".to_string(),
+            None => "".to_string(),
         }
     }
 }
 
 impl fmt::Debug for Bus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("Bus {{ RAM: {} KiB }}", RAM_SIZE / 1024))
+        f.write_fmt(format_args!("Bus {{ RAM: {} KiB", RAM_SIZE / 1024))?;
+        for m in &self.devices {
+            f.write_fmt(format_args!(
+                ", {}: ${:04X}-${:04X}",
+                m.device.name(),
+                m.range.start(),
+                m.range.end()
+            ))?;
+        }
+        f.write_str(" }")
     }
 }
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusError::EmptyRange(range) => {
+                write!(f, "empty range ${:04X}-${:04X}", range.start(), range.end())
+            }
+            BusError::Overlap {
+                range,
+                name,
+                existing,
+            } => write!(
+                f,
+                "${:04X}-${:04X} overlaps {name} at ${:04X}-${:04X}",
+                range.start(),
+                range.end(),
+                existing.start(),
+                existing.end()
+            ),
+            BusError::LoadOverflow { addr, len } => {
+                write!(f, "{len} bytes at ${addr:04X} run past $FFFF")
+            }
+        }
+    }
+}
+
+impl error::Error for BusError {}
@@ -1,10 +1,11 @@
+use std::error;
 use std::fmt;
 
 use crate::bus;
 use crate::dec;
 use crate::isa;
 
-// A 65C02-like CPU
+// A 6502-family CPU; the W65C02S by default, or one of its predecessors (see isa::Variant).
 pub struct Cpu {
     pub pc: u16, // program counter
     pub s: u8,   // stack pointer
@@ -13,11 +14,56 @@ pub struct Cpu {
     pub y: u8,   // Y register
     pub p: u8,   // processor status
 
+    pub state: RunState,
+
+    // In strict mode, reserved opcodes are reported as errors rather than executed as NOPs.
+    pub strict: bool,
+
+    // In cycle-accurate mode, the CPU also makes the dummy reads and writes that fill out each
+    // instruction's cycles on real hardware, so that I/O side effects match it.
+    pub cycle_accurate: bool,
+
+    variant: isa::Variant,
     decoder: dec::Decoder,
+
+    // cycles consumed by the current instruction beyond its opcode's base cycle count
+    extra_cycles: u8,
+    // whether the current instruction's indexed operand crossed a page boundary
+    page_crossed: bool,
+    // whether the current instruction spends a cycle fixing up an indexed address even
+    // without a page cross, as stores and read-modify-write instructions do
+    fixup_always: bool,
+    // address of the current instruction's operand value, re-read by the 65C02's decimal cycle
+    operand_addr: u16,
+
+    // NMIB level seen at the last set_nmi(), and whether its falling edge awaits service
+    nmi_line: bool,
+    nmi_pending: bool,
+
+    // RDY level: pulled low, it stalls the CPU until released
+    rdy: bool,
+    // SOB level seen at the last set_so(); its falling edge sets V
+    so_line: bool,
+
+    // The I flag from before the last instruction, if that was CLI, SEI or PLP. IRQ is polled
+    // before those instructions update I, so their effect is delayed by one instruction.
+    irq_mask_delayed: Option<bool>,
+}
+
+// Whether the CPU is executing instructions, or has been paused by WAI or STP.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RunState {
+    Running,
+    Waiting, // WAI: paused until an interrupt
+    Stopped, // STP: paused until reset
 }
 
 impl Cpu {
     pub fn new() -> Cpu {
+        Self::with_variant(isa::Variant::default())
+    }
+
+    pub fn with_variant(variant: isa::Variant) -> Cpu {
         Cpu {
             pc: 0,
             s: 0,
@@ -25,10 +71,27 @@ impl Cpu {
             x: 0,
             y: 0,
             p: 0,
-            decoder: dec::Decoder::new(),
+            state: RunState::Running,
+            strict: false,
+            cycle_accurate: false,
+            variant,
+            decoder: dec::Decoder::new(variant),
+            extra_cycles: 0,
+            page_crossed: false,
+            fixup_always: false,
+            operand_addr: 0,
+            nmi_line: false,
+            nmi_pending: false,
+            rdy: true,
+            so_line: false,
+            irq_mask_delayed: None,
         }
     }
 
+    pub fn variant(&self) -> isa::Variant {
+        self.variant
+    }
+
     // Reset internal CPU state, as if the reset line had been asserted.
     pub fn reset(&mut self, bus: &mut bus::Bus) {
         self.pc = bus.read_u16(VEC_RES);
@@ -37,29 +100,145 @@ impl Cpu {
         self.x = 0x00;
         self.y = 0x00;
         self.p = 0b00110100; // W65C02S manual §3.1 Reset says xx1101xx
+        self.state = RunState::Running;
+        self.nmi_pending = false;
+        self.irq_mask_delayed = None;
     }
 
-    pub fn interrupt(&mut self, bus: &mut bus::Bus) {
-        if self.get_p_bit(StatusMask::Interrupt) {
-            return;
+    // Update the level of the NMI line. NMI is edge-triggered: only the transition to
+    // asserted latches an interrupt, which stays pending until serviced by nmi().
+    pub fn set_nmi(&mut self, asserted: bool) {
+        if asserted && !self.nmi_line {
+            self.nmi_pending = true;
         }
-        // TODO: check this is the right order
-        self.push_addr(bus, self.pc);
-        self.push(bus, self.p);
+        self.nmi_line = asserted;
+    }
+
+    // Update the level of the RDY line. While it's pulled low the CPU stalls: steps take a
+    // single cycle without executing anything, and interrupts wait until it's released.
+    pub fn set_rdy(&mut self, ready: bool) {
+        self.rdy = ready;
+    }
+
+    // Update the level of the SOB (set overflow) line. The transition to asserted sets V.
+    pub fn set_so(&mut self, asserted: bool) {
+        if asserted && !self.so_line {
+            self.set_p_bit(StatusMask::Overflow, true);
+        }
+        self.so_line = asserted;
+    }
+
+    // Service a pending NMI, returning the number of cycles taken to enter the handler (if any).
+    // NMI can't be disabled, and takes priority over IRQ; call it before interrupt().
+    pub fn nmi(&mut self, bus: &mut bus::Bus) -> u8 {
+        if !self.nmi_pending || !self.rdy || self.state == RunState::Stopped {
+            return 0;
+        }
+        self.nmi_pending = false;
+        self.state = RunState::Running;
+        self.dummy_read(bus, self.pc);
+        self.dummy_read(bus, self.pc);
+        self.enter_interrupt(bus, self.pc, false, VEC_NMI);
+        7
+    }
+
+    // Service an IRQ, returning the number of cycles taken to enter the handler (if any).
+    pub fn interrupt(&mut self, bus: &mut bus::Bus) -> u8 {
+        if !self.rdy || self.state == RunState::Stopped {
+            return 0;
+        }
+        // An IRQ ends WAI even while interrupts are disabled; execution then
+        // continues with the instruction following WAI, per the W65C02S datasheet.
+        if self.state == RunState::Waiting {
+            self.state = RunState::Running;
+        }
+        let masked = self
+            .irq_mask_delayed
+            .unwrap_or(self.get_p_bit(StatusMask::Interrupt));
+        if masked {
+            return 0;
+        }
+        self.dummy_read(bus, self.pc);
+        self.dummy_read(bus, self.pc);
+        self.enter_interrupt(bus, self.pc, false, VEC_IRQ);
+        7
+    }
+
+    // The interrupt sequence shared by BRK, IRQ and NMI: push the return address and status,
+    // then disable interrupts and jump through the vector. The pushed status has the unused
+    // bit 5 set, and B set only for BRK, which is how a handler tells BRK from IRQ.
+    // The 65C02 also clears decimal mode for the handler; the NMOS 6502 leaves it alone.
+    fn enter_interrupt(&mut self, bus: &mut bus::Bus, ret: u16, brk: bool, vector: u16) {
+        self.push_addr(bus, ret);
+        let mut p = self.p | 1 << 5;
+        if brk {
+            p |= StatusMask::Break as u8;
+        } else {
+            p &= !(StatusMask::Break as u8);
+        }
+        self.push(bus, p);
         self.set_p_bit(StatusMask::Interrupt, true);
-        self.pc = bus.read_u16(VEC_IRQ);
+        if self.variant != isa::Variant::Nmos6502 {
+            self.set_p_bit(StatusMask::Decimal, false);
+        }
+        self.irq_mask_delayed = None;
+        self.pc = bus.read_u16(vector);
     }
 
-    // Load and execute a single instruction.
-    pub fn step(&mut self, bus: &mut bus::Bus) {
-        match self.decoder.opcode(bus.read(self.pc)) {
-            None => panic!("illegal opcode"),
-            Some(opcode) => self.execute(opcode, bus),
+    // Load and execute a single instruction, returning what was executed and the number of
+    // cycles it took. While paused by WAI or STP, or stalled by RDY, the clock keeps running but
+    // nothing executes, so each step takes a single cycle. An instruction that can't be
+    // executed is returned as an error, leaving PC pointing at it.
+    pub fn step(&mut self, bus: &mut bus::Bus) -> Result<StepInfo, CpuError> {
+        let pc = self.pc;
+        if self.state != RunState::Running || !self.rdy {
+            return Ok(StepInfo {
+                pc,
+                opcode: None,
+                cycles: 1,
+            });
+        }
+        self.extra_cycles = 0;
+        self.page_crossed = false;
+        let irq_mask = self.get_p_bit(StatusMask::Interrupt);
+        self.irq_mask_delayed = None;
+        let code = bus.read(self.pc);
+        if self.strict && self.decoder.is_reserved(code) {
+            return Err(CpuError::UndefinedOpcode { pc, code });
+        }
+        match self.decoder.opcode(code) {
+            None => Err(CpuError::UndefinedOpcode { pc, code }),
+            Some(opcode) => {
+                self.fixup_always = !opcode.page_cross_penalty(self.variant);
+                if let Err(IllegalMode) = self.execute(opcode, bus) {
+                    self.pc = pc;
+                    return Err(CpuError::IllegalAddressMode {
+                        pc,
+                        code,
+                        mnemonic: opcode.mnemonic,
+                        mode: opcode.mode,
+                    });
+                }
+                if matches!(
+                    opcode.mnemonic,
+                    isa::Mnemonic::Cli | isa::Mnemonic::Sei | isa::Mnemonic::Plp
+                ) {
+                    self.irq_mask_delayed = Some(irq_mask);
+                }
+                if self.page_crossed && opcode.page_cross_penalty(self.variant) {
+                    self.extra_cycles += 1;
+                }
+                Ok(StepInfo {
+                    pc,
+                    opcode: Some(opcode),
+                    cycles: opcode.cycles + self.extra_cycles,
+                })
+            }
         }
     }
 
     // Execute an instruction, reading the operands for its address mode from the bus.
-    fn execute(&mut self, opcode: isa::Opcode, bus: &mut bus::Bus) {
+    fn execute(&mut self, opcode: isa::Opcode, bus: &mut bus::Bus) -> Result<(), IllegalMode> {
         use isa::AddressMode::*;
         use isa::Mnemonic as M;
         use isa::OpValue;
@@ -67,35 +246,40 @@ impl Cpu {
         // progress PC for the fetched opcode
         self.pc = self.pc.wrapping_add(1);
 
+        // single-byte instructions spend their second cycle reading the following byte
+        if matches!(opcode.mode, Implied | Accumulator) && opcode.cycles >= 2 {
+            self.dummy_read(bus, self.pc);
+        }
+
         match opcode.mnemonic {
             M::Adc => {
-                let a = self.a;
-                let b = self.read_operand_value(bus, opcode);
-                let sum16 = (self.carry() as u16) + (a as u16) + (b as u16);
-                let sum = sum16 as u8;
-                self.a = sum;
-                self.update_p_z_n(sum);
-                self.set_p_bit(StatusMask::Carry, sum < a);
-
-                // Whether the sign of `a` and `sum` differs AND the sign of `b` and `sum` differs.
-                // This implies that `a` and `b` are same-sign, but `sum` is other-sign: overflow.
-                //
-                // a b s | a^s b^s | & | !=0 | Overflow?
-                // -------------------------------------
-                // 0 0 0 |  0   0  | 0 |  0  | no
-                // 0 0 1 |  1   1  | 1 |  1  | yes
-                // 0 1 0 |  0   1  | 0 |  0  | no
-                // 0 1 1 |  1   0  | 0 |  0  | no
-                // 1 0 0 |  1   0  | 0 |  0  | no
-                // 1 0 1 |  0   1  | 0 |  0  | no
-                // 1 1 0 |  1   1  | 1 |  1  | yes
-                // 1 1 1 |  0   0  | 0 |  0  | no
-                self.set_p_bit(StatusMask::Overflow, ((a ^ sum) & (b ^ sum)) >> 7 != 0);
+                let b = self.read_operand_value(bus, opcode)?;
+                self.adc(bus, b);
+            }
+            M::Alr => {
+                let before = self.a & self.read_operand_value(bus, opcode)?;
+                self.a = before >> 1;
+                self.update_p_z_n(self.a);
+                self.set_p_bit(StatusMask::Carry, before & 1 == 1);
+            }
+            M::Anc => {
+                self.a &= self.read_operand_value(bus, opcode)?;
+                self.update_p_z_n(self.a);
+                self.set_p_bit(StatusMask::Carry, self.a & 0x80 != 0);
             }
             M::And => {
-                self.a &= self.read_operand_value(bus, opcode);
+                self.a &= self.read_operand_value(bus, opcode)?;
+                self.update_p_z_n(self.a);
+            }
+            M::Ane => {
+                // unstable; this uses the "magic" constant most commonly seen on real chips
+                self.a = (self.a | 0xEE) & self.x & self.read_operand_value(bus, opcode)?;
                 self.update_p_z_n(self.a);
             }
+            M::Arr => {
+                let b = self.read_operand_value(bus, opcode)?;
+                self.arr(b);
+            }
             M::Asl => {
                 let result: u8;
                 let carry: u8;
@@ -107,211 +291,280 @@ impl Cpu {
                     }
                     OpValue::U16(addr) => {
                         let x = bus.read(addr);
+                        self.rmw_dummy(bus, addr, x);
                         result = x << 1;
                         carry = x >> 7;
                         bus.write(addr, result);
                     }
-                    _ => panic!("illegal AddressMode: {:?}", opcode),
+                    _ => return Err(IllegalMode),
                 }
                 self.update_p_z_n(result);
                 self.set_p_bit(StatusMask::Carry, carry == 1);
             }
+            M::Bbr0 | M::Bbr1 | M::Bbr2 | M::Bbr3 | M::Bbr4 | M::Bbr5 | M::Bbr6 | M::Bbr7 => {
+                match self.read_operand(bus, opcode.mode) {
+                    OpValue::U16(addr) => {
+                        let set = bus.read(addr) & bit_mask(opcode) != 0;
+                        self.dummy_read(bus, addr);
+                        match self.read_operand(bus, Relative) {
+                            OpValue::U16(target) if !set => self.branch(bus, target),
+                            _ => {}
+                        }
+                    }
+                    _ => return Err(IllegalMode),
+                }
+            }
+            M::Bbs0 | M::Bbs1 | M::Bbs2 | M::Bbs3 | M::Bbs4 | M::Bbs5 | M::Bbs6 | M::Bbs7 => {
+                match self.read_operand(bus, opcode.mode) {
+                    OpValue::U16(addr) => {
+                        let set = bus.read(addr) & bit_mask(opcode) != 0;
+                        self.dummy_read(bus, addr);
+                        match self.read_operand(bus, Relative) {
+                            OpValue::U16(target) if set => self.branch(bus, target),
+                            _ => {}
+                        }
+                    }
+                    _ => return Err(IllegalMode),
+                }
+            }
             M::Bcc => {
                 if !self.get_p_bit(StatusMask::Carry) {
                     match self.read_operand(bus, opcode.mode) {
-                        OpValue::U16(addr) => self.pc = addr,
-                        _ => panic!("illegal AddressMode: {:?}", opcode),
+                        OpValue::U16(addr) => self.branch(bus, addr),
+                        _ => return Err(IllegalMode),
                     }
                 } else {
-                    self.pc += 1; // skip operand
+                    self.read_pc_u8(bus); // skip operand
                 }
             }
             M::Bcs => {
                 if self.get_p_bit(StatusMask::Carry) {
                     match self.read_operand(bus, opcode.mode) {
-                        OpValue::U16(addr) => self.pc = addr,
-                        _ => panic!("illegal AddressMode: {:?}", opcode),
+                        OpValue::U16(addr) => self.branch(bus, addr),
+                        _ => return Err(IllegalMode),
                     }
                 } else {
-                    self.pc += 1; // skip operand
+                    self.read_pc_u8(bus); // skip operand
                 }
             }
             M::Beq => {
                 if self.get_p_bit(StatusMask::Zero) {
                     match self.read_operand(bus, opcode.mode) {
-                        OpValue::U16(addr) => self.pc = addr,
-                        _ => panic!("illegal AddressMode: {:?}", opcode),
+                        OpValue::U16(addr) => self.branch(bus, addr),
+                        _ => return Err(IllegalMode),
                     }
                 } else {
-                    self.pc += 1; // skip operand
+                    self.read_pc_u8(bus); // skip operand
                 }
             }
             M::Bit => {
-                let operand = self.read_operand_value(bus, opcode);
-                let r = self.a & operand;
-                self.set_p_bit(StatusMask::Negative, r & StatusMask::Negative as u8 != 0);
-                self.set_p_bit(StatusMask::Overflow, r & StatusMask::Overflow as u8 != 0);
-                self.set_p_bit(StatusMask::Zero, r == 0);
+                let operand = self.read_operand_value(bus, opcode)?;
+                // N and V are copied from the operand, except for BIT #imm which only affects Z.
+                if opcode.mode != Immediate {
+                    self.set_p_bit(
+                        StatusMask::Negative,
+                        operand & StatusMask::Negative as u8 != 0,
+                    );
+                    self.set_p_bit(
+                        StatusMask::Overflow,
+                        operand & StatusMask::Overflow as u8 != 0,
+                    );
+                }
+                self.set_p_bit(StatusMask::Zero, self.a & operand == 0);
             }
             M::Bmi => {
                 if self.get_p_bit(StatusMask::Negative) {
                     match self.read_operand(bus, opcode.mode) {
-                        OpValue::U16(addr) => self.pc = addr,
-                        _ => panic!("illegal AddressMode: {:?}", opcode),
+                        OpValue::U16(addr) => self.branch(bus, addr),
+                        _ => return Err(IllegalMode),
                     }
                 } else {
-                    self.pc += 1; // skip operand
+                    self.read_pc_u8(bus); // skip operand
                 }
             }
             M::Bne => {
                 if !self.get_p_bit(StatusMask::Zero) {
                     match self.read_operand(bus, opcode.mode) {
-                        OpValue::U16(addr) => self.pc = addr,
-                        _ => panic!("illegal AddressMode: {:?}", opcode),
+                        OpValue::U16(addr) => self.branch(bus, addr),
+                        _ => return Err(IllegalMode),
                     }
                 } else {
-                    self.pc += 1; // skip operand
+                    self.read_pc_u8(bus); // skip operand
                 }
             }
             M::Bpl => {
                 if !self.get_p_bit(StatusMask::Negative) {
                     match self.read_operand(bus, opcode.mode) {
-                        OpValue::U16(addr) => self.pc = addr,
-                        _ => panic!("illegal AddressMode: {:?}", opcode),
+                        OpValue::U16(addr) => self.branch(bus, addr),
+                        _ => return Err(IllegalMode),
                     }
                 } else {
-                    self.pc += 1; // skip operand
+                    self.read_pc_u8(bus); // skip operand
                 }
             }
+            M::Bra => match self.read_operand(bus, opcode.mode) {
+                OpValue::U16(addr) => self.branch(bus, addr),
+                _ => return Err(IllegalMode),
+            },
             M::Brk => match opcode.mode {
-                Implied => {
-                    self.push_addr(bus, self.pc + 1);
-                    self.push(bus, self.p | StatusMask::Break as u8);
-                    self.p |= StatusMask::Interrupt as u8;
-                    self.pc = bus.read_u16(VEC_IRQ);
-                }
-                _ => panic!("illegal AddressMode: {opcode:?}"),
+                // the byte following BRK is skipped, and can identify the reason for the break
+                Implied => self.enter_interrupt(bus, self.pc.wrapping_add(1), true, VEC_IRQ),
+                _ => return Err(IllegalMode),
             },
             M::Bvc => {
                 if !self.get_p_bit(StatusMask::Overflow) {
                     match self.read_operand(bus, opcode.mode) {
-                        OpValue::U16(addr) => self.pc = addr,
-                        _ => panic!("illegal AddressMode: {:?}", opcode),
+                        OpValue::U16(addr) => self.branch(bus, addr),
+                        _ => return Err(IllegalMode),
                     }
                 } else {
-                    self.pc += 1; // skip operand
+                    self.read_pc_u8(bus); // skip operand
                 }
             }
             M::Bvs => {
                 if self.get_p_bit(StatusMask::Overflow) {
                     match self.read_operand(bus, opcode.mode) {
-                        OpValue::U16(addr) => self.pc = addr,
-                        _ => panic!("illegal AddressMode: {:?}", opcode),
+                        OpValue::U16(addr) => self.branch(bus, addr),
+                        _ => return Err(IllegalMode),
                     }
                 } else {
-                    self.pc += 1; // skip operand
+                    self.read_pc_u8(bus); // skip operand
                 }
             }
             M::Clc => match opcode.mode {
                 Implied => self.set_p_bit(StatusMask::Carry, false),
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
             M::Cld => match opcode.mode {
                 Implied => self.set_p_bit(StatusMask::Decimal, false),
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
             M::Cli => match opcode.mode {
                 Implied => self.set_p_bit(StatusMask::Interrupt, false),
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
             M::Clv => match opcode.mode {
                 Implied => self.set_p_bit(StatusMask::Overflow, false),
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
             M::Cmp => {
-                let result = self.a.wrapping_sub(self.read_operand_value(bus, opcode));
-                self.update_p_z_n(result);
-                self.set_p_bit(StatusMask::Carry, result < self.a);
+                let b = self.read_operand_value(bus, opcode)?;
+                self.compare(self.a, b);
             }
             M::Cpx => {
-                let result = self.x.wrapping_sub(self.read_operand_value(bus, opcode));
-                self.update_p_z_n(result);
-                self.set_p_bit(StatusMask::Carry, result > self.x);
+                let b = self.read_operand_value(bus, opcode)?;
+                self.compare(self.x, b);
             }
             M::Cpy => {
-                let result = self.y.wrapping_sub(self.read_operand_value(bus, opcode));
-                self.update_p_z_n(result);
-                self.set_p_bit(StatusMask::Carry, result > self.y);
+                let b = self.read_operand_value(bus, opcode)?;
+                self.compare(self.y, b);
+            }
+            M::Dcp => {
+                let result = self.modify(bus, opcode, |_, val| val.wrapping_sub(1))?;
+                self.compare(self.a, result);
             }
             M::Dec => match self.read_operand(bus, opcode.mode) {
+                OpValue::None => {
+                    self.a = self.a.wrapping_sub(1);
+                    self.update_p_z_n(self.a);
+                }
                 OpValue::U16(addr) => {
-                    let result = bus.read(addr).wrapping_sub(1);
+                    let val = bus.read(addr);
+                    self.rmw_dummy(bus, addr, val);
+                    let result = val.wrapping_sub(1);
                     bus.write(addr, result);
                     self.update_p_z_n(result);
                 }
-                _ => panic!("illegal AddressMode: {opcode:?}"),
+                _ => return Err(IllegalMode),
             },
             M::Dex => match opcode.mode {
                 Implied => {
                     self.x = self.x.wrapping_sub(1);
                     self.update_p_z_n(self.x);
                 }
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
             M::Dey => match opcode.mode {
                 Implied => {
                     self.y = self.y.wrapping_sub(1);
                     self.update_p_z_n(self.y);
                 }
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
             M::Eor => {
-                self.a ^= self.read_operand_value(bus, opcode);
+                self.a ^= self.read_operand_value(bus, opcode)?;
                 self.update_p_z_n(self.a);
             }
             M::Inc => match self.read_operand(bus, opcode.mode) {
+                OpValue::None => {
+                    self.a = self.a.wrapping_add(1);
+                    self.update_p_z_n(self.a);
+                }
                 OpValue::U16(addr) => {
-                    let result = bus.read(addr).wrapping_add(1);
+                    let val = bus.read(addr);
+                    self.rmw_dummy(bus, addr, val);
+                    let result = val.wrapping_add(1);
                     bus.write(addr, result);
                     self.update_p_z_n(result);
                 }
-                _ => panic!("illegal AddressMode: {opcode:?}"),
+                _ => return Err(IllegalMode),
             },
+            M::Isc => {
+                let result = self.modify(bus, opcode, |_, val| val.wrapping_add(1))?;
+                self.sbc(bus, result);
+            }
             M::Inx => match opcode.mode {
                 Implied => {
                     self.x = self.x.wrapping_add(1);
                     self.update_p_z_n(self.x);
                 }
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
             M::Iny => match opcode.mode {
                 Implied => {
                     self.y = self.y.wrapping_add(1);
                     self.update_p_z_n(self.y);
                 }
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
+            M::Jam => self.state = RunState::Stopped,
             M::Jmp => match self.read_operand(bus, opcode.mode) {
                 OpValue::U16(addr) => self.pc = addr,
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
-            M::Jsr => match self.read_operand(bus, opcode.mode) {
-                OpValue::U16(addr) => {
+            M::Jsr => match opcode.mode {
+                // The return address pushed is that of JSR's last byte, which RTS increments;
+                // the CPU reads that byte only after pushing.
+                Absolute => {
+                    let lo = self.read_pc_u8(bus) as u16;
+                    self.dummy_read(bus, 0x0100 | self.s as u16);
                     self.push_addr(bus, self.pc);
-                    self.pc = addr;
+                    let hi = self.read_pc_u8(bus) as u16;
+                    self.pc = hi << 8 | lo;
                 }
-                _ => panic!("illegal AddressMode: {opcode:?}"),
+                _ => return Err(IllegalMode),
             },
+            M::Las => {
+                self.s &= self.read_operand_value(bus, opcode)?;
+                self.a = self.s;
+                self.x = self.s;
+                self.update_p_z_n(self.s);
+            }
+            M::Lax => {
+                self.a = self.read_operand_value(bus, opcode)?;
+                self.x = self.a;
+                self.update_p_z_n(self.a);
+            }
             M::Lda => {
-                self.a = self.read_operand_value(bus, opcode);
+                self.a = self.read_operand_value(bus, opcode)?;
                 self.update_p_z_n(self.a);
             }
             M::Ldx => {
-                self.x = self.read_operand_value(bus, opcode);
+                self.x = self.read_operand_value(bus, opcode)?;
                 self.update_p_z_n(self.x);
             }
             M::Ldy => {
-                self.y = self.read_operand_value(bus, opcode);
+                self.y = self.read_operand_value(bus, opcode)?;
                 self.update_p_z_n(self.y);
             }
             M::Lsr => match opcode.mode {
@@ -325,17 +578,35 @@ impl Cpu {
                 _ => match self.read_operand(bus, opcode.mode) {
                     OpValue::U16(addr) => {
                         let before = bus.read(addr);
+                        self.rmw_dummy(bus, addr, before);
                         let after = before >> 1;
                         bus.write(addr, after);
                         self.update_p_z_n(after);
                         self.set_p_bit(StatusMask::Carry, before & 1 == 1);
                     }
-                    _ => panic!("illegal AddressMode: {opcode:?}"),
+                    _ => return Err(IllegalMode),
                 },
             },
-            M::Nop => {}
+            M::Lxa => {
+                // unstable; this uses the "magic" constant most commonly seen on real chips
+                self.a = (self.a | 0xEE) & self.read_operand_value(bus, opcode)?;
+                self.x = self.a;
+                self.update_p_z_n(self.a);
+            }
+            M::Nop => {
+                // reserved NOPs skip over their operand bytes, and read any memory operand
+                if let OpValue::U16(addr) = self.read_operand(bus, opcode.mode) {
+                    self.dummy_read(bus, addr);
+                    // the W65C02S $5C spends four more cycles on the same address
+                    if opcode.mode == Absolute && opcode.cycles == 8 {
+                        for _ in 0..4 {
+                            self.dummy_read(bus, addr);
+                        }
+                    }
+                }
+            }
             M::Ora => {
-                self.a |= self.read_operand_value(bus, opcode);
+                self.a |= self.read_operand_value(bus, opcode)?;
                 self.update_p_z_n(self.a);
             }
             M::Pha => self.push(bus, self.a),
@@ -343,18 +614,43 @@ impl Cpu {
             M::Phx => self.push(bus, self.x),
             M::Phy => self.push(bus, self.y),
             M::Pla => {
+                self.dummy_read(bus, 0x0100 | self.s as u16);
                 self.a = self.pop(bus);
                 self.update_p_z_n(self.a);
             }
-            M::Plp => self.p = self.pop(bus) & !0b00110000,
+            M::Plp => {
+                self.dummy_read(bus, 0x0100 | self.s as u16);
+                self.p = self.pop(bus) & !0b00110000;
+            }
             M::Plx => {
+                self.dummy_read(bus, 0x0100 | self.s as u16);
                 self.x = self.pop(bus);
                 self.update_p_z_n(self.x);
             }
             M::Ply => {
+                self.dummy_read(bus, 0x0100 | self.s as u16);
                 self.y = self.pop(bus);
                 self.update_p_z_n(self.y);
             }
+            M::Rmb0 | M::Rmb1 | M::Rmb2 | M::Rmb3 | M::Rmb4 | M::Rmb5 | M::Rmb6 | M::Rmb7 => {
+                match self.read_operand(bus, opcode.mode) {
+                    OpValue::U16(addr) => {
+                        let val = bus.read(addr);
+                        self.rmw_dummy(bus, addr, val);
+                        bus.write(addr, val & !bit_mask(opcode));
+                    }
+                    _ => return Err(IllegalMode),
+                }
+            }
+            M::Rla => {
+                let result = self.modify(bus, opcode, |cpu, val| {
+                    let carry = cpu.carry();
+                    cpu.set_p_bit(StatusMask::Carry, val & 0x80 != 0);
+                    val << 1 | carry
+                })?;
+                self.a &= result;
+                self.update_p_z_n(self.a);
+            }
             M::Rol => match opcode.mode {
                 Accumulator => {
                     let before = self.a;
@@ -366,12 +662,13 @@ impl Cpu {
                 _ => match self.read_operand(bus, opcode.mode) {
                     OpValue::U16(addr) => {
                         let before = bus.read(addr);
+                        self.rmw_dummy(bus, addr, before);
                         let after = before << 1 | self.get_p_bit(StatusMask::Carry) as u8;
                         bus.write(addr, after);
                         self.update_p_z_n(after);
                         self.set_p_bit(StatusMask::Carry, before & 0b10000000 != 0);
                     }
-                    _ => panic!("illegal AddressMode: {opcode:?}"),
+                    _ => return Err(IllegalMode),
                 },
             },
             M::Ror => match opcode.mode {
@@ -385,75 +682,120 @@ impl Cpu {
                 _ => match self.read_operand(bus, opcode.mode) {
                     OpValue::U16(addr) => {
                         let before = bus.read(addr);
+                        self.rmw_dummy(bus, addr, before);
                         let after = before >> 1 | (self.get_p_bit(StatusMask::Carry) as u8) << 7;
                         bus.write(addr, after);
                         self.update_p_z_n(after);
                         self.set_p_bit(StatusMask::Carry, before & 0b00000001 != 0);
                     }
-                    _ => panic!("illegal AddressMode: {opcode:?}"),
+                    _ => return Err(IllegalMode),
                 },
             },
+            M::Rra => {
+                let result = self.modify(bus, opcode, |cpu, val| {
+                    let carry = cpu.carry();
+                    cpu.set_p_bit(StatusMask::Carry, val & 1 != 0);
+                    val >> 1 | carry << 7
+                })?;
+                self.adc(bus, result);
+            }
             M::Rti => match opcode.mode {
                 Implied => {
+                    self.dummy_read(bus, 0x0100 | self.s as u16);
                     self.p = self.pop(bus) & !(StatusMask::Break as u8) | 1 << 5;
                     self.pc = self.pop_addr(bus);
                 }
-                _ => panic!("illegal AddressMode: {opcode:?}"),
+                _ => return Err(IllegalMode),
             },
             M::Rts => match opcode.mode {
-                Implied => self.pc = self.pop_addr(bus),
-                _ => panic!("illegal AddressMode: {opcode:?}"),
+                Implied => {
+                    self.dummy_read(bus, 0x0100 | self.s as u16);
+                    let addr = self.pop_addr(bus);
+                    self.dummy_read(bus, addr);
+                    self.pc = addr.wrapping_add(1);
+                }
+                _ => return Err(IllegalMode),
+            },
+            M::Sax => match self.read_operand(bus, opcode.mode) {
+                OpValue::U16(addr) => bus.write(addr, self.a & self.x),
+                _ => return Err(IllegalMode),
             },
             M::Sbc => {
-                let a = self.a;
-                let b = self.read_operand_value(bus, opcode);
-                let sum16 = (a as i16) - (b as i16) - (!self.get_p_bit(StatusMask::Carry) as i16);
-                let sum = sum16 as u8;
-                self.a = sum;
-                self.update_p_z_n(sum);
-                self.set_p_bit(StatusMask::Carry, sum < a);
-
-                // Whether the sign of `a` and `sum` differs AND the sign of `-b` and `sum` differs.
-                // This implies that `a` and `-b` are same-sign, but `sum` is other-sign: overflow.
-                // Note `(a - b) == (a + -b)` hence using `-b` (or bitwise !b).
-                //
-                // Truth table for sign bit:
-                // a b !b s | a^s !b^s | & | !=0 | Overflow?
-                // ----------------------------------------
-                // 0 0  1 0 |  0    1  | 0 |  0  | no
-                // 0 0  1 1 |  1    0  | 0 |  0  | no
-                // 0 1  0 0 |  0    0  | 0 |  0  | no
-                // 0 1  0 1 |  1    1  | 1 |  1  | yes
-                // 1 0  1 0 |  1    1  | 1 |  1  | yes
-                // 1 0  1 1 |  0    0  | 0 |  0  | no
-                // 1 1  0 0 |  1    0  | 0 |  0  | no
-                // 1 1  0 1 |  0    1  | 0 |  0  | no
-                self.set_p_bit(StatusMask::Overflow, ((a ^ sum) & (!b ^ sum)) >> 7 != 0);
+                let b = self.read_operand_value(bus, opcode)?;
+                self.sbc(bus, b);
+            }
+            M::Sbx => {
+                let b = self.read_operand_value(bus, opcode)?;
+                let ax = self.a & self.x;
+                self.x = ax.wrapping_sub(b);
+                self.update_p_z_n(self.x);
+                self.set_p_bit(StatusMask::Carry, ax >= b);
             }
             M::Sec => match opcode.mode {
                 Implied => self.set_p_bit(StatusMask::Carry, true),
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
             M::Sed => match opcode.mode {
                 Implied => self.set_p_bit(StatusMask::Decimal, true),
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
             M::Sei => match opcode.mode {
                 Implied => self.set_p_bit(StatusMask::Interrupt, true),
-                _ => panic!("illegal AddressMode: {:?}", opcode),
+                _ => return Err(IllegalMode),
             },
+            M::Sha => self.store_unstable(bus, opcode, self.y, self.a & self.x)?,
+            M::Shx => self.store_unstable(bus, opcode, self.y, self.x)?,
+            M::Shy => self.store_unstable(bus, opcode, self.x, self.y)?,
+            M::Slo => {
+                let result = self.modify(bus, opcode, |cpu, val| {
+                    cpu.set_p_bit(StatusMask::Carry, val & 0x80 != 0);
+                    val << 1
+                })?;
+                self.a |= result;
+                self.update_p_z_n(self.a);
+            }
+            M::Smb0 | M::Smb1 | M::Smb2 | M::Smb3 | M::Smb4 | M::Smb5 | M::Smb6 | M::Smb7 => {
+                match self.read_operand(bus, opcode.mode) {
+                    OpValue::U16(addr) => {
+                        let val = bus.read(addr);
+                        self.rmw_dummy(bus, addr, val);
+                        bus.write(addr, val | bit_mask(opcode));
+                    }
+                    _ => return Err(IllegalMode),
+                }
+            }
+            M::Sre => {
+                let result = self.modify(bus, opcode, |cpu, val| {
+                    cpu.set_p_bit(StatusMask::Carry, val & 1 != 0);
+                    val >> 1
+                })?;
+                self.a ^= result;
+                self.update_p_z_n(self.a);
+            }
             M::Sta => match self.read_operand(bus, opcode.mode) {
                 OpValue::U16(addr) => bus.write(addr, self.a),
-                _ => panic!("illegal AddressMode: {opcode:?}"),
+                _ => return Err(IllegalMode),
             },
+            M::Stp => {
+                self.dummy_read(bus, self.pc);
+                self.state = RunState::Stopped;
+            }
             M::Stx => match self.read_operand(bus, opcode.mode) {
                 OpValue::U16(addr) => bus.write(addr, self.x),
-                _ => panic!("illegal AddressMode: {opcode:?}"),
+                _ => return Err(IllegalMode),
             },
             M::Sty => match self.read_operand(bus, opcode.mode) {
                 OpValue::U16(addr) => bus.write(addr, self.y),
-                _ => panic!("illegal AddressMode: {opcode:?}"),
+                _ => return Err(IllegalMode),
+            },
+            M::Stz => match self.read_operand(bus, opcode.mode) {
+                OpValue::U16(addr) => bus.write(addr, 0x00),
+                _ => return Err(IllegalMode),
             },
+            M::Tas => {
+                self.s = self.a & self.x;
+                self.store_unstable(bus, opcode, self.y, self.s)?;
+            }
             M::Tax => {
                 self.x = self.a;
                 self.update_p_z_n(self.x);
@@ -462,6 +804,24 @@ impl Cpu {
                 self.y = self.a;
                 self.update_p_z_n(self.y);
             }
+            M::Trb => match self.read_operand(bus, opcode.mode) {
+                OpValue::U16(addr) => {
+                    let val = bus.read(addr);
+                    self.rmw_dummy(bus, addr, val);
+                    self.set_p_bit(StatusMask::Zero, self.a & val == 0);
+                    bus.write(addr, val & !self.a);
+                }
+                _ => return Err(IllegalMode),
+            },
+            M::Tsb => match self.read_operand(bus, opcode.mode) {
+                OpValue::U16(addr) => {
+                    let val = bus.read(addr);
+                    self.rmw_dummy(bus, addr, val);
+                    self.set_p_bit(StatusMask::Zero, self.a & val == 0);
+                    bus.write(addr, val | self.a);
+                }
+                _ => return Err(IllegalMode),
+            },
             M::Tsx => {
                 self.x = self.s;
                 self.update_p_z_n(self.x);
@@ -478,6 +838,286 @@ impl Cpu {
                 self.a = self.y;
                 self.update_p_z_n(self.a);
             }
+            M::Wai => {
+                self.dummy_read(bus, self.pc);
+                self.state = RunState::Waiting;
+            }
+        }
+        Ok(())
+    }
+
+    /// ADC in binary or decimal mode, depending on the D flag.
+    fn adc(&mut self, bus: &mut bus::Bus, b: u8) {
+        if self.get_p_bit(StatusMask::Decimal) {
+            self.adc_decimal(b);
+            self.decimal_cycle(bus);
+        } else {
+            self.adc_binary(b);
+        }
+    }
+
+    /// SBC in binary or decimal mode, depending on the D flag.
+    fn sbc(&mut self, bus: &mut bus::Bus, b: u8) {
+        if self.get_p_bit(StatusMask::Decimal) {
+            self.sbc_decimal(b);
+            self.decimal_cycle(bus);
+        } else {
+            self.sbc_binary(b);
+        }
+    }
+
+    /// The 65C02 takes an extra cycle to set N and Z from a decimal result, in which it reads
+    /// the operand again.
+    fn decimal_cycle(&mut self, bus: &mut bus::Bus) {
+        if self.variant != isa::Variant::Nmos6502 {
+            self.extra_cycles += 1;
+            self.dummy_read(bus, self.operand_addr);
+        }
+    }
+
+    /// CMP, CPX and CPY: C is set unless the subtraction borrowed, N and Z reflect the result.
+    fn compare(&mut self, reg: u8, b: u8) {
+        self.update_p_z_n(reg.wrapping_sub(b));
+        self.set_p_bit(StatusMask::Carry, reg >= b);
+    }
+
+    /// Read-modify-write the operand of an undocumented NMOS instruction, returning the result.
+    fn modify(
+        &mut self,
+        bus: &mut bus::Bus,
+        opcode: isa::Opcode,
+        f: impl FnOnce(&mut Self, u8) -> u8,
+    ) -> Result<u8, IllegalMode> {
+        match self.read_operand(bus, opcode.mode) {
+            isa::OpValue::U16(addr) => {
+                let val = bus.read(addr);
+                self.rmw_dummy(bus, addr, val);
+                let result = f(self, val);
+                bus.write(addr, result);
+                Ok(result)
+            }
+            _ => Err(IllegalMode),
+        }
+    }
+
+    /// The unstable NMOS stores SHA, SHX, SHY and TAS, which AND the value with the high byte
+    /// of the base address plus one. If indexing crossed a page, that value also replaces the
+    /// high byte of the address written to.
+    fn store_unstable(
+        &mut self,
+        bus: &mut bus::Bus,
+        opcode: isa::Opcode,
+        index: u8,
+        val: u8,
+    ) -> Result<(), IllegalMode> {
+        match self.read_operand(bus, opcode.mode) {
+            isa::OpValue::U16(addr) => {
+                let base = addr.wrapping_sub(index as u16);
+                let val = val & ((base >> 8) as u8).wrapping_add(1);
+                let addr = if self.page_crossed {
+                    (val as u16) << 8 | addr & 0x00FF
+                } else {
+                    addr
+                };
+                bus.write(addr, val);
+                Ok(())
+            }
+            _ => Err(IllegalMode),
+        }
+    }
+
+    /// ARR, the undocumented NMOS AND then ROR A, which takes C and V from the adder rather
+    /// than the rotate, and applies a decimal correction of its own in decimal mode.
+    fn arr(&mut self, b: u8) {
+        let and = self.a & b;
+        let result = and >> 1 | self.carry() << 7;
+        self.update_p_z_n(result);
+        self.set_p_bit(StatusMask::Overflow, (and ^ result) & 0x40 != 0);
+        if !self.get_p_bit(StatusMask::Decimal) {
+            self.a = result;
+            self.set_p_bit(StatusMask::Carry, result & 0x40 != 0);
+            return;
+        }
+        let mut result = result;
+        if (and & 0x0F) + (and & 0x01) > 0x05 {
+            result = result & 0xF0 | result.wrapping_add(0x06) & 0x0F;
+        }
+        let carry = (and & 0xF0) as u16 + (and & 0x10) as u16 > 0x50;
+        if carry {
+            result = result.wrapping_add(0x60);
+        }
+        self.a = result;
+        self.set_p_bit(StatusMask::Carry, carry);
+    }
+
+    /// ADC in binary mode.
+    fn adc_binary(&mut self, b: u8) {
+        let a = self.a;
+        let sum16 = (self.carry() as u16) + (a as u16) + (b as u16);
+        let sum = sum16 as u8;
+        self.a = sum;
+        self.update_p_z_n(sum);
+        self.set_p_bit(StatusMask::Carry, sum16 > 0xFF);
+
+        // Whether the sign of `a` and `sum` differs AND the sign of `b` and `sum` differs.
+        // This implies that `a` and `b` are same-sign, but `sum` is other-sign: overflow.
+        //
+        // a b s | a^s b^s | & | !=0 | Overflow?
+        // -------------------------------------
+        // 0 0 0 |  0   0  | 0 |  0  | no
+        // 0 0 1 |  1   1  | 1 |  1  | yes
+        // 0 1 0 |  0   1  | 0 |  0  | no
+        // 0 1 1 |  1   0  | 0 |  0  | no
+        // 1 0 0 |  1   0  | 0 |  0  | no
+        // 1 0 1 |  0   1  | 0 |  0  | no
+        // 1 1 0 |  1   1  | 1 |  1  | yes
+        // 1 1 1 |  0   0  | 0 |  0  | no
+        self.set_p_bit(StatusMask::Overflow, ((a ^ sum) & (b ^ sum)) >> 7 != 0);
+    }
+
+    /// SBC in binary mode.
+    fn sbc_binary(&mut self, b: u8) {
+        let a = self.a;
+        let sum16 = (a as i16) - (b as i16) - (!self.get_p_bit(StatusMask::Carry) as i16);
+        let sum = sum16 as u8;
+        self.a = sum;
+        self.update_p_z_n(sum);
+        self.set_p_bit(StatusMask::Carry, sum16 >= 0);
+
+        // Whether the sign of `a` and `sum` differs AND the sign of `-b` and `sum` differs.
+        // This implies that `a` and `-b` are same-sign, but `sum` is other-sign: overflow.
+        // Note `(a - b) == (a + -b)` hence using `-b` (or bitwise !b).
+        //
+        // Truth table for sign bit:
+        // a b !b s | a^s !b^s | & | !=0 | Overflow?
+        // ----------------------------------------
+        // 0 0  1 0 |  0    1  | 0 |  0  | no
+        // 0 0  1 1 |  1    0  | 0 |  0  | no
+        // 0 1  0 0 |  0    0  | 0 |  0  | no
+        // 0 1  0 1 |  1    1  | 1 |  1  | yes
+        // 1 0  1 0 |  1    1  | 1 |  1  | yes
+        // 1 0  1 1 |  0    0  | 0 |  0  | no
+        // 1 1  0 0 |  1    0  | 0 |  0  | no
+        // 1 1  0 1 |  0    1  | 0 |  0  | no
+        self.set_p_bit(StatusMask::Overflow, ((a ^ sum) & (!b ^ sum)) >> 7 != 0);
+    }
+
+    /// ADC in decimal mode: add BCD digit by digit, adjusting each nibble that exceeds 9.
+    /// V reflects the signed sum before the high digit is adjusted, as on the 6502; unlike the
+    /// NMOS 6502, the 65C02 sets N and Z from the decimal result, at the cost of a cycle.
+    /// The NMOS 6502 takes N from that same intermediate sum, and Z from the binary sum.
+    fn adc_decimal(&mut self, b: u8) {
+        let a = self.a;
+        let carry = self.carry();
+        let mut lo = (a & 0x0F) as u16 + (b & 0x0F) as u16 + carry as u16;
+        if lo > 0x09 {
+            lo = ((lo + 0x06) & 0x0F) + 0x10;
+        }
+        let mut sum = (a & 0xF0) as u16 + (b & 0xF0) as u16 + lo;
+        let signed = (a & 0xF0) as i8 as i16 + (b & 0xF0) as i8 as i16 + lo as i16;
+        self.set_p_bit(StatusMask::Overflow, !(-128..=127).contains(&signed));
+        let intermediate = sum as u8;
+        if sum > 0x9F {
+            sum += 0x60;
+        }
+        self.a = sum as u8;
+        self.set_p_bit(StatusMask::Carry, sum > 0xFF);
+        if self.variant == isa::Variant::Nmos6502 {
+            let binary = a.wrapping_add(b).wrapping_add(carry);
+            self.set_p_bit(StatusMask::Zero, binary == 0);
+            self.set_p_bit(StatusMask::Negative, intermediate & 0x80 != 0);
+        } else {
+            self.update_p_z_n(self.a);
+        }
+    }
+
+    /// SBC in decimal mode: subtract in binary, then correct each digit that borrowed.
+    /// C and V are the same as for binary SBC; the 65C02 sets N and Z from the decimal result,
+    /// at the cost of a cycle. The NMOS 6502 corrects digit by digit, and sets every flag
+    /// from the binary result.
+    fn sbc_decimal(&mut self, b: u8) {
+        let a = self.a;
+        let borrow = !self.get_p_bit(StatusMask::Carry) as i16;
+        let lo = (a & 0x0F) as i16 - (b & 0x0F) as i16 - borrow;
+        let bin = a as i16 - b as i16 - borrow;
+        let diff = if self.variant == isa::Variant::Nmos6502 {
+            let lo = if lo < 0 {
+                ((lo - 0x06) & 0x0F) - 0x10
+            } else {
+                lo
+            };
+            let diff = (a & 0xF0) as i16 - (b & 0xF0) as i16 + lo;
+            if diff < 0 {
+                diff - 0x60
+            } else {
+                diff
+            }
+        } else {
+            let mut diff = bin;
+            if bin < 0 {
+                diff -= 0x60;
+            }
+            if lo < 0 {
+                diff -= 0x06;
+            }
+            diff
+        };
+        let sum = bin as u8;
+        self.set_p_bit(StatusMask::Overflow, ((a ^ sum) & (!b ^ sum)) >> 7 != 0);
+        self.set_p_bit(StatusMask::Carry, bin >= 0);
+        self.a = diff as u8;
+        if self.variant == isa::Variant::Nmos6502 {
+            self.update_p_z_n(sum);
+        } else {
+            self.update_p_z_n(self.a);
+        }
+    }
+
+    /// Take a branch to target, which costs an extra cycle, plus another if it's on a different
+    /// page to the instruction following the branch. The 65C02 spends both reading the
+    /// following instruction; the NMOS 6502 reads the target before fixing its high byte.
+    fn branch(&mut self, bus: &mut bus::Bus, target: u16) {
+        self.extra_cycles += 1;
+        self.dummy_read(bus, self.pc);
+        if (self.pc ^ target) & 0xFF00 != 0 {
+            self.extra_cycles += 1;
+            if self.variant == isa::Variant::Nmos6502 {
+                self.dummy_read(bus, self.pc & 0xFF00 | target & 0x00FF);
+            } else {
+                self.dummy_read(bus, self.pc);
+            }
+        }
+        self.pc = target;
+    }
+
+    /// Read from the bus in cycle-accurate mode only, for an access made by the hardware whose
+    /// value the instruction doesn't use.
+    fn dummy_read(&mut self, bus: &mut bus::Bus, addr: u16) {
+        if self.cycle_accurate {
+            bus.read(addr);
+        }
+    }
+
+    /// The extra cycle of a read-modify-write instruction, between reading and writing back
+    /// val: the 65C02 reads the address again, where the NMOS 6502 writes val unmodified.
+    fn rmw_dummy(&mut self, bus: &mut bus::Bus, addr: u16, val: u8) {
+        if !self.cycle_accurate {
+            return;
+        }
+        if self.variant == isa::Variant::Nmos6502 {
+            bus.write(addr, val);
+        } else {
+            bus.read(addr);
+        }
+    }
+
+    /// The cycle spent adding an index to a zeropage address: the NMOS 6502 reads the base
+    /// address, where the 65C02 reads the operand byte again.
+    fn zeropage_index_dummy_read(&mut self, bus: &mut bus::Bus, base: u8) {
+        if self.variant == isa::Variant::Nmos6502 {
+            self.dummy_read(bus, base as u16);
+        } else {
+            self.dummy_read(bus, self.pc.wrapping_sub(1));
         }
     }
 
@@ -510,19 +1150,40 @@ impl Cpu {
 
         match mode {
             Absolute => OV::U16(self.read_pc_u16(bus)),
-            AbsoluteX => OV::U16(self.read_pc_u16(bus).wrapping_add(self.x as u16)),
-            AbsoluteY => OV::U16(self.read_pc_u16(bus).wrapping_add(self.y as u16)),
+            AbsoluteIndexedIndirect => {
+                let ptr = self.read_pc_u16(bus).wrapping_add(self.x as u16);
+                self.dummy_read(bus, self.pc.wrapping_sub(1));
+                OV::U16(bus.read_u16(ptr))
+            }
+            AbsoluteX => {
+                let base = self.read_pc_u16(bus);
+                OV::U16(self.index(bus, base, self.x))
+            }
+            AbsoluteY => {
+                let base = self.read_pc_u16(bus);
+                OV::U16(self.index(bus, base, self.y))
+            }
             Accumulator => OV::None,
             Immediate => OV::U8(self.read_pc_u8(bus)),
             Implied => OV::None,
             Indirect => {
                 let ptr = self.read_pc_u16(bus);
-                OV::U16(bus.read_u16(ptr))
+                if self.variant == isa::Variant::Nmos6502 {
+                    // NMOS bug: the pointer's high byte is read from the same page, so JMP ($xxFF)
+                    // reads it from the start of that page
+                    let lo = bus.read(ptr) as u16;
+                    let hi = bus.read(ptr & 0xFF00 | ptr.wrapping_add(1) & 0x00FF) as u16;
+                    OV::U16(hi << 8 | lo)
+                } else {
+                    // the 65C02 fixed the bug, taking a cycle in which it reads the operand again
+                    self.dummy_read(bus, self.pc.wrapping_sub(1));
+                    OV::U16(bus.read_u16(ptr))
+                }
             }
             IndirectY => {
                 let ptr = self.read_pc_u8(bus);
-                let addr = self.read_u16_zp(bus, ptr).wrapping_add(self.y as u16);
-                OV::U16(addr)
+                let base = self.read_u16_zp(bus, ptr);
+                OV::U16(self.index(bus, base, self.y))
             }
             Relative => {
                 // #![feature(mixed_integer_ops)]
@@ -532,21 +1193,63 @@ impl Cpu {
                 OV::U16((base + offset) as u16)
             }
             XIndirect => {
-                let ptr = self.read_pc_u8(bus).wrapping_add(self.x);
-                OV::U16(self.read_u16_zp(bus, ptr))
+                let base = self.read_pc_u8(bus);
+                self.zeropage_index_dummy_read(bus, base);
+                OV::U16(self.read_u16_zp(bus, base.wrapping_add(self.x)))
             }
             Zeropage => OV::U16(self.read_pc_u8(bus) as u16),
-            ZeropageX => OV::U16(self.read_pc_u8(bus).wrapping_add(self.x) as u16),
-            ZeropageY => OV::U16(self.read_pc_u8(bus).wrapping_add(self.y) as u16),
+            ZeropageIndirect => {
+                let ptr = self.read_pc_u8(bus);
+                OV::U16(self.read_u16_zp(bus, ptr))
+            }
+            ZeropageX => {
+                let base = self.read_pc_u8(bus);
+                self.zeropage_index_dummy_read(bus, base);
+                OV::U16(base.wrapping_add(self.x) as u16)
+            }
+            ZeropageY => {
+                let base = self.read_pc_u8(bus);
+                self.zeropage_index_dummy_read(bus, base);
+                OV::U16(base.wrapping_add(self.y) as u16)
+            }
+            // Only the zeropage address is read; PC is left at the relative branch offset,
+            // which the instruction reads as a Relative operand once it has tested the bit.
+            ZeropageRelative => OV::U16(self.read_pc_u8(bus) as u16),
         }
     }
 
-    fn read_operand_value(&mut self, bus: &mut bus::Bus, opcode: isa::Opcode) -> u8 {
+    /// Index base address by an X or Y register, noting whether that crossed a page boundary.
+    /// Fixing up the high byte takes a cycle, in which the NMOS 6502 reads the address before
+    /// the fix-up and the 65C02 reads the last operand byte again.
+    fn index(&mut self, bus: &mut bus::Bus, base: u16, reg: u8) -> u16 {
+        let addr = base.wrapping_add(reg as u16);
+        self.page_crossed = (base ^ addr) & 0xFF00 != 0;
+        if self.page_crossed || self.fixup_always {
+            if self.variant == isa::Variant::Nmos6502 {
+                self.dummy_read(bus, base & 0xFF00 | addr & 0x00FF);
+            } else {
+                self.dummy_read(bus, self.pc.wrapping_sub(1));
+            }
+        }
+        addr
+    }
+
+    fn read_operand_value(
+        &mut self,
+        bus: &mut bus::Bus,
+        opcode: isa::Opcode,
+    ) -> Result<u8, IllegalMode> {
         use isa::OpValue;
         match self.read_operand(bus, opcode.mode) {
-            OpValue::U8(val) => val,
-            OpValue::U16(addr) => bus.read(addr),
-            OpValue::None => panic!("illegal AddressMode: {:?}", opcode),
+            OpValue::U8(val) => {
+                self.operand_addr = self.pc.wrapping_sub(1);
+                Ok(val)
+            }
+            OpValue::U16(addr) => {
+                self.operand_addr = addr;
+                Ok(bus.read(addr))
+            }
+            OpValue::None => Err(IllegalMode),
         }
     }
 
@@ -597,6 +1300,76 @@ impl Cpu {
     }
 }
 
+// An instruction met an operand its address mode can't provide; step() reports it as a CpuError.
+struct IllegalMode;
+
+// The bit selected by an RMBn, SMBn, BBRn or BBSn opcode, which encodes n in its high nibble.
+fn bit_mask(opcode: isa::Opcode) -> u8 {
+    1 << ((opcode.code >> 4) & 0x07)
+}
+
+// What a single Cpu::step executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepInfo {
+    pub pc: u16,                     // address of the instruction
+    pub opcode: Option<isa::Opcode>, // None while paused or stalled
+    pub cycles: u8,                  // clock cycles taken, including any penalties
+}
+
+// An instruction the CPU could not execute, with the address and byte of its opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    // no instruction is defined for the opcode, or strict mode rejected a reserved one
+    UndefinedOpcode {
+        pc: u16,
+        code: u8,
+    },
+    // the opcode table pairs an instruction with an address mode it can't execute
+    IllegalAddressMode {
+        pc: u16,
+        code: u8,
+        mnemonic: isa::Mnemonic,
+        mode: isa::AddressMode,
+    },
+}
+
+impl CpuError {
+    pub fn pc(&self) -> u16 {
+        match *self {
+            CpuError::UndefinedOpcode { pc, .. } | CpuError::IllegalAddressMode { pc, .. } => pc,
+        }
+    }
+
+    pub fn code(&self) -> u8 {
+        match *self {
+            CpuError::UndefinedOpcode { code, .. } | CpuError::IllegalAddressMode { code, .. } => {
+                code
+            }
+        }
+    }
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::UndefinedOpcode { pc, code } => {
+                write!(f, "undefined opcode ${code:02X} at ${pc:04X}")
+            }
+            CpuError::IllegalAddressMode {
+                pc,
+                code,
+                mnemonic,
+                mode,
+            } => write!(
+                f,
+                "illegal address mode {mode:?} for {mnemonic:?} (opcode ${code:02X}) at ${pc:04X}"
+            ),
+        }
+    }
+}
+
+impl error::Error for CpuError {}
+
 impl fmt::Debug for Cpu {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
         let stat = stat(&self.p);
@@ -647,7 +1420,6 @@ pub enum StatusMask {
     Negative = 1 << StatusBit::Negative as u8,
 }
 
-#[allow(unused)]
 pub const VEC_NMI: u16 = 0xFFFA;
 pub const VEC_RES: u16 = 0xFFFC;
 pub const VEC_IRQ: u16 = 0xFFFE;
@@ -10,40 +10,65 @@ pub fn parse(data: &str) -> Result<Info, Box<dyn Error>> {
 
     let mut label_to_addr: HashMap<String, u16> = HashMap::new();
     let mut addr_to_label: HashMap<u16, String> = HashMap::new();
+    let mut segments: Vec<Segment> = Vec::new();
 
-    for (_, [keyword, pairstr]) in line_pattern.captures_iter(&data).map(|c| c.extract()) {
-        match keyword {
-            "sym" => {
-                let mut label: Option<String> = None;
-                let mut addr: Option<u16> = None;
-                for (k, v) in pairstr.split(",").map(|p| p.split_once("=").unwrap()) {
-                    match k {
-                        "name" => label = Some(v.trim_matches('"').to_string()),
-                        "val" => {
-                            addr = Some(u16::from_str_radix(v.strip_prefix("0x").unwrap(), 16)?)
-                        }
-                        _ => {}
-                    }
+    for (_, [keyword, pairstr]) in line_pattern.captures_iter(data).map(|c| c.extract()) {
+        if keyword == "sym" {
+            let mut label: Option<String> = None;
+            let mut addr: Option<u16> = None;
+            for (k, v) in pairstr.split(",").map(|p| p.split_once("=").unwrap()) {
+                match k {
+                    "name" => label = Some(v.trim_matches('"').to_string()),
+                    "val" => addr = Some(u16::from_str_radix(v.strip_prefix("0x").unwrap(), 16)?),
+                    _ => {}
                 }
-                if let (Some(label), Some(addr)) = (label, addr) {
-                    label_to_addr.insert(label.clone(), addr);
-                    addr_to_label.insert(addr, label.clone());
+            }
+            if let (Some(label), Some(addr)) = (label, addr) {
+                label_to_addr.insert(label.clone(), addr);
+                addr_to_label.insert(addr, label.clone());
+            }
+        } else if keyword == "seg" {
+            let mut seg = Segment::default();
+            let mut oname: Option<String> = None;
+            let mut ooffs: usize = 0;
+            for (k, v) in pairstr.split(",").map(|p| p.split_once("=").unwrap()) {
+                let hex = |v: &str| usize::from_str_radix(v.trim_start_matches("0x"), 16);
+                match k {
+                    "name" => seg.name = v.trim_matches('"').to_string(),
+                    "start" => seg.start = hex(v)? as u32,
+                    "size" => seg.size = hex(v)?,
+                    "oname" => oname = Some(v.trim_matches('"').to_string()),
+                    "ooffs" => ooffs = v.parse()?,
+                    _ => {}
                 }
             }
-            _ => {}
+            seg.output = oname.map(|name| (name, ooffs));
+            segments.push(seg);
         }
     }
 
     Ok(Info {
         label_to_addr,
         addr_to_label,
+        segments,
     })
 }
 
+// A segment placed by the linker: where it goes in memory, and where ld65 wrote its bytes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub name: String,
+    pub start: u32,
+    pub size: usize,
+    pub output: Option<(String, usize)>, // output file and offset into it; None for e.g. BSS
+}
+
 #[allow(unused)]
+#[derive(Default)]
 pub struct Info {
     pub label_to_addr: HashMap<String, u16>,
     pub addr_to_label: HashMap<u16, String>,
+    pub segments: Vec<Segment>,
 }
 
 #[allow(unused)]
@@ -55,4 +80,13 @@ impl Info {
     pub fn label(&self, addr: u16) -> Option<&str> {
         self.addr_to_label.get(&addr).map(|x| x.as_str())
     }
+
+    // The closest label at or before addr, and addr's offset from it.
+    pub fn nearest_label(&self, addr: u16) -> Option<(&str, u16)> {
+        self.addr_to_label
+            .iter()
+            .filter(|(a, _)| **a <= addr)
+            .max_by_key(|(a, _)| **a)
+            .map(|(a, label)| (label.as_str(), addr - a))
+    }
 }
@@ -2,25 +2,36 @@ use crate::isa;
 
 pub struct Decoder {
     table: [Option<isa::Opcode>; 256],
+    reserved: [bool; 256],
 }
 
 impl Decoder {
-    pub fn new() -> Self {
-        Decoder {
-            table: build_opcode_table(),
-        }
+    pub fn new(variant: isa::Variant) -> Self {
+        let (table, reserved) = build_opcode_table(variant);
+        Decoder { table, reserved }
     }
 
     pub fn opcode(&self, code: u8) -> Option<isa::Opcode> {
         self.table[code as usize]
     }
+
+    // Whether the opcode is reserved or undocumented, rather than a documented instruction.
+    pub fn is_reserved(&self, code: u8) -> bool {
+        self.reserved[code as usize]
+    }
 }
 
-// Build an array of isa::Opcode indexed by by their u8 opcode.
-fn build_opcode_table() -> [Option<isa::Opcode>; 256] {
+// Build an array of isa::Opcode indexed by by their u8 opcode, alongside which are reserved.
+fn build_opcode_table(variant: isa::Variant) -> ([Option<isa::Opcode>; 256], [bool; 256]) {
     let mut optab = [None; 256];
-    for opcode in isa::opcode_list() {
+    let mut reserved = [false; 256];
+    let (documented, undocumented) = isa::variant_opcode_lists(variant);
+    for opcode in documented {
+        optab[opcode.code as usize] = Some(opcode);
+    }
+    for opcode in undocumented {
         optab[opcode.code as usize] = Some(opcode);
+        reserved[opcode.code as usize] = true;
     }
-    optab
+    (optab, reserved)
 }
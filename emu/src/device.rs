@@ -0,0 +1,65 @@
+use std::any::Any;
+
+use crate::bus::WINDOWS;
+
+// Device is a memory-mapped peripheral, attached to the Bus at an address range. Registers are
+// addressed by their offset from the start of that range.
+pub trait Device: Any {
+    // Short name for the monitor and for errors, e.g. "UART".
+    fn name(&self) -> &str;
+
+    // Read a register, with whatever side effects that has on hardware, e.g. popping a FIFO.
+    fn read(&mut self, reg: u16) -> u8;
+
+    fn write(&mut self, reg: u16, data: u8);
+
+    // Read a register without side effects, e.g. for the monitor.
+    fn peek(&self, reg: u16) -> u8;
+
+    fn reset(&mut self) {}
+
+    // Advance by the given number of clock cycles.
+    fn step(&mut self, _cycles: u8) {}
+
+    // Advance by more clock cycles than step takes at once.
+    fn step_cycles(&mut self, mut cycles: u32) {
+        while cycles > 0 {
+            let n = cycles.min(u8::MAX as u32);
+            self.step(n as u8);
+            cycles -= n;
+        }
+    }
+
+    // At the earliest, how many cycles from now the device asserts IRQ or NMI if left alone, or
+    // None if it never will by itself, e.g. only on input from the host. While WAI pauses the
+    // CPU, the system steps the devices this far in one go.
+    fn next_interrupt(&self) -> Option<u32> {
+        None
+    }
+
+    // Whether the device is asserting the (active low, level-triggered) IRQ line.
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // Whether the device is asserting the (active low, edge-triggered) NMI line.
+    fn nmi(&self) -> bool {
+        false
+    }
+
+    // Name of the register read at reg, if it has one.
+    fn name_for_read(&self, _reg: u16) -> Option<&str> {
+        None
+    }
+
+    // Name of the register written at reg, if it has one.
+    fn name_for_write(&self, _reg: u16) -> Option<&str> {
+        None
+    }
+
+    // For a device that controls the memory map, like BIFRÖST: the physical RAM bank mapped
+    // into each of the CPU's address windows. The Bus reads it after writes and resets.
+    fn banks(&self) -> Option<&[u8; WINDOWS]> {
+        None
+    }
+}
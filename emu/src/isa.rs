@@ -2,19 +2,36 @@ use std::collections::HashMap;
 use std::fmt;
 
 // Opcode is an 8-bit machine instruction alongside its Mnemonic and AddressMode.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Opcode {
     pub code: u8,
     pub mnemonic: Mnemonic,
     pub mode: AddressMode,
+    pub cycles: u8, // base cycle count, before any page-crossing, branch or decimal penalty
 }
 
 impl Opcode {
-    pub fn new(mnemonic: Mnemonic, mode: AddressMode, code: u8) -> Opcode {
+    pub fn new(mnemonic: Mnemonic, mode: AddressMode, code: u8, cycles: u8) -> Opcode {
         Opcode {
             code,
             mnemonic,
             mode,
+            cycles,
+        }
+    }
+
+    /// Whether indexing across a page boundary costs an extra cycle. That's every indexed
+    /// read, but not stores or read-modify-write instructions, which always take the cycle.
+    /// The 65C02 shortened its shifts and rotates, which only take the cycle on a page cross.
+    pub fn page_cross_penalty(&self, variant: Variant) -> bool {
+        use AddressMode::*;
+        use Mnemonic::*;
+        match self.mnemonic {
+            Sta | Stz | Inc | Dec => false,
+            Sax | Sha | Shx | Shy | Tas => false,
+            Slo | Rla | Sre | Rra | Dcp | Isc => false,
+            Asl | Lsr | Rol | Ror => variant != Variant::Nmos6502 && self.mode == AbsoluteX,
+            _ => matches!(self.mode, AbsoluteX | AbsoluteY | IndirectY),
         }
     }
 }
@@ -31,68 +48,144 @@ impl fmt::Debug for Opcode {
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Mnemonic {
-    Adc, // add with carry
-    And, // and (with accumulator)
-    Asl, // arithmetic shift left
-    Bcc, // branch on carry clear
-    Bcs, // branch on carry set
-    Beq, // branch on equal (zero set)
-    Bit, // bit test
-    Bmi, // branch on minus (negative set)
-    Bne, // branch on not equal (zero clear)
-    Bpl, // branch on plus (negative clear)
-    Brk, // break / interrupt
-    Bvc, // branch on overflow clear
-    Bvs, // branch on overflow set
-    Clc, // clear carry
-    Cld, // clear decimal
-    Cli, // clear interrupt disable
-    Clv, // clear overflow
-    Cmp, // compare (with accumulator)
-    Cpx, // compare with X
-    Cpy, // compare with Y
-    Dec, // decrement
-    Dex, // decrement X
-    Dey, // decrement Y
-    Eor, // exclusive or (with accumulator)
-    Inc, // increment
-    Inx, // increment X
-    Iny, // increment Y
-    Jmp, // jump
-    Jsr, // jump subroutine
-    Lda, // load accumulator
-    Ldx, // load X
-    Ldy, // load Y
-    Lsr, // logical shift right
-    Nop, // no operation
-    Ora, // or with accumulator
-    Pha, // push accumulator
-    Phx, // push X
-    Phy, // push Y
-    Php, // push processor status (P)
-    Pla, // pull accumulator
-    Plp, // pull processor status (P)
-    Plx, // pull X
-    Ply, // pull Y
-    Rol, // rotate left
-    Ror, // rotate right
-    Rti, // return from interrupt
-    Rts, // return from subroutine
-    Sbc, // subtract with carry
-    Sec, // set carry
-    Sed, // set decimal
-    Sei, // set interrupt disable
-    Sta, // store accumulator
-    Stx, // store X
-    Sty, // store Y
-    Tax, // transfer accumulator to X
-    Tay, // transfer accumulator to Y
-    Tsx, // transfer stack pointer to X
-    Txa, // transfer X to accumulator
-    Txs, // transfer X to stack pointer
-    Tya, // transfer Y to accumulator
+    Adc,  // add with carry
+    Alr,  // AND then LSR (NMOS undocumented)
+    Anc,  // AND then copy N to C (NMOS undocumented)
+    And,  // and (with accumulator)
+    Ane,  // (A | magic) AND X AND immediate (NMOS undocumented, unstable)
+    Arr,  // AND then ROR (NMOS undocumented)
+    Asl,  // arithmetic shift left
+    Bbr0, // branch on bit 0 reset
+    Bbr1, // branch on bit 1 reset
+    Bbr2, // branch on bit 2 reset
+    Bbr3, // branch on bit 3 reset
+    Bbr4, // branch on bit 4 reset
+    Bbr5, // branch on bit 5 reset
+    Bbr6, // branch on bit 6 reset
+    Bbr7, // branch on bit 7 reset
+    Bbs0, // branch on bit 0 set
+    Bbs1, // branch on bit 1 set
+    Bbs2, // branch on bit 2 set
+    Bbs3, // branch on bit 3 set
+    Bbs4, // branch on bit 4 set
+    Bbs5, // branch on bit 5 set
+    Bbs6, // branch on bit 6 set
+    Bbs7, // branch on bit 7 set
+    Bcc,  // branch on carry clear
+    Bcs,  // branch on carry set
+    Beq,  // branch on equal (zero set)
+    Bit,  // bit test
+    Bmi,  // branch on minus (negative set)
+    Bne,  // branch on not equal (zero clear)
+    Bpl,  // branch on plus (negative clear)
+    Bra,  // branch always
+    Brk,  // break / interrupt
+    Bvc,  // branch on overflow clear
+    Bvs,  // branch on overflow set
+    Clc,  // clear carry
+    Cld,  // clear decimal
+    Cli,  // clear interrupt disable
+    Clv,  // clear overflow
+    Cmp,  // compare (with accumulator)
+    Cpx,  // compare with X
+    Cpy,  // compare with Y
+    Dcp,  // DEC then CMP (NMOS undocumented)
+    Dec,  // decrement
+    Dex,  // decrement X
+    Dey,  // decrement Y
+    Eor,  // exclusive or (with accumulator)
+    Inc,  // increment
+    Inx,  // increment X
+    Iny,  // increment Y
+    Isc,  // INC then SBC (NMOS undocumented)
+    Jam,  // halt the CPU (NMOS undocumented)
+    Jmp,  // jump
+    Jsr,  // jump subroutine
+    Las,  // AND with S into A, X and S (NMOS undocumented)
+    Lax,  // LDA and LDX (NMOS undocumented)
+    Lda,  // load accumulator
+    Ldx,  // load X
+    Ldy,  // load Y
+    Lsr,  // logical shift right
+    Lxa,  // (A | magic) AND immediate into A and X (NMOS undocumented, unstable)
+    Nop,  // no operation
+    Ora,  // or with accumulator
+    Pha,  // push accumulator
+    Phx,  // push X
+    Phy,  // push Y
+    Php,  // push processor status (P)
+    Pla,  // pull accumulator
+    Plp,  // pull processor status (P)
+    Plx,  // pull X
+    Ply,  // pull Y
+    Rmb0, // reset memory bit 0
+    Rmb1, // reset memory bit 1
+    Rmb2, // reset memory bit 2
+    Rmb3, // reset memory bit 3
+    Rmb4, // reset memory bit 4
+    Rmb5, // reset memory bit 5
+    Rmb6, // reset memory bit 6
+    Rmb7, // reset memory bit 7
+    Rla,  // ROL then AND (NMOS undocumented)
+    Rol,  // rotate left
+    Ror,  // rotate right
+    Rti,  // return from interrupt
+    Rra,  // ROR then ADC (NMOS undocumented)
+    Rts,  // return from subroutine
+    Sax,  // store A AND X (NMOS undocumented)
+    Sbc,  // subtract with carry
+    Sbx,  // X = (A AND X) - immediate (NMOS undocumented)
+    Sec,  // set carry
+    Sed,  // set decimal
+    Sei,  // set interrupt disable
+    Sha,  // store A AND X AND (high byte + 1) (NMOS undocumented, unstable)
+    Shx,  // store X AND (high byte + 1) (NMOS undocumented, unstable)
+    Shy,  // store Y AND (high byte + 1) (NMOS undocumented, unstable)
+    Slo,  // ASL then ORA (NMOS undocumented)
+    Smb0, // set memory bit 0
+    Smb1, // set memory bit 1
+    Smb2, // set memory bit 2
+    Smb3, // set memory bit 3
+    Smb4, // set memory bit 4
+    Smb5, // set memory bit 5
+    Smb6, // set memory bit 6
+    Smb7, // set memory bit 7
+    Sre,  // LSR then EOR (NMOS undocumented)
+    Sta,  // store accumulator
+    Stp,  // stop the clock
+    Stx,  // store X
+    Sty,  // store Y
+    Stz,  // store zero
+    Tas,  // S = A AND X, then SHA (NMOS undocumented, unstable)
+    Tax,  // transfer accumulator to X
+    Tay,  // transfer accumulator to Y
+    Trb,  // test and reset bits
+    Tsb,  // test and set bits
+    Tsx,  // transfer stack pointer to X
+    Txa,  // transfer X to accumulator
+    Txs,  // transfer X to stack pointer
+    Tya,  // transfer Y to accumulator
+    Wai,  // wait for interrupt
 }
 
+// Rockwell/WDC bit manipulation mnemonics, indexed by bit number.
+pub const RMB: [Mnemonic; 8] = {
+    use Mnemonic::*;
+    [Rmb0, Rmb1, Rmb2, Rmb3, Rmb4, Rmb5, Rmb6, Rmb7]
+};
+pub const SMB: [Mnemonic; 8] = {
+    use Mnemonic::*;
+    [Smb0, Smb1, Smb2, Smb3, Smb4, Smb5, Smb6, Smb7]
+};
+pub const BBR: [Mnemonic; 8] = {
+    use Mnemonic::*;
+    [Bbr0, Bbr1, Bbr2, Bbr3, Bbr4, Bbr5, Bbr6, Bbr7]
+};
+pub const BBS: [Mnemonic; 8] = {
+    use Mnemonic::*;
+    [Bbs0, Bbs1, Bbs2, Bbs3, Bbs4, Bbs5, Bbs6, Bbs7]
+};
+
 impl fmt::Display for Mnemonic {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", format!("{:?}", self).to_uppercase())
@@ -107,13 +200,16 @@ pub enum AddressMode {
     AbsoluteY, // $LLHH,Y
     Immediate, // $BB
     Implied,
-    Indirect,  // ($LLHH)
-    XIndirect, // ($LL,X)
-    IndirectY, // ($LL),Y
-    Relative,  // $BB (signed)
-    Zeropage,  // $LL
-    ZeropageX, // $LL,X
-    ZeropageY, // $LL,Y
+    Indirect,                // ($LLHH)
+    AbsoluteIndexedIndirect, // ($LLHH,X)
+    XIndirect,               // ($LL,X)
+    IndirectY,               // ($LL),Y
+    ZeropageIndirect,        // ($LL)
+    ZeropageRelative,        // $LL,$BB (signed)
+    Relative,                // $BB (signed)
+    Zeropage,                // $LL
+    ZeropageX,               // $LL,X
+    ZeropageY,               // $LL,Y
 }
 
 /// Length in bytes of operand associated with given AddressMode
@@ -121,8 +217,14 @@ pub fn operand_length(mode: AddressMode) -> u16 {
     use AddressMode::*;
     match mode {
         Accumulator | Implied => 0,
-        Immediate | XIndirect | IndirectY | Relative | Zeropage | ZeropageX | ZeropageY => 1,
-        Absolute | AbsoluteX | AbsoluteY | Indirect => 2,
+        Immediate | XIndirect | IndirectY | Relative | Zeropage | ZeropageX | ZeropageY
+        | ZeropageIndirect => 1,
+        Absolute
+        | AbsoluteX
+        | AbsoluteY
+        | Indirect
+        | AbsoluteIndexedIndirect
+        | ZeropageRelative => 2,
     }
 }
 
@@ -139,164 +241,354 @@ pub fn opcode_list() -> Vec<Opcode> {
     use AddressMode::*; // Absolute, Immediate etc
     use Mnemonic::*; // Adc, Bcc, Clc etc
     let new = Opcode::new;
-    vec![
-        new(Adc, Absolute, 0x6D),
-        new(Adc, AbsoluteX, 0x7D),
-        new(Adc, AbsoluteY, 0x79),
-        new(Adc, Immediate, 0x69),
-        new(Adc, IndirectY, 0x71),
-        new(Adc, XIndirect, 0x61),
-        new(Adc, Zeropage, 0x65),
-        new(Adc, ZeropageX, 0x75),
-        new(And, Absolute, 0x2D),
-        new(And, AbsoluteX, 0x3D),
-        new(And, AbsoluteY, 0x39),
-        new(And, Immediate, 0x29),
-        new(And, IndirectY, 0x31),
-        new(And, XIndirect, 0x21),
-        new(And, Zeropage, 0x25),
-        new(And, ZeropageX, 0x35),
-        new(Asl, Absolute, 0x0E),
-        new(Asl, AbsoluteX, 0x1E),
-        new(Asl, Accumulator, 0x0A),
-        new(Asl, Zeropage, 0x06),
-        new(Asl, ZeropageX, 0x16),
-        new(Bcc, Relative, 0x90),
-        new(Bcs, Relative, 0xB0),
-        new(Beq, Relative, 0xF0),
-        new(Bit, Absolute, 0x2C),
-        new(Bit, AbsoluteX, 0x3C),
-        new(Bit, Zeropage, 0x24),
-        new(Bmi, Relative, 0x30),
-        new(Bne, Relative, 0xD0),
-        new(Bpl, Relative, 0x10),
-        new(Brk, Implied, 0x00),
-        new(Bvc, Relative, 0x50),
-        new(Bvs, Relative, 0x70),
-        new(Clc, Implied, 0x18),
-        new(Cld, Implied, 0xD8),
-        new(Cli, Implied, 0x58),
-        new(Clv, Implied, 0xB8),
-        new(Cmp, Absolute, 0xCD),
-        new(Cmp, AbsoluteX, 0xDD),
-        new(Cmp, AbsoluteY, 0xD9),
-        new(Cmp, Immediate, 0xC9),
-        new(Cmp, IndirectY, 0xD1),
-        new(Cmp, XIndirect, 0xC1),
-        new(Cmp, Zeropage, 0xC5),
-        new(Cmp, ZeropageX, 0xD5),
-        new(Cpx, Absolute, 0xEC),
-        new(Cpx, Immediate, 0xE0),
-        new(Cpx, Zeropage, 0xE4),
-        new(Cpy, Absolute, 0xCC),
-        new(Cpy, Immediate, 0xC0),
-        new(Cpy, Zeropage, 0xC4),
-        new(Dec, Absolute, 0xCE),
-        new(Dec, AbsoluteX, 0xDE),
-        new(Dec, Zeropage, 0xC6),
-        new(Dec, ZeropageX, 0xD6),
-        new(Dex, Implied, 0xCA),
-        new(Dey, Implied, 0x88),
-        new(Eor, Absolute, 0x4D),
-        new(Eor, AbsoluteX, 0x5D),
-        new(Eor, AbsoluteY, 0x59),
-        new(Eor, Immediate, 0x49),
-        new(Eor, IndirectY, 0x51),
-        new(Eor, XIndirect, 0x41),
-        new(Eor, Zeropage, 0x45),
-        new(Eor, ZeropageX, 0x55),
-        new(Inc, Absolute, 0xEE),
-        new(Inc, AbsoluteX, 0xFE),
-        new(Inc, Zeropage, 0xE6),
-        new(Inc, ZeropageX, 0xF6),
-        new(Inx, Implied, 0xE8),
-        new(Iny, Implied, 0xC8),
-        new(Jmp, Absolute, 0x4C),
-        new(Jmp, Indirect, 0x6C),
-        new(Jsr, Absolute, 0x20),
-        new(Lda, Absolute, 0xAD),
-        new(Lda, AbsoluteX, 0xBD),
-        new(Lda, AbsoluteY, 0xB9),
-        new(Lda, Immediate, 0xA9),
-        new(Lda, IndirectY, 0xB1),
-        new(Lda, XIndirect, 0xA1),
-        new(Lda, Zeropage, 0xA5),
-        new(Lda, ZeropageX, 0xB5),
-        new(Ldx, Absolute, 0xAE),
-        new(Ldx, AbsoluteY, 0xBE),
-        new(Ldx, Immediate, 0xA2),
-        new(Ldx, Zeropage, 0xA6),
-        new(Ldx, ZeropageY, 0xB6),
-        new(Ldy, Absolute, 0xAC),
-        new(Ldy, AbsoluteX, 0xBC),
-        new(Ldy, Immediate, 0xA0),
-        new(Ldy, Zeropage, 0xA4),
-        new(Ldy, ZeropageX, 0xB4),
-        new(Lsr, Absolute, 0x4E),
-        new(Lsr, AbsoluteX, 0x5E),
-        new(Lsr, Accumulator, 0x4A),
-        new(Lsr, Zeropage, 0x46),
-        new(Lsr, ZeropageX, 0x56),
-        new(Nop, Implied, 0xEA),
-        new(Ora, Absolute, 0x0D),
-        new(Ora, AbsoluteX, 0x1D),
-        new(Ora, AbsoluteY, 0x19),
-        new(Ora, Immediate, 0x09),
-        new(Ora, IndirectY, 0x11),
-        new(Ora, XIndirect, 0x01),
-        new(Ora, Zeropage, 0x05),
-        new(Ora, ZeropageX, 0x15),
-        new(Pha, Implied, 0x48),
-        new(Php, Implied, 0x08),
-        new(Phx, Implied, 0xDA),
-        new(Phy, Implied, 0x5A),
-        new(Pla, Implied, 0x68),
-        new(Plp, Implied, 0x28),
-        new(Plx, Implied, 0xFA),
-        new(Ply, Implied, 0x7A),
-        new(Rol, Absolute, 0x2E),
-        new(Rol, AbsoluteX, 0x3E),
-        new(Rol, Accumulator, 0x2A),
-        new(Rol, Zeropage, 0x26),
-        new(Rol, ZeropageX, 0x36),
-        new(Ror, Absolute, 0x6E),
-        new(Ror, AbsoluteX, 0x7E),
-        new(Ror, Accumulator, 0x6A),
-        new(Ror, Zeropage, 0x66),
-        new(Ror, ZeropageX, 0x76),
-        new(Rti, Implied, 0x40),
-        new(Rts, Implied, 0x60),
-        new(Sbc, Absolute, 0xED),
-        new(Sbc, AbsoluteX, 0xFD),
-        new(Sbc, AbsoluteY, 0xF9),
-        new(Sbc, Immediate, 0xE9),
-        new(Sbc, IndirectY, 0xF1),
-        new(Sbc, XIndirect, 0xE1),
-        new(Sbc, Zeropage, 0xE5),
-        new(Sbc, ZeropageX, 0xF5),
-        new(Sec, Implied, 0x38),
-        new(Sed, Implied, 0xF8),
-        new(Sei, Implied, 0x78),
-        new(Sta, Absolute, 0x8D),
-        new(Sta, AbsoluteX, 0x9D),
-        new(Sta, AbsoluteY, 0x99),
-        new(Sta, IndirectY, 0x91),
-        new(Sta, XIndirect, 0x81),
-        new(Sta, Zeropage, 0x85),
-        new(Sta, ZeropageX, 0x95),
-        new(Stx, Absolute, 0x8E),
-        new(Stx, Zeropage, 0x86),
-        new(Stx, ZeropageY, 0x96),
-        new(Sty, Absolute, 0x8C),
-        new(Sty, Zeropage, 0x84),
-        new(Sty, ZeropageX, 0x94),
-        new(Tax, Implied, 0xAA),
-        new(Tay, Implied, 0xA8),
-        new(Tsx, Implied, 0xBA),
-        new(Txa, Implied, 0x8A),
-        new(Txs, Implied, 0x9A),
-        new(Tya, Implied, 0x98),
-    ]
+    let mut list = vec![
+        new(Adc, Absolute, 0x6D, 4),
+        new(Adc, AbsoluteX, 0x7D, 4),
+        new(Adc, AbsoluteY, 0x79, 4),
+        new(Adc, Immediate, 0x69, 2),
+        new(Adc, IndirectY, 0x71, 5),
+        new(Adc, XIndirect, 0x61, 6),
+        new(Adc, Zeropage, 0x65, 3),
+        new(Adc, ZeropageX, 0x75, 4),
+        new(Adc, ZeropageIndirect, 0x72, 5),
+        new(And, Absolute, 0x2D, 4),
+        new(And, AbsoluteX, 0x3D, 4),
+        new(And, AbsoluteY, 0x39, 4),
+        new(And, Immediate, 0x29, 2),
+        new(And, IndirectY, 0x31, 5),
+        new(And, XIndirect, 0x21, 6),
+        new(And, Zeropage, 0x25, 3),
+        new(And, ZeropageX, 0x35, 4),
+        new(And, ZeropageIndirect, 0x32, 5),
+        new(Asl, Absolute, 0x0E, 6),
+        new(Asl, AbsoluteX, 0x1E, 6),
+        new(Asl, Accumulator, 0x0A, 2),
+        new(Asl, Zeropage, 0x06, 5),
+        new(Asl, ZeropageX, 0x16, 6),
+        new(Bcc, Relative, 0x90, 2),
+        new(Bcs, Relative, 0xB0, 2),
+        new(Beq, Relative, 0xF0, 2),
+        new(Bit, Absolute, 0x2C, 4),
+        new(Bit, AbsoluteX, 0x3C, 4),
+        new(Bit, Immediate, 0x89, 2),
+        new(Bit, Zeropage, 0x24, 3),
+        new(Bit, ZeropageX, 0x34, 4),
+        new(Bmi, Relative, 0x30, 2),
+        new(Bne, Relative, 0xD0, 2),
+        new(Bpl, Relative, 0x10, 2),
+        new(Bra, Relative, 0x80, 2),
+        new(Brk, Implied, 0x00, 7),
+        new(Bvc, Relative, 0x50, 2),
+        new(Bvs, Relative, 0x70, 2),
+        new(Clc, Implied, 0x18, 2),
+        new(Cld, Implied, 0xD8, 2),
+        new(Cli, Implied, 0x58, 2),
+        new(Clv, Implied, 0xB8, 2),
+        new(Cmp, Absolute, 0xCD, 4),
+        new(Cmp, AbsoluteX, 0xDD, 4),
+        new(Cmp, AbsoluteY, 0xD9, 4),
+        new(Cmp, Immediate, 0xC9, 2),
+        new(Cmp, IndirectY, 0xD1, 5),
+        new(Cmp, XIndirect, 0xC1, 6),
+        new(Cmp, Zeropage, 0xC5, 3),
+        new(Cmp, ZeropageX, 0xD5, 4),
+        new(Cmp, ZeropageIndirect, 0xD2, 5),
+        new(Cpx, Absolute, 0xEC, 4),
+        new(Cpx, Immediate, 0xE0, 2),
+        new(Cpx, Zeropage, 0xE4, 3),
+        new(Cpy, Absolute, 0xCC, 4),
+        new(Cpy, Immediate, 0xC0, 2),
+        new(Cpy, Zeropage, 0xC4, 3),
+        new(Dec, Absolute, 0xCE, 6),
+        new(Dec, AbsoluteX, 0xDE, 7),
+        new(Dec, Accumulator, 0x3A, 2),
+        new(Dec, Zeropage, 0xC6, 5),
+        new(Dec, ZeropageX, 0xD6, 6),
+        new(Dex, Implied, 0xCA, 2),
+        new(Dey, Implied, 0x88, 2),
+        new(Eor, Absolute, 0x4D, 4),
+        new(Eor, AbsoluteX, 0x5D, 4),
+        new(Eor, AbsoluteY, 0x59, 4),
+        new(Eor, Immediate, 0x49, 2),
+        new(Eor, IndirectY, 0x51, 5),
+        new(Eor, XIndirect, 0x41, 6),
+        new(Eor, Zeropage, 0x45, 3),
+        new(Eor, ZeropageX, 0x55, 4),
+        new(Eor, ZeropageIndirect, 0x52, 5),
+        new(Inc, Absolute, 0xEE, 6),
+        new(Inc, AbsoluteX, 0xFE, 7),
+        new(Inc, Accumulator, 0x1A, 2),
+        new(Inc, Zeropage, 0xE6, 5),
+        new(Inc, ZeropageX, 0xF6, 6),
+        new(Inx, Implied, 0xE8, 2),
+        new(Iny, Implied, 0xC8, 2),
+        new(Jmp, Absolute, 0x4C, 3),
+        new(Jmp, Indirect, 0x6C, 6),
+        new(Jmp, AbsoluteIndexedIndirect, 0x7C, 6),
+        new(Jsr, Absolute, 0x20, 6),
+        new(Lda, Absolute, 0xAD, 4),
+        new(Lda, AbsoluteX, 0xBD, 4),
+        new(Lda, AbsoluteY, 0xB9, 4),
+        new(Lda, Immediate, 0xA9, 2),
+        new(Lda, IndirectY, 0xB1, 5),
+        new(Lda, XIndirect, 0xA1, 6),
+        new(Lda, Zeropage, 0xA5, 3),
+        new(Lda, ZeropageX, 0xB5, 4),
+        new(Lda, ZeropageIndirect, 0xB2, 5),
+        new(Ldx, Absolute, 0xAE, 4),
+        new(Ldx, AbsoluteY, 0xBE, 4),
+        new(Ldx, Immediate, 0xA2, 2),
+        new(Ldx, Zeropage, 0xA6, 3),
+        new(Ldx, ZeropageY, 0xB6, 4),
+        new(Ldy, Absolute, 0xAC, 4),
+        new(Ldy, AbsoluteX, 0xBC, 4),
+        new(Ldy, Immediate, 0xA0, 2),
+        new(Ldy, Zeropage, 0xA4, 3),
+        new(Ldy, ZeropageX, 0xB4, 4),
+        new(Lsr, Absolute, 0x4E, 6),
+        new(Lsr, AbsoluteX, 0x5E, 6),
+        new(Lsr, Accumulator, 0x4A, 2),
+        new(Lsr, Zeropage, 0x46, 5),
+        new(Lsr, ZeropageX, 0x56, 6),
+        new(Nop, Implied, 0xEA, 2),
+        new(Ora, Absolute, 0x0D, 4),
+        new(Ora, AbsoluteX, 0x1D, 4),
+        new(Ora, AbsoluteY, 0x19, 4),
+        new(Ora, Immediate, 0x09, 2),
+        new(Ora, IndirectY, 0x11, 5),
+        new(Ora, XIndirect, 0x01, 6),
+        new(Ora, Zeropage, 0x05, 3),
+        new(Ora, ZeropageX, 0x15, 4),
+        new(Ora, ZeropageIndirect, 0x12, 5),
+        new(Pha, Implied, 0x48, 3),
+        new(Php, Implied, 0x08, 3),
+        new(Phx, Implied, 0xDA, 3),
+        new(Phy, Implied, 0x5A, 3),
+        new(Pla, Implied, 0x68, 4),
+        new(Plp, Implied, 0x28, 4),
+        new(Plx, Implied, 0xFA, 4),
+        new(Ply, Implied, 0x7A, 4),
+        new(Rol, Absolute, 0x2E, 6),
+        new(Rol, AbsoluteX, 0x3E, 6),
+        new(Rol, Accumulator, 0x2A, 2),
+        new(Rol, Zeropage, 0x26, 5),
+        new(Rol, ZeropageX, 0x36, 6),
+        new(Ror, Absolute, 0x6E, 6),
+        new(Ror, AbsoluteX, 0x7E, 6),
+        new(Ror, Accumulator, 0x6A, 2),
+        new(Ror, Zeropage, 0x66, 5),
+        new(Ror, ZeropageX, 0x76, 6),
+        new(Rti, Implied, 0x40, 6),
+        new(Rts, Implied, 0x60, 6),
+        new(Sbc, Absolute, 0xED, 4),
+        new(Sbc, AbsoluteX, 0xFD, 4),
+        new(Sbc, AbsoluteY, 0xF9, 4),
+        new(Sbc, Immediate, 0xE9, 2),
+        new(Sbc, IndirectY, 0xF1, 5),
+        new(Sbc, XIndirect, 0xE1, 6),
+        new(Sbc, Zeropage, 0xE5, 3),
+        new(Sbc, ZeropageX, 0xF5, 4),
+        new(Sbc, ZeropageIndirect, 0xF2, 5),
+        new(Sec, Implied, 0x38, 2),
+        new(Sed, Implied, 0xF8, 2),
+        new(Sei, Implied, 0x78, 2),
+        new(Sta, Absolute, 0x8D, 4),
+        new(Sta, AbsoluteX, 0x9D, 5),
+        new(Sta, AbsoluteY, 0x99, 5),
+        new(Sta, IndirectY, 0x91, 6),
+        new(Sta, XIndirect, 0x81, 6),
+        new(Sta, Zeropage, 0x85, 3),
+        new(Sta, ZeropageX, 0x95, 4),
+        new(Sta, ZeropageIndirect, 0x92, 5),
+        new(Stp, Implied, 0xDB, 3),
+        new(Stx, Absolute, 0x8E, 4),
+        new(Stx, Zeropage, 0x86, 3),
+        new(Stx, ZeropageY, 0x96, 4),
+        new(Sty, Absolute, 0x8C, 4),
+        new(Sty, Zeropage, 0x84, 3),
+        new(Sty, ZeropageX, 0x94, 4),
+        new(Stz, Absolute, 0x9C, 4),
+        new(Stz, AbsoluteX, 0x9E, 5),
+        new(Stz, Zeropage, 0x64, 3),
+        new(Stz, ZeropageX, 0x74, 4),
+        new(Tax, Implied, 0xAA, 2),
+        new(Tay, Implied, 0xA8, 2),
+        new(Trb, Absolute, 0x1C, 6),
+        new(Trb, Zeropage, 0x14, 5),
+        new(Tsb, Absolute, 0x0C, 6),
+        new(Tsb, Zeropage, 0x04, 5),
+        new(Tsx, Implied, 0xBA, 2),
+        new(Txa, Implied, 0x8A, 2),
+        new(Txs, Implied, 0x9A, 2),
+        new(Tya, Implied, 0x98, 2),
+        new(Wai, Implied, 0xCB, 3),
+    ];
+
+    // RMBn, SMBn, BBRn and BBSn encode their bit number n in the opcode's high nibble.
+    for bit in 0..8 {
+        let n = (bit as u8) << 4;
+        list.push(new(RMB[bit], Zeropage, 0x07 | n, 5));
+        list.push(new(SMB[bit], Zeropage, 0x87 | n, 5));
+        list.push(new(BBR[bit], ZeropageRelative, 0x0F | n, 5));
+        list.push(new(BBS[bit], ZeropageRelative, 0x8F | n, 5));
+    }
+
+    list
+}
+
+// The W65C02S's reserved opcodes, which execute as NOPs of various lengths and cycle counts.
+// They're kept apart from opcode_list() so the assembler only ever emits NOP as $EA.
+pub fn reserved_opcode_list() -> Vec<Opcode> {
+    use AddressMode::*; // Absolute, Immediate etc
+    use Mnemonic::*; // Nop
+    let new = Opcode::new;
+    let mut list = vec![
+        new(Nop, Zeropage, 0x44, 3),
+        new(Nop, ZeropageX, 0x54, 4),
+        new(Nop, ZeropageX, 0xD4, 4),
+        new(Nop, ZeropageX, 0xF4, 4),
+        new(Nop, Absolute, 0x5C, 8),
+        new(Nop, Absolute, 0xDC, 4),
+        new(Nop, Absolute, 0xFC, 4),
+    ];
+    for code in [0x02, 0x22, 0x42, 0x62, 0x82, 0xC2, 0xE2] {
+        list.push(new(Nop, Immediate, code, 2));
+    }
+    // columns $x3 and $xB are single-byte, single-cycle NOPs, except for WAI ($CB) and STP ($DB)
+    for hi in 0..16 {
+        list.push(new(Nop, Implied, hi << 4 | 0x03, 1));
+        if hi != 0xC && hi != 0xD {
+            list.push(new(Nop, Implied, hi << 4 | 0x0B, 1));
+        }
+    }
+    list
+}
+
+// Undocumented opcodes of the NMOS 6502, which fill the gaps in its documented instruction set.
+pub fn nmos_undocumented_opcode_list() -> Vec<Opcode> {
+    use AddressMode::*; // Absolute, Immediate etc
+    use Mnemonic::*; // Slo, Lax etc
+    let new = Opcode::new;
+    let mut list = vec![
+        new(Alr, Immediate, 0x4B, 2),
+        new(Anc, Immediate, 0x0B, 2),
+        new(Anc, Immediate, 0x2B, 2),
+        new(Ane, Immediate, 0x8B, 2),
+        new(Arr, Immediate, 0x6B, 2),
+        new(Las, AbsoluteY, 0xBB, 4),
+        new(Lax, Absolute, 0xAF, 4),
+        new(Lax, AbsoluteY, 0xBF, 4),
+        new(Lax, IndirectY, 0xB3, 5),
+        new(Lax, XIndirect, 0xA3, 6),
+        new(Lax, Zeropage, 0xA7, 3),
+        new(Lax, ZeropageY, 0xB7, 4),
+        new(Lxa, Immediate, 0xAB, 2),
+        new(Sax, Absolute, 0x8F, 4),
+        new(Sax, XIndirect, 0x83, 6),
+        new(Sax, Zeropage, 0x87, 3),
+        new(Sax, ZeropageY, 0x97, 4),
+        new(Sbc, Immediate, 0xEB, 2),
+        new(Sbx, Immediate, 0xCB, 2),
+        new(Sha, AbsoluteY, 0x9F, 5),
+        new(Sha, IndirectY, 0x93, 6),
+        new(Shx, AbsoluteY, 0x9E, 5),
+        new(Shy, AbsoluteX, 0x9C, 5),
+        new(Tas, AbsoluteY, 0x9B, 5),
+        new(Nop, Absolute, 0x0C, 4),
+        new(Nop, Zeropage, 0x04, 3),
+        new(Nop, Zeropage, 0x44, 3),
+        new(Nop, Zeropage, 0x64, 3),
+    ];
+    // read-modify-write combinations share the same address modes, at the same column offsets
+    for (mnemonic, base) in [(Slo, 0x00), (Rla, 0x20), (Sre, 0x40), (Rra, 0x60)]
+        .into_iter()
+        .chain([(Dcp, 0xC0), (Isc, 0xE0)])
+    {
+        list.push(new(mnemonic, XIndirect, base | 0x03, 8));
+        list.push(new(mnemonic, Zeropage, base | 0x07, 5));
+        list.push(new(mnemonic, Absolute, base | 0x0F, 6));
+        list.push(new(mnemonic, IndirectY, base | 0x13, 8));
+        list.push(new(mnemonic, ZeropageX, base | 0x17, 6));
+        list.push(new(mnemonic, AbsoluteY, base | 0x1B, 7));
+        list.push(new(mnemonic, AbsoluteX, base | 0x1F, 7));
+    }
+    for code in [0x1A, 0x3A, 0x5A, 0x7A, 0xDA, 0xFA] {
+        list.push(new(Nop, Implied, code, 2));
+    }
+    for code in [0x80, 0x82, 0x89, 0xC2, 0xE2] {
+        list.push(new(Nop, Immediate, code, 2));
+    }
+    for code in [0x14, 0x34, 0x54, 0x74, 0xD4, 0xF4] {
+        list.push(new(Nop, ZeropageX, code, 4));
+    }
+    for code in [0x1C, 0x3C, 0x5C, 0x7C, 0xDC, 0xFC] {
+        list.push(new(Nop, AbsoluteX, code, 4));
+    }
+    for code in [
+        0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2,
+    ] {
+        list.push(new(Jam, Implied, code, 2));
+    }
+    list
+}
+
+// CPU variants, which differ in their instruction sets and a few quirks.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Variant {
+    Nmos6502,      // the original NMOS 6502, as on the original pda6502 board
+    Rockwell65C02, // CMOS 65C02 with the Rockwell bit instructions, but no WAI or STP
+    #[default]
+    Wdc65C02S, // WDC W65C02S, as on pda6502v2
+}
+
+// The documented and reserved/undocumented opcodes of a CPU variant.
+pub fn variant_opcode_lists(variant: Variant) -> (Vec<Opcode>, Vec<Opcode>) {
+    use Mnemonic::*;
+    match variant {
+        Variant::Wdc65C02S => (opcode_list(), reserved_opcode_list()),
+        Variant::Rockwell65C02 => {
+            let documented = opcode_list()
+                .into_iter()
+                .filter(|op| !matches!(op.mnemonic, Wai | Stp))
+                .collect();
+            let mut reserved = reserved_opcode_list();
+            reserved.push(Opcode::new(Nop, AddressMode::Implied, 0xCB, 1));
+            reserved.push(Opcode::new(Nop, AddressMode::Implied, 0xDB, 1));
+            (documented, reserved)
+        }
+        Variant::Nmos6502 => {
+            let documented = opcode_list()
+                .into_iter()
+                .filter(|op| !is_cmos_addition(op))
+                .map(|op| match (op.mnemonic, op.mode) {
+                    // the 65C02 shortened these, and fixed JMP ($xxFF) at the cost of a cycle
+                    (Asl | Lsr | Rol | Ror, AddressMode::AbsoluteX) => Opcode { cycles: 7, ..op },
+                    (Jmp, AddressMode::Indirect) => Opcode { cycles: 5, ..op },
+                    _ => op,
+                })
+                .collect();
+            (documented, nmos_undocumented_opcode_list())
+        }
+    }
+}
+
+// Whether an opcode was introduced by the 65C02, and so is absent from the NMOS 6502.
+fn is_cmos_addition(op: &Opcode) -> bool {
+    use AddressMode::*;
+    use Mnemonic::*;
+    RMB.contains(&op.mnemonic)
+        || SMB.contains(&op.mnemonic)
+        || BBR.contains(&op.mnemonic)
+        || BBS.contains(&op.mnemonic)
+        || matches!(
+            op.mnemonic,
+            Bra | Phx | Phy | Plx | Ply | Stp | Stz | Trb | Tsb | Wai
+        )
+        || matches!(op.mode, ZeropageIndirect | AbsoluteIndexedIndirect)
+        || matches!(
+            (op.mnemonic, op.mode),
+            (Bit, Immediate | ZeropageX | AbsoluteX) | (Inc | Dec, Accumulator)
+        )
 }
 
 pub struct OpcodeByMnemonicAndAddressMode {
@@ -314,9 +606,8 @@ impl OpcodeByMnemonicAndAddressMode {
 
     pub fn get(&self, m: Mnemonic, am: AddressMode) -> Result<Opcode, Error> {
         self.map
-            .get(&m)
-            .unwrap() // all Mnemonic values should be in the HashMap
-            .get(&am) // might be None for this AddressMode
+            .get(&m) // None for mnemonics only the NMOS 6502 has, e.g. ALR
+            .and_then(|modes| modes.get(&am)) // might be None for this AddressMode
             .copied() // Option<&Opcode> -> Option<Opcode>
             .ok_or(Error::IllegalAddressMode(m, am))
     }
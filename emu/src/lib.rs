@@ -1,9 +1,21 @@
+#![allow(clippy::new_without_default)]
+
 pub mod asm;
+pub mod bifrost;
 pub mod bus;
 pub mod cpu;
 pub mod dbginfo;
 pub mod dec;
+pub mod device;
 pub mod isa;
+pub mod loader;
+pub mod memconf;
 pub mod mon;
+pub mod sid;
+pub mod sidlog;
+pub mod spi;
 pub mod sys;
+pub mod trap;
 pub mod uart;
+pub mod via;
+pub mod wav;
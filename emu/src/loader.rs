@@ -0,0 +1,357 @@
+// Loading programs into memory from the formats 6502 toolchains produce: raw binaries at an
+// explicit address, Intel HEX, Motorola S-records, C64-style PRG files that start with their
+// load address, and ld65's output files placed by the segments of its debug info or map file.
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::bus::Bus;
+use crate::dbginfo;
+use crate::memconf;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Raw,
+    IntelHex,
+    SRecord,
+    Prg,
+}
+
+// A run of bytes to place at an address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub name: Option<String>, // the ld65 segment, if that's where it came from
+    pub addr: u32,
+    pub data: Vec<u8>,
+}
+
+// A program to load: its segments, and where to start it if the file says.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub segments: Vec<Segment>,
+    pub start: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    // a raw binary, or a PRG too short to have one, has no load address
+    NoAddress,
+    // len bytes at addr don't fit in the 64 KiB address space
+    Range { addr: u32, len: usize },
+    // a malformed record in a HEX or S-record file
+    Syntax { line: usize, message: String },
+    // a record's checksum doesn't match
+    Checksum { line: usize },
+    Io { path: String, message: String },
+    // an ld65 output file is too short for a segment placed in it
+    MissingOutput { segment: String, path: String },
+}
+
+impl Image {
+    // Add bytes at addr, extending the last segment if they follow on from it.
+    fn add(&mut self, addr: u32, data: &[u8]) {
+        if let Some(last) = self.segments.last_mut() {
+            if last.name.is_none() && last.addr + last.data.len() as u32 == addr {
+                last.data.extend_from_slice(data);
+                return;
+            }
+        }
+        self.segments.push(Segment {
+            name: None,
+            addr,
+            data: data.to_vec(),
+        });
+    }
+
+    // Check every segment fits in the address space, then write them all to the bus.
+    pub fn load(&self, bus: &mut Bus) -> Result<(), LoadError> {
+        for seg in &self.segments {
+            if seg.addr as usize + seg.data.len() > 0x10000 {
+                return Err(LoadError::Range {
+                    addr: seg.addr,
+                    len: seg.data.len(),
+                });
+            }
+        }
+        for seg in &self.segments {
+            bus.load(seg.addr as u16, seg.data.clone())
+                .map_err(|_| LoadError::Range {
+                    addr: seg.addr,
+                    len: seg.data.len(),
+                })?;
+        }
+        Ok(())
+    }
+}
+
+// Guess the format of a file from its name, then its contents.
+pub fn detect(path: &str, data: &[u8]) -> Format {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match ext.as_str() {
+        "hex" | "ihex" | "ihx" => Format::IntelHex,
+        "srec" | "s19" | "s28" | "s37" | "mot" => Format::SRecord,
+        "prg" => Format::Prg,
+        _ => match data {
+            [b':', c, ..] if c.is_ascii_hexdigit() => Format::IntelHex,
+            [b'S', b'0'..=b'9', ..] => Format::SRecord,
+            _ => Format::Raw,
+        },
+    }
+}
+
+// Read a file in whatever format it's in. Raw binaries go at addr, which other formats ignore.
+pub fn load_file(path: &str, addr: Option<u16>) -> Result<Image, LoadError> {
+    let data = read(path)?;
+    parse(detect(path, &data), &data, addr)
+}
+
+pub fn parse(format: Format, data: &[u8], addr: Option<u16>) -> Result<Image, LoadError> {
+    match format {
+        Format::Raw => {
+            let addr = addr.ok_or(LoadError::NoAddress)?;
+            let mut image = Image::default();
+            image.add(addr as u32, data);
+            Ok(image)
+        }
+        Format::Prg => {
+            let [lo, hi, data @ ..] = data else {
+                return Err(LoadError::NoAddress);
+            };
+            let mut image = Image::default();
+            image.add(u16::from_le_bytes([*lo, *hi]) as u32, data);
+            Ok(image)
+        }
+        Format::IntelHex => parse_intel_hex(&String::from_utf8_lossy(data)),
+        Format::SRecord => parse_srecord(&String::from_utf8_lossy(data)),
+    }
+}
+
+// Intel HEX: ":LLAAAATT" then LL data bytes and a checksum, all in hex, e.g.
+//
+//     :03F000004C00F0D1
+//     :00000001FF
+fn parse_intel_hex(text: &str) -> Result<Image, LoadError> {
+    let mut image = Image::default();
+    let mut base: u32 = 0; // from extended segment or linear address records
+    for (i, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+        if line.is_empty() {
+            continue;
+        }
+        let hex = line.strip_prefix(':').ok_or(LoadError::Syntax {
+            line: i,
+            message: "expected ':'".to_string(),
+        })?;
+        let bytes = record_bytes(hex, i)?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(syntax(i, "wrong record length"));
+        }
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return Err(LoadError::Checksum { line: i });
+        }
+        let addr = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let data = &bytes[4..bytes.len() - 1];
+        let word = |data: &[u8]| data.iter().fold(0u32, |n, b| n << 8 | *b as u32);
+        let data_len = match bytes[3] {
+            0x02 | 0x04 => Some(2),
+            0x03 | 0x05 => Some(4),
+            _ => None,
+        };
+        if data_len.is_some_and(|len| len != data.len()) {
+            return Err(syntax(i, "wrong address record length"));
+        }
+        match bytes[3] {
+            0x00 => image.add(base + addr, data),
+            0x01 => break,
+            0x02 => base = word(data) << 4,
+            0x03 => image.start = Some((word(&data[..2]) << 4) + word(&data[2..])),
+            0x04 => base = word(data) << 16,
+            0x05 => image.start = Some(word(data)),
+            t => return Err(syntax(i, &format!("unknown record type {t:02X}"))),
+        }
+    }
+    Ok(image)
+}
+
+// Motorola S-records: "S" and a type digit, then in hex a byte count, an address of 2, 3 or 4
+// bytes depending on the type, data and a checksum, e.g.
+//
+//     S106F0004C00F0CD
+//     S903F0000C
+fn parse_srecord(text: &str) -> Result<Image, LoadError> {
+    let mut image = Image::default();
+    for (i, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+        if line.is_empty() {
+            continue;
+        }
+        let [b'S', kind, ..] = *line.as_bytes() else {
+            return Err(syntax(i, "expected 'S' and a record type"));
+        };
+        let addr_len = match kind {
+            b'0' | b'1' | b'5' | b'9' => 2,
+            b'2' | b'6' | b'8' => 3,
+            b'3' | b'7' => 4,
+            _ => return Err(syntax(i, "unknown record type")),
+        };
+        let bytes = record_bytes(&line[2..], i)?;
+        if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+            return Err(syntax(i, "wrong record length"));
+        }
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0xFF {
+            return Err(LoadError::Checksum { line: i });
+        }
+        if bytes.len() < addr_len + 2 {
+            return Err(syntax(i, "record too short for its address"));
+        }
+        let addr = bytes[1..=addr_len]
+            .iter()
+            .fold(0u32, |n, b| n << 8 | *b as u32);
+        let data = &bytes[1 + addr_len..bytes.len() - 1];
+        match kind {
+            b'1'..=b'3' => image.add(addr, data),
+            b'7'..=b'9' => image.start = Some(addr),
+            _ => {} // header and record counts
+        }
+    }
+    Ok(image)
+}
+
+// The bytes of a record written as pairs of hex digits.
+fn record_bytes(hex: &str, line: usize) -> Result<Vec<u8>, LoadError> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(syntax(line, "odd number of hex digits"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or(syntax(line, "not hex"))
+        })
+        .collect()
+}
+
+fn syntax(line: usize, message: &str) -> LoadError {
+    LoadError::Syntax {
+        line,
+        message: message.to_string(),
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, LoadError> {
+    fs::read(path).map_err(|e| LoadError::Io {
+        path: path.to_string(),
+        message: e.to_string(),
+    })
+}
+
+// An ld65 program placed by the segments in its debug info, each read from its offset in the
+// output file it was written to. Output files are relative to dir. Segments with no output,
+// like BSS, are skipped.
+pub fn from_ld65_debug(info: &dbginfo::Info, dir: &Path) -> Result<Image, LoadError> {
+    let mut image = Image::default();
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    for seg in &info.segments {
+        let Some((oname, offset)) = &seg.output else {
+            continue;
+        };
+        if seg.size == 0 {
+            continue;
+        }
+        if !files.iter().any(|(name, _)| name == oname) {
+            let path = dir.join(oname).to_string_lossy().to_string();
+            files.push((oname.clone(), read(&path)?));
+        }
+        let (_, file) = files.iter().find(|(name, _)| name == oname).unwrap();
+        let data = file
+            .get(*offset..offset + seg.size)
+            .ok_or(LoadError::MissingOutput {
+                segment: seg.name.clone(),
+                path: oname.clone(),
+            })?;
+        image.segments.push(Segment {
+            name: Some(seg.name.clone()),
+            addr: seg.start,
+            data: data.to_vec(),
+        });
+    }
+    Ok(image)
+}
+
+// An ld65 program placed by the segment list of its map file, e.g.
+//
+//     Segment list:
+//     -------------
+//     Name                   Start     End    Size  Align
+//     ----------------------------------------------------
+//     os                    00F000  00F757  000758  00001
+//
+// The map doesn't say which output file a segment is in, so that comes from the linker
+// config's memory areas: ld65 writes an area's file from the area's start, so a segment is at
+// its offset from that. Segments in areas without a file, or written to /dev/null, are skipped.
+pub fn from_ld65_map(map: &str, areas: &[memconf::Area], dir: &Path) -> Result<Image, LoadError> {
+    let mut image = Image::default();
+    let list = map
+        .split_once("Segment list:")
+        .map(|(_, rest)| rest)
+        .unwrap_or("");
+    for line in list.lines().skip(4) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [name, start, _end, size, _align] = fields[..] else {
+            break; // end of the list
+        };
+        let hex = |v: &str| usize::from_str_radix(v, 16).map_err(|e| syntax(0, &e.to_string()));
+        let (start, size) = (hex(start)?, hex(size)?);
+        let Some(area) = areas
+            .iter()
+            .find(|a| a.start as usize <= start && start < a.start as usize + a.size.max(1))
+        else {
+            continue;
+        };
+        let Some(file) = area.file.as_deref().filter(|f| *f != "/dev/null") else {
+            continue;
+        };
+        if size == 0 {
+            continue;
+        }
+        let path = dir.join(file).to_string_lossy().to_string();
+        let offset = start - area.start as usize;
+        let data = read(&path)?
+            .get(offset..offset + size)
+            .ok_or(LoadError::MissingOutput {
+                segment: name.to_string(),
+                path: file.to_string(),
+            })?
+            .to_vec();
+        image.segments.push(Segment {
+            name: Some(name.to_string()),
+            addr: start as u32,
+            data,
+        });
+    }
+    Ok(image)
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::NoAddress => write!(f, "no load address"),
+            LoadError::Range { addr, len } => {
+                write!(f, "{len} bytes at ${addr:04X} don't fit below $10000")
+            }
+            LoadError::Syntax { line, message } => write!(f, "line {line}: {message}"),
+            LoadError::Checksum { line } => write!(f, "line {line}: bad checksum"),
+            LoadError::Io { path, message } => write!(f, "{path}: {message}"),
+            LoadError::MissingOutput { segment, path } => {
+                write!(f, "segment {segment} is past the end of {path}")
+            }
+        }
+    }
+}
+
+impl error::Error for LoadError {}
@@ -1,25 +1,238 @@
-mod asm;
-mod bus;
-mod cpu;
-mod dbginfo;
-mod dec;
-mod isa;
-mod mon;
-mod sys;
-mod uart;
+use std::env;
+use std::fs;
+use std::process;
+
+use pda6502v2emu::isa::Variant;
+use pda6502v2emu::sid::Sid;
+use pda6502v2emu::sys::SysError;
+use pda6502v2emu::{asm, loader, sidlog, sys, trap, wav};
 
 fn main() {
-    let mut sys = sys::Sys::new();
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(|a| a.as_str()) == Some("trap") {
+        process::exit(trapmain(&args[2..]));
+    }
+    if args.get(1).map(|a| a.as_str()) == Some("sidlog") {
+        process::exit(sidlogmain(&args[2..]));
+    }
+
+    let mut sys = match sys::Sys::new() {
+        Ok(sys) => sys,
+        Err(e) => {
+            eprintln!("{e}");
+            process::exit(2);
+        }
+    };
+    sys.set_break_on_protected_write(args.iter().any(|a| a == "--break-on-protected-write"));
     sys.reset();
 
+    // a program to run after the OS boots, as --load <file>, or --load <file.bin>@<hex addr>
+    if let Some(arg) = args.iter().skip_while(|a| *a != "--load").nth(1) {
+        let (path, addr) = match arg.rsplit_once('@') {
+            Some((path, addr)) => match u16::from_str_radix(addr, 16) {
+                Ok(addr) => (path, Some(addr)),
+                Err(_) => {
+                    eprintln!("--load: not a hex address: {addr}");
+                    process::exit(2);
+                }
+            },
+            None => (arg.as_str(), None),
+        };
+        if let Err(e) = loader::load_file(path, addr).and_then(|image| sys.load(&image)) {
+            eprintln!("{path}: {e}");
+            process::exit(2);
+        }
+    }
+
+    // record the SID's output, as --wav <file> [--sample-rate <Hz>]
+    if let Some(path) = args.iter().skip_while(|a| *a != "--wav").nth(1) {
+        let rate = match args.iter().skip_while(|a| *a != "--sample-rate").nth(1) {
+            Some(rate) => rate.parse().unwrap_or_else(|_| {
+                eprintln!("--sample-rate: not a number: {rate}");
+                process::exit(2);
+            }),
+            None => 44100,
+        };
+        if let Err(e) = sys.record_wav(path, rate) {
+            eprintln!("{path}: {e}");
+            process::exit(2);
+        }
+    }
+
+    // log writes to the SID's registers, as --sid-log <file>
+    if let Some(path) = args.iter().skip_while(|a| *a != "--sid-log").nth(1) {
+        if let Err(e) = sys.record_sid_log(path) {
+            eprintln!("{path}: {e}");
+            process::exit(2);
+        }
+    }
+
     // go away for now, rust unused code detector
     if false {
         oldmain();
     }
 
-    // run some instructions
-    loop {
-        sys.step();
+    // run until STP halts the CPU
+    let status = match sys.run() {
+        Ok(()) => {
+            println!("STP: CPU stopped");
+            0
+        }
+        Err(SysError::Cpu(e)) => {
+            eprintln!("{}", sys.crash_report(&e));
+            1
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    };
+    if let Err(e) = sys.flush_wav() {
+        eprintln!("writing WAV: {e}");
+    }
+    if let Err(e) = sys.flush_sid_log() {
+        eprintln!("writing SID log: {e}");
+    }
+    process::exit(status);
+}
+
+// Print a SID write log as a table of registers per frame, and optionally replay it into a WAV:
+//
+//     cargo run --release -- sidlog tune.sidw --wav tune.wav --sample-rate 48000
+fn sidlogmain(args: &[String]) -> i32 {
+    const USAGE: &str = "usage: sidlog <log> [--wav <file> [--sample-rate <Hz>]]";
+    let Some(path) = args.first() else {
+        eprintln!("{USAGE}");
+        return 2;
+    };
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return 2;
+        }
+    };
+    let writes = match sidlog::decode(&data) {
+        Ok(writes) => writes,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return 1;
+        }
+    };
+    print!("{}", sidlog::frame_table(&writes, sidlog::FRAME_CYCLES));
+
+    if let Some(out) = args.iter().skip_while(|a| *a != "--wav").nth(1) {
+        let rate = match args.iter().skip_while(|a| *a != "--sample-rate").nth(1) {
+            Some(rate) => match rate.parse() {
+                Ok(rate) => rate,
+                Err(_) => {
+                    eprintln!("--sample-rate: not a number: {rate}");
+                    return 2;
+                }
+            },
+            None => 44100,
+        };
+        let mut sid = Sid::new();
+        sid.set_sample_rate(Some(rate));
+        if let Err(e) = sidlog::replay(&writes, &mut sid) {
+            eprintln!("{path}: {e}");
+            return 1;
+        }
+        if let Err(e) = wav::create(out, rate).and_then(|mut w| w.write(&sid.take_samples())) {
+            eprintln!("{out}: {e}");
+            return 1;
+        }
+    }
+    0
+}
+
+// Run a self-checking test binary until it traps, e.g. Klaus Dormann's functional test:
+//
+//     cargo run --release -- trap 6502_functional_test.bin 0 400 3469 6502_functional_test.lst
+fn trapmain(args: &[String]) -> i32 {
+    const USAGE: &str = "usage: trap <image.bin> <load> <start> <success> [listing.lst] \
+                         [--variant nmos|rockwell|wdc]";
+    let variant = match args.iter().skip_while(|a| *a != "--variant").nth(1) {
+        Some(v) if v == "nmos" => Variant::Nmos6502,
+        Some(v) if v == "rockwell" => Variant::Rockwell65C02,
+        Some(v) if v == "wdc" => Variant::Wdc65C02S,
+        Some(v) => {
+            eprintln!("--variant: not a CPU variant: {v}\n{USAGE}");
+            return 2;
+        }
+        None if args.iter().any(|a| a == "--variant") => {
+            eprintln!("{USAGE}");
+            return 2;
+        }
+        None => Variant::Wdc65C02S,
+    };
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--variant" {
+            iter.next();
+        } else {
+            positional.push(arg);
+        }
+    }
+    let [image, load, start, success, rest @ ..] = positional.as_slice() else {
+        eprintln!("{USAGE}");
+        return 2;
+    };
+    if rest.len() > 1 {
+        eprintln!("{USAGE}");
+        return 2;
+    }
+    let hex = |arg: &str| {
+        let digits = arg.trim_start_matches('$').trim_start_matches("0x");
+        u16::from_str_radix(digits, 16).map_err(|_| format!("not a hex address: {arg}"))
+    };
+    let (load, start, success) = match (hex(load), hex(start), hex(success)) {
+        (Ok(load), Ok(start), Ok(success)) => (load, start, success),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            eprintln!("{e}\n{USAGE}");
+            return 2;
+        }
+    };
+
+    let image = match fs::read(image) {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("{image}: {e}");
+            return 2;
+        }
+    };
+    let mut runner = match trap::Runner::new(variant, &image, load, start) {
+        Ok(runner) => runner,
+        Err(e) => {
+            eprintln!("{e}");
+            return 2;
+        }
+    };
+    runner.success(success);
+    if let Some(path) = rest.first() {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("{path}: {e}");
+                return 2;
+            }
+        };
+        runner.listing(trap::Listing::parse(&text));
+    }
+    match runner.run() {
+        Ok(report) => {
+            println!("{report}");
+            if report.passed {
+                0
+            } else {
+                1
+            }
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
     }
 }
 
@@ -101,10 +314,10 @@ fn oldmain() {
         .rti()
         .print_listing();
 
-    let mut sys = sys::Sys::new();
+    let mut sys = sys::Sys::new().unwrap();
 
     // preload program to RAM
-    sys.bus.load(asm.org, asm.assemble().unwrap());
+    sys.bus.load(asm.org, asm.assemble().unwrap()).unwrap();
 
     // set reset vector to program address
     sys.bus.write(0xFFFC, asm.org as u8);
@@ -114,6 +327,6 @@ fn oldmain() {
 
     // run some instructions
     for _ in 0..80 {
-        sys.step();
+        sys.step().unwrap();
     }
 }
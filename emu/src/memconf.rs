@@ -0,0 +1,138 @@
+use std::{error::Error, fs, ops::RangeInclusive};
+
+use crate::bus::Protection;
+
+// Memory areas of an ld65 linker config, e.g. os/memory.conf:
+//
+//     MEMORY {
+//       os:
+//         start $F000,
+//         size 4096,
+//         type ro,
+//         file "os.rom";
+//     }
+//
+// An area's protection on the emulated bus comes from "type ro", or from an "emu:" comment,
+// which ld65 ignores: "# emu: read-only", "# emu: write-ignored" or "# emu: write-trap". The
+// comment belongs to the area whose semicolon ends its line, or else to the area being
+// defined, and overrides the type.
+
+pub fn load(path: &str) -> Result<Vec<Area>, Box<dyn Error>> {
+    parse(&fs::read_to_string(path)?)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Area {
+    pub name: String,
+    pub start: u16,
+    pub size: usize,
+    pub file: Option<String>,
+    pub protection: Option<Protection>,
+}
+
+impl Area {
+    // The addresses the area spans, clipped to the 64 KiB address space.
+    pub fn range(&self) -> RangeInclusive<u16> {
+        let end = (self.start as usize + self.size.max(1) - 1).min(0xFFFF);
+        self.start..=end as u16
+    }
+}
+
+// Prefix of emu: directives once pulled out of their comments, which can't appear in ld65
+// config syntax otherwise.
+const DIRECTIVE: &str = "@emu:";
+
+pub fn parse(data: &str) -> Result<Vec<Area>, Box<dyn Error>> {
+    // drop comments, keeping emu: directives as tokens in place of theirs, or before the
+    // line's last semicolon so a trailing comment stays with the statement it ends
+    let mut text = String::new();
+    for line in data.lines() {
+        let (code, comment) = line.split_once('#').unwrap_or((line, ""));
+        let (code, end) = match code.rsplit_once(';') {
+            Some((code, end)) => (code, Some(end)),
+            None => (code, None),
+        };
+        text.push_str(code);
+        if let Some(directive) = comment.trim().strip_prefix("emu:") {
+            text.push_str(&format!(" {DIRECTIVE}{} ", directive.trim()));
+        }
+        if let Some(end) = end {
+            text.push(';');
+            text.push_str(end);
+        }
+        text.push('\n');
+    }
+
+    let Some(block) = section(&text, "MEMORY") else {
+        return Ok(Vec::new());
+    };
+
+    let mut areas = Vec::new();
+    for statement in block.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+        let mut statement = statement.to_string();
+        let mut directive = None;
+        while let Some(i) = statement.find(DIRECTIVE) {
+            let rest = &statement[i + DIRECTIVE.len()..];
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            directive = Some(protection(&rest[..end])?);
+            statement.replace_range(i..i + DIRECTIVE.len() + end, "");
+        }
+        let (name, attrs) = statement
+            .split_once(':')
+            .ok_or(format!("memory area without a name: {statement}"))?;
+
+        let mut area = Area {
+            name: name.trim().to_string(),
+            start: 0,
+            size: 0,
+            file: None,
+            protection: directive,
+        };
+        for attr in attrs.split(',').map(str::trim).filter(|a| !a.is_empty()) {
+            let (key, value) = attr
+                .split_once(|c: char| c == '=' || c.is_whitespace())
+                .ok_or(format!("attribute without a value: {attr}"))?;
+            let value = value.trim().trim_start_matches('=').trim();
+            match key.to_lowercase().as_str() {
+                "start" => area.start = number(value)?.try_into()?,
+                "size" => area.size = number(value)?,
+                "file" => area.file = Some(value.trim_matches('"').to_string()),
+                "type" if value.eq_ignore_ascii_case("ro") => {
+                    area.protection = area.protection.or(Some(Protection::ReadOnly))
+                }
+                _ => {}
+            }
+        }
+        areas.push(area);
+    }
+    Ok(areas)
+}
+
+// The contents of the braces after a section name, e.g. MEMORY { ... }.
+fn section<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let start = text.find(name)? + name.len();
+    let open = start + text[start..].find('{')? + 1;
+    let close = open + text[open..].find('}')?;
+    Some(&text[open..close])
+}
+
+// A number in ld65 syntax: $hex, %binary or decimal.
+fn number(value: &str) -> Result<usize, Box<dyn Error>> {
+    let n = if let Some(hex) = value.strip_prefix('$') {
+        usize::from_str_radix(hex, 16)?
+    } else if let Some(bin) = value.strip_prefix('%') {
+        usize::from_str_radix(bin, 2)?
+    } else {
+        value.parse()?
+    };
+    Ok(n)
+}
+
+fn protection(directive: &str) -> Result<Protection, Box<dyn Error>> {
+    match directive {
+        "read-only" => Ok(Protection::ReadOnly),
+        "write-ignored" => Ok(Protection::WriteIgnored),
+        "write-trap" => Ok(Protection::WriteTrap),
+        _ => Err(format!("unknown emu: directive: {directive}").into()),
+    }
+}
@@ -6,9 +6,10 @@ use crate::cpu;
 use crate::dbginfo;
 use crate::isa;
 
-use crate::bus::Bus;
-use crate::cpu::Cpu;
+use crate::bus::{Bus, ProtectedWrite};
+use crate::cpu::{Cpu, CpuError};
 use crate::dec::Decoder;
+use crate::sys::Pin;
 
 lazy_static! {
     static ref STAT_INACTIVE_RE: Regex = Regex::new(r"[NVBDIZC]").unwrap();
@@ -55,16 +56,22 @@ impl Reg {
 }
 
 impl Monitor {
-    pub fn new() -> Self {
+    // A monitor naming addresses by the labels in dbginfo, e.g. the OS's.
+    pub fn new(dbginfo: dbginfo::Info) -> Self {
+        Self::with_variant(isa::Variant::default(), dbginfo)
+    }
+
+    // A monitor disassembling the instruction set of the given CPU variant.
+    pub fn with_variant(variant: isa::Variant, dbginfo: dbginfo::Info) -> Self {
         Self {
-            decoder: Decoder::new(),
+            decoder: Decoder::new(variant),
             prev_reg: Reg::default(),
-            dbginfo: dbginfo::load("../os/debug.out").unwrap(),
+            dbginfo,
         }
     }
 
     pub fn reset(&self, bus: &mut Bus) {
-        let addr = bus.read_u16(cpu::VEC_RES);
+        let addr = bus.peek_u16(cpu::VEC_RES);
         let label = self.dbginfo.label(addr).unwrap_or("");
         println!(
             "RESET: VEC_RES {:#06X} -> {} {:#06X}",
@@ -74,10 +81,16 @@ impl Monitor {
         );
     }
 
-    pub fn step(&mut self, bus: &mut Bus, cpu: &Cpu) {
+    pub fn step(&mut self, bus: &mut Bus, cpu: &Cpu, cycles: u64) {
+        let leds = bus
+            .leds()
+            .map(|l| format!(" L:{}", leds(l).replace('●', "\x1b[22;91m●\x1b[2;39m")))
+            .unwrap_or_default();
         print!(
-            "\x1b[2mPC:{:04X} S:{} A:{} X:{} Y:{} P:{}\x1b[0m  ",
+            "\x1b[2m{:>10} PC:{:04X}{} S:{} A:{} X:{} Y:{} P:{}{leds}\x1b[0m  ",
+            cycles,
             cpu.pc,
+            physical(bus, cpu.pc),
             diff(cpu.s, self.prev_reg.s, "22;32", "2;39"),
             diff(cpu.a, self.prev_reg.a, "22;32", "2;39"),
             diff(cpu.x, self.prev_reg.x, "22;32", "2;39"),
@@ -86,7 +99,7 @@ impl Monitor {
         );
         self.prev_reg.update(cpu);
 
-        let code = bus.read(cpu.pc);
+        let code = bus.peek(cpu.pc);
         let opcode = self.decoder.opcode(code);
 
         match opcode {
@@ -101,6 +114,52 @@ impl Monitor {
         }
     }
 
+    // Report an input pin of the CPU changing level.
+    pub fn pin(&self, cycles: u64, pin: Pin, asserted: bool) {
+        println!(
+            "\x1b[1;33mPIN\x1b[0m {pin} {} at cycle {cycles}",
+            if asserted { "asserted" } else { "released" }
+        );
+    }
+
+    // Report a write to protected RAM by the instruction at pc.
+    pub fn protected_write(&self, pc: u16, write: &ProtectedWrite) {
+        println!(
+            "\x1b[1;31mPROTECTED WRITE\x1b[0m {:?}: ${:04X} {}<- #${:02X} by ${pc:04X} {}",
+            write.protection,
+            write.addr,
+            self.label(write.addr),
+            write.data,
+            self.label(pc),
+        );
+    }
+
+    // A report of the CPU state when it couldn't execute an instruction: the error, where it
+    // happened, the registers, the bytes at PC and the top of the stack.
+    pub fn crash_report(&self, bus: &mut Bus, cpu: &Cpu, cycles: u64, err: &CpuError) -> String {
+        let pc = err.pc();
+        let location = match self.dbginfo.nearest_label(pc) {
+            Some((label, 0)) => format!(" ({label})"),
+            Some((label, offset)) => format!(" ({label}+{offset})"),
+            None => "".to_string(),
+        };
+        let code: Vec<String> = (0..4)
+            .map(|i| format!("{:02X}", bus.peek(pc.wrapping_add(i))))
+            .collect();
+        let stack: Vec<String> = (cpu.s as u16 + 1..=0xFF)
+            .take(8)
+            .map(|s| format!("{:02X}", bus.peek(0x0100 | s)))
+            .collect();
+        format!(
+            "CPU error: {err}\n  at ${pc:04X}{}{location} after {cycles} cycles\n  {cpu:?}\n  \
+             code  ${pc:04X}: {}\n  stack $01{:02X}: {}",
+            physical(bus, pc),
+            code.join(" "),
+            cpu.s.wrapping_add(1),
+            stack.join(" ")
+        )
+    }
+
     fn label(&self, addr: u16) -> String {
         self.dbginfo
             .label(addr)
@@ -113,25 +172,33 @@ impl Monitor {
 
         let addr = cpu.pc + 1; // operand address; one byte after the opcode
         let operand = match opcode.mode {
-            isa::AddressMode::Absolute => asm::Operand::Abs(asm::Addr::Literal(bus.read_u16(addr))), // $LLHH
+            isa::AddressMode::Absolute => asm::Operand::Abs(asm::Addr::Literal(bus.peek_u16(addr))), // $LLHH
             isa::AddressMode::AbsoluteX => {
-                asm::Operand::AbsX(asm::Addr::Literal(bus.read_u16(addr)))
+                asm::Operand::AbsX(asm::Addr::Literal(bus.peek_u16(addr)))
             } // $LLHH,X
             isa::AddressMode::AbsoluteY => {
-                asm::Operand::AbsY(asm::Addr::Literal(bus.read_u16(addr)))
+                asm::Operand::AbsY(asm::Addr::Literal(bus.peek_u16(addr)))
             } // $LLHH,Y
             isa::AddressMode::Accumulator => asm::Operand::A,
-            isa::AddressMode::Immediate => asm::Operand::Imm(bus.read(addr)), // $BB
+            isa::AddressMode::Immediate => asm::Operand::Imm(bus.peek(addr)), // $BB
             isa::AddressMode::Implied => asm::Operand::Impl,
-            isa::AddressMode::Indirect => asm::Operand::Ind(asm::Addr::Literal(bus.read_u16(addr))), // ($LLHH)
-            isa::AddressMode::IndirectY => asm::Operand::IndY(bus.read(addr)), // ($LL),Y
+            isa::AddressMode::Indirect => asm::Operand::Ind(asm::Addr::Literal(bus.peek_u16(addr))), // ($LLHH)
+            isa::AddressMode::AbsoluteIndexedIndirect => {
+                asm::Operand::AbsXInd(asm::Addr::Literal(bus.peek_u16(addr)))
+            } // ($LLHH,X)
+            isa::AddressMode::IndirectY => asm::Operand::IndY(bus.peek(addr)), // ($LL),Y
             isa::AddressMode::Relative => {
-                asm::Operand::Rel(asm::BranchTarget::Offset(bus.read(addr) as i8))
+                asm::Operand::Rel(asm::BranchTarget::Offset(bus.peek(addr) as i8))
             } // $BB (signed)
-            isa::AddressMode::XIndirect => asm::Operand::XInd(bus.read(addr)), // ($LL,X)
-            isa::AddressMode::Zeropage => asm::Operand::Z(bus.read(addr)),     // $LL
-            isa::AddressMode::ZeropageX => asm::Operand::ZX(bus.read(addr)),   // $LL,X
-            isa::AddressMode::ZeropageY => asm::Operand::ZY(bus.read(addr)),   // $LL,Y
+            isa::AddressMode::XIndirect => asm::Operand::XInd(bus.peek(addr)), // ($LL,X)
+            isa::AddressMode::Zeropage => asm::Operand::Z(bus.peek(addr)),     // $LL
+            isa::AddressMode::ZeropageX => asm::Operand::ZX(bus.peek(addr)),   // $LL,X
+            isa::AddressMode::ZeropageY => asm::Operand::ZY(bus.peek(addr)),   // $LL,Y
+            isa::AddressMode::ZeropageIndirect => asm::Operand::ZInd(bus.peek(addr)), // ($LL)
+            isa::AddressMode::ZeropageRelative => asm::Operand::ZRel(
+                bus.peek(addr),
+                asm::BranchTarget::Offset(bus.peek(addr.wrapping_add(1)) as i8),
+            ), // $LL,$BB (signed)
         };
 
         let comment: Option<String> = match operand {
@@ -170,47 +237,81 @@ impl Monitor {
             asm::Operand::Impl => None,
             asm::Operand::Ind(ref addr) => match addr {
                 asm::Addr::Literal(val) => {
-                    let indirect = bus.read_u16(*val);
+                    let indirect = bus.peek_u16(*val);
                     Some(format!(
                         "→ ${:04X} -> #${:02X}",
                         indirect,
-                        bus.read(indirect)
+                        bus.peek(indirect)
                     ))
                 }
                 asm::Addr::Label(_text) => todo!(),
             },
+            asm::Operand::AbsXInd(ref addr) => match addr {
+                asm::Addr::Literal(val) => {
+                    let indexed = val.wrapping_add(cpu.x as u16);
+                    let indirect = bus.peek_u16(indexed);
+                    Some(format!(
+                        "→ ${:04X} → ${:04X} {}",
+                        indexed,
+                        indirect,
+                        self.label(indirect)
+                    ))
+                }
+                asm::Addr::Label(_) => None,
+            },
             asm::Operand::XInd(zp) => {
                 let indirect = zp.wrapping_add(cpu.x) as u16;
                 Some(format!(
                     "→ ${:02X} → #${:02X}",
                     indirect,
-                    bus.read(indirect)
+                    bus.peek(indirect)
                 ))
             }
             asm::Operand::IndY(zp) => {
-                let indirect = bus.read_u16(zp as u16);
+                let indirect = bus.peek_u16(zp as u16);
                 let indexed = indirect.wrapping_add(cpu.y as u16);
                 Some(format!(
                     "→ ${:04X},Y → ${:04X} → #${:02X}",
                     indirect,
                     indexed,
-                    bus.read(indexed)
+                    bus.peek(indexed)
+                ))
+            }
+            asm::Operand::ZInd(zp) => {
+                let indirect = bus.peek_u16(zp as u16);
+                Some(format!(
+                    "→ ${:04X} → #${:02X}",
+                    indirect,
+                    bus.peek(indirect)
                 ))
             }
             asm::Operand::Rel(ref target) => match target {
-                asm::BranchTarget::Offset(offset) => Some(format!(
-                    "→ ${:04X}",
-                    cpu.pc.wrapping_add_signed(*offset as i16) as u16
-                )),
+                asm::BranchTarget::Offset(offset) => {
+                    let target = addr.wrapping_add(1).wrapping_add_signed(*offset as i16);
+                    Some(format!("→ ${:04X} {}", target, self.label(target)))
+                }
                 asm::BranchTarget::Label(_text) => todo!(),
             },
-            asm::Operand::Z(zp) => Some(format!("→ #${:02X}", bus.read(zp as u16))),
+            asm::Operand::ZRel(zp, ref target) => match target {
+                asm::BranchTarget::Offset(offset) => {
+                    let target = addr.wrapping_add(2).wrapping_add_signed(*offset as i16);
+                    Some(format!(
+                        "→ #${:02X}:{:#010b} → ${:04X} {}",
+                        bus.peek(zp as u16),
+                        bus.peek(zp as u16),
+                        target,
+                        self.label(target)
+                    ))
+                }
+                asm::BranchTarget::Label(_) => None,
+            },
+            asm::Operand::Z(zp) => Some(format!("→ #${:02X}", bus.peek(zp as u16))),
             asm::Operand::ZX(zp) => {
                 let indexed = zp.wrapping_add(cpu.x);
                 Some(format!(
                     "→ ${:02X} → #${:02X}",
                     indexed,
-                    bus.read(indexed as u16)
+                    bus.peek(indexed as u16)
                 ))
             }
             asm::Operand::ZY(zp) => {
@@ -218,7 +319,7 @@ impl Monitor {
                 Some(format!(
                     "→ ${:02X} → #${:02X}",
                     indexed,
-                    bus.read(indexed as u16)
+                    bus.peek(indexed as u16)
                 ))
             }
         };
@@ -237,6 +338,14 @@ impl Monitor {
     }
 }
 
+// The BLINKEN LEDs as a status line, leftmost first: "●" for each lit, "○" for each dark.
+pub fn leds(leds: u8) -> String {
+    (0..8)
+        .rev()
+        .map(|i| if leds & 1 << i != 0 { '●' } else { '○' })
+        .collect()
+}
+
 fn diff(a: u8, b: u8, style: &str, reset: &str) -> String {
     if a == b {
         format!("{a:02X}")
@@ -244,3 +353,10 @@ fn diff(a: u8, b: u8, style: &str, reset: &str) -> String {
         format!("\x1b[{style}m{a:02X}\x1b[{reset}m")
     }
 }
+
+// Where addr is in physical RAM, e.g. "@7F000", or "" if it isn't RAM.
+fn physical(bus: &Bus, addr: u16) -> String {
+    bus.physical(addr)
+        .map(|phys| format!("@{phys:05X}"))
+        .unwrap_or_default()
+}
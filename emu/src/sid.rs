@@ -0,0 +1,534 @@
+use std::f32::consts::PI;
+
+use crate::device::Device;
+
+// The SID has 5 address lines; adec.v repeats its 32 registers across $D400-$D7FF.
+pub const SIZE: usize = 32;
+
+// The SID's clock: pda6502v2 runs it at 1 MHz, which ARMSID requires.
+pub const CLOCK_HZ: u32 = 1_000_000;
+
+// Which SID the ARMSID is emulating. They differ mostly in the filter: the 6581's cutoff
+// frequency curve is far from linear, and its mixer has a DC offset that makes volume changes
+// audible, which C64 programs used to play samples.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Model {
+    #[default]
+    Mos6581,
+    Mos8580,
+}
+
+// Sid is the MOS 6581/8580 Sound Interface Device: three voices, each an oscillator with
+// triangle, sawtooth, pulse and noise waveforms shaped by an ADSR envelope generator, then a
+// multimode filter and master volume.
+//
+// It's clocked cycle by cycle. With a sample rate set, it also renders its audio output,
+// averaging the cycles of each sample, for the host to take.
+//
+// Voice 3's oscillator and envelope read back through OSC3 and ENV3; programs use its noise
+// waveform as a random number generator, which is deterministic from reset, or from a seed.
+// The write-only registers read as the last value written to any register, since that's what
+// lingers on the SID's data bus.
+pub struct Sid {
+    model: Model,
+    regs: [u8; Self::WRITE_REGS], // write-only registers, as last written
+    voices: [Voice; 3],
+    filter: Filter,
+    bus_latch: u8,
+    pots: [u8; 2],   // POTX, POTY
+    noise_seed: u32, // the noise shift register's value on reset, and while testing
+
+    output: Option<Output>, // rendering, when a sample rate is set
+}
+
+#[derive(Default)]
+struct Voice {
+    acc: u32,       // 24-bit phase accumulator
+    noise: u32,     // 23-bit noise shift register
+    msb_rose: bool, // the accumulator's MSB rose this cycle, for syncing the next voice
+    env: Envelope,
+}
+
+#[derive(Default)]
+struct Envelope {
+    state: EnvState,
+    level: u8,
+    rate_counter: u16,
+    exp_counter: u8, // decay and release slow down as the level falls
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+enum EnvState {
+    Attack,
+    DecaySustain,
+    #[default]
+    Release,
+}
+
+// A state-variable filter, as the SID's, integrated once per cycle.
+#[derive(Default)]
+struct Filter {
+    lp: f32,
+    bp: f32,
+    hp: f32,
+    w0: f32,    // cutoff as angular frequency per cycle
+    q_inv: f32, // 1/Q, from the resonance
+}
+
+// Audio rendering state.
+struct Output {
+    sample_rate: u32,
+    phase: u32, // accumulates sample_rate per cycle; a sample is due at every CLOCK_HZ
+    sum: i64,   // of the cycles since the last sample
+    cycles: u32,
+    dc_in: f32, // previous input and output of the DC blocking filter
+    dc_out: f32,
+    dc_pole: f32,
+    samples: Vec<i16>, // rendered, not yet taken
+}
+
+impl Sid {
+    const REG_FREQ_LO: usize = 0x00; // per voice, 7 registers apart
+    const REG_FREQ_HI: usize = 0x01;
+    const REG_PW_LO: usize = 0x02;
+    const REG_PW_HI: usize = 0x03;
+    const REG_CONTROL: usize = 0x04;
+    const REG_ATTACK_DECAY: usize = 0x05;
+    const REG_SUSTAIN_RELEASE: usize = 0x06;
+    const REG_FC_LO: usize = 0x15;
+    const REG_FC_HI: usize = 0x16;
+    const REG_RES_FILT: usize = 0x17;
+    const REG_MODE_VOL: usize = 0x18;
+    const WRITE_REGS: usize = 0x19;
+
+    const REG_POTX: u16 = 0x19;
+    const REG_POTY: u16 = 0x1A;
+    const REG_OSC3: u16 = 0x1B;
+    const REG_ENV3: u16 = 0x1C;
+
+    const REG_READ: [&'static str; 4] = ["POTX", "POTY", "OSC3", "ENV3"];
+
+    // as named by Mapping the Commodore 64, like os/sid.s
+    const REG_WRITE: [&'static str; Self::WRITE_REGS] = [
+        "FRELO1", "FREHI1", "PWLO1", "PWHI1", "VCREG1", "ATDCY1", "SUREL1", "FRELO2", "FREHI2",
+        "PWLO2", "PWHI2", "VCREG2", "ATDCY2", "SUREL2", "FRELO3", "FREHI3", "PWLO3", "PWHI3",
+        "VCREG3", "ATDCY3", "SUREL3", "CUTLO", "CUTHI", "RESON", "SIGVOL",
+    ];
+
+    // voice control register bits
+    const GATE: u8 = 1 << 0;
+    const SYNC: u8 = 1 << 1;
+    const RING: u8 = 1 << 2;
+    const TEST: u8 = 1 << 3;
+    const TRIANGLE: u8 = 1 << 4;
+    const SAWTOOTH: u8 = 1 << 5;
+    const PULSE: u8 = 1 << 6;
+    const NOISE: u8 = 1 << 7;
+
+    // mode/volume register bits
+    const LOWPASS: u8 = 1 << 4;
+    const BANDPASS: u8 = 1 << 5;
+    const HIGHPASS: u8 = 1 << 6;
+    const VOICE3_OFF: u8 = 1 << 7;
+
+    const NOISE_RESET: u32 = 0x7FFFF8;
+
+    // Mixer DC offset of the 6581, in the units of a voice's output.
+    const DC_6581: i32 = -(0xFFF * 0xFF / 18);
+
+    // Three voices at full amplitude and volume are full scale in the rendered samples.
+    const FULL_SCALE: f32 = (3 * 0x800 * 0xFF * 15) as f32;
+
+    pub fn new() -> Self {
+        Self::with_model(Model::default())
+    }
+
+    pub fn with_model(model: Model) -> Self {
+        let mut sid = Self {
+            model,
+            regs: [0x00; Self::WRITE_REGS],
+            voices: Default::default(),
+            filter: Filter::default(),
+            bus_latch: 0x00,
+            pots: [0xFF; 2], // no paddles connected
+            noise_seed: Self::NOISE_RESET,
+            output: None,
+        };
+        sid.reset();
+        sid
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+        self.update_filter();
+    }
+
+    // Set the paddle positions read through POTX and POTY.
+    pub fn set_pots(&mut self, x: u8, y: u8) {
+        self.pots = [x, y];
+    }
+
+    // Seed the noise generators, in place of the SID's own reset value, and reset them. The
+    // 23-bit shift register can't be all zeros, so a seed of 0 restores the SID's.
+    pub fn seed_noise(&mut self, seed: u32) {
+        self.noise_seed = match seed & 0x7FFFFF {
+            0 => Self::NOISE_RESET,
+            seed => seed,
+        };
+        for voice in self.voices.iter_mut() {
+            voice.noise = self.noise_seed;
+        }
+    }
+
+    // Render audio output at sample_rate samples per second, or stop rendering with None.
+    pub fn set_sample_rate(&mut self, sample_rate: Option<u32>) {
+        self.output = sample_rate.map(|sample_rate| Output {
+            sample_rate,
+            phase: 0,
+            sum: 0,
+            cycles: 0,
+            dc_in: 0.0,
+            dc_out: 0.0,
+            // a 5 Hz high-pass, like the coupling capacitor on the SID's audio output
+            dc_pole: 1.0 - 2.0 * PI * 5.0 / sample_rate as f32,
+            samples: Vec::new(),
+        });
+    }
+
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.output.as_ref().map(|o| o.sample_rate)
+    }
+
+    // Take the samples rendered since last taken.
+    pub fn take_samples(&mut self) -> Vec<i16> {
+        match &mut self.output {
+            Some(o) => std::mem::take(&mut o.samples),
+            None => Vec::new(),
+        }
+    }
+
+    fn voice_reg(&self, v: usize, reg: usize) -> u8 {
+        self.regs[v * 7 + reg]
+    }
+
+    fn freq(&self, v: usize) -> u32 {
+        u16::from_le_bytes([
+            self.voice_reg(v, Self::REG_FREQ_LO),
+            self.voice_reg(v, Self::REG_FREQ_HI),
+        ]) as u32
+    }
+
+    fn pulse_width(&self, v: usize) -> u32 {
+        u16::from_le_bytes([
+            self.voice_reg(v, Self::REG_PW_LO),
+            self.voice_reg(v, Self::REG_PW_HI) & 0x0F,
+        ]) as u32
+    }
+
+    // The voice that syncs and ring modulates voice v: voice 3 for voice 1, and so on.
+    fn source(v: usize) -> usize {
+        (v + 2) % 3
+    }
+
+    // Advance by a single clock cycle.
+    fn clock(&mut self) {
+        for v in 0..3 {
+            let control = self.voice_reg(v, Self::REG_CONTROL);
+            let freq = self.freq(v);
+            let voice = &mut self.voices[v];
+            if control & Self::TEST != 0 {
+                voice.acc = 0;
+                voice.noise = self.noise_seed;
+                voice.msb_rose = false;
+                continue;
+            }
+            let prev = voice.acc;
+            voice.acc = (prev + freq) & 0xFFFFFF;
+            voice.msb_rose = prev & 0x800000 == 0 && voice.acc & 0x800000 != 0;
+            // noise is clocked by bit 19
+            if prev & 0x080000 == 0 && voice.acc & 0x080000 != 0 {
+                let bit = (voice.noise >> 22 ^ voice.noise >> 17) & 1;
+                voice.noise = (voice.noise << 1 | bit) & 0x7FFFFF;
+            }
+        }
+        for v in 0..3 {
+            if self.voice_reg(v, Self::REG_CONTROL) & Self::SYNC != 0
+                && self.voices[Self::source(v)].msb_rose
+            {
+                self.voices[v].acc = 0;
+            }
+        }
+        for v in 0..3 {
+            let ad = self.voice_reg(v, Self::REG_ATTACK_DECAY);
+            let sr = self.voice_reg(v, Self::REG_SUSTAIN_RELEASE);
+            self.voices[v].env.clock(ad, sr);
+        }
+
+        if self.output.is_some() {
+            let out = self.mix();
+            let o = self.output.as_mut().unwrap();
+            o.sum += out as i64;
+            o.cycles += 1;
+            o.phase += o.sample_rate;
+            if o.phase >= CLOCK_HZ {
+                o.phase -= CLOCK_HZ;
+                o.sample();
+            }
+        }
+    }
+
+    // The 12-bit output of voice v's waveform generator. Selecting several waveforms ANDs them,
+    // roughly as the SID does.
+    fn waveform(&self, v: usize) -> u32 {
+        let control = self.voice_reg(v, Self::REG_CONTROL);
+        let voice = &self.voices[v];
+        let mut out = 0xFFF;
+        if control & (Self::TRIANGLE | Self::SAWTOOTH | Self::PULSE | Self::NOISE) == 0 {
+            return 0;
+        }
+        if control & Self::TRIANGLE != 0 {
+            let mut msb = voice.acc & 0x800000 != 0;
+            if control & Self::RING != 0 {
+                msb ^= self.voices[Self::source(v)].acc & 0x800000 != 0;
+            }
+            let acc = if msb { !voice.acc } else { voice.acc };
+            out &= acc >> 11 & 0xFFF;
+        }
+        if control & Self::SAWTOOTH != 0 {
+            out &= voice.acc >> 12;
+        }
+        if control & Self::PULSE != 0
+            && control & Self::TEST == 0
+            && voice.acc >> 12 < self.pulse_width(v)
+        {
+            out = 0;
+        }
+        if control & Self::NOISE != 0 {
+            let n = voice.noise;
+            // bits 22, 20, 16, 13, 11, 7, 4 and 2 of the shift register
+            out &= (n >> 11 & 0x800)
+                | (n >> 10 & 0x400)
+                | (n >> 7 & 0x200)
+                | (n >> 5 & 0x100)
+                | (n >> 4 & 0x080)
+                | (n >> 1 & 0x040)
+                | (n << 1 & 0x020)
+                | (n << 2 & 0x010);
+        }
+        out
+    }
+
+    // Voice v's output: its waveform centred on zero, scaled by its envelope.
+    fn voice_output(&self, v: usize) -> i32 {
+        (self.waveform(v) as i32 - 0x800) * self.voices[v].env.level as i32
+    }
+
+    // The mixed output of voices and filter, at the master volume.
+    fn mix(&mut self) -> i32 {
+        let filt = self.regs[Self::REG_RES_FILT];
+        let mode = self.regs[Self::REG_MODE_VOL];
+        let mut direct = 0;
+        let mut filtered = 0;
+        for v in 0..3 {
+            if filt & 1 << v != 0 {
+                filtered += self.voice_output(v);
+            } else if v != 2 || mode & Self::VOICE3_OFF == 0 {
+                direct += self.voice_output(v);
+            }
+        }
+
+        let f = &mut self.filter;
+        f.lp -= f.w0 * f.bp;
+        f.bp -= f.w0 * f.hp;
+        f.hp = f.bp * f.q_inv - f.lp - filtered as f32;
+        let mut out = direct as f32;
+        if mode & Self::LOWPASS != 0 {
+            out += f.lp;
+        }
+        if mode & Self::BANDPASS != 0 {
+            out += f.bp;
+        }
+        if mode & Self::HIGHPASS != 0 {
+            out += f.hp;
+        }
+        if self.model == Model::Mos6581 {
+            out += Self::DC_6581 as f32;
+        }
+        out as i32 * (mode & 0x0F) as i32
+    }
+
+    // Recalculate the filter's coefficients from its registers.
+    fn update_filter(&mut self) {
+        let fc =
+            (self.regs[Self::REG_FC_HI] as u32) << 3 | (self.regs[Self::REG_FC_LO] & 0x07) as u32;
+        let hz = match self.model {
+            Model::Mos6581 => cutoff_6581(fc),
+            Model::Mos8580 => 30.0 + fc as f32 * 12500.0 / 2047.0,
+        };
+        let res = (self.regs[Self::REG_RES_FILT] >> 4) as f32;
+        self.filter.w0 = 2.0 * PI * hz / CLOCK_HZ as f32;
+        self.filter.q_inv = 1.0 / (0.707 + res / 15.0);
+    }
+}
+
+// A typical 6581's filter cutoff frequency in Hz for the 11-bit cutoff register: roughly flat
+// at the bottom, steepest in the middle. Chips varied a lot.
+fn cutoff_6581(fc: u32) -> f32 {
+    const CURVE: [(u32, f32); 8] = [
+        (0, 220.0),
+        (384, 300.0),
+        (640, 800.0),
+        (768, 1600.0),
+        (1024, 4000.0),
+        (1280, 7000.0),
+        (1536, 10000.0),
+        (2047, 18000.0),
+    ];
+    let i = CURVE.iter().position(|&(x, _)| x >= fc).unwrap_or(0).max(1);
+    let ((x0, y0), (x1, y1)) = (CURVE[i - 1], CURVE[i]);
+    y0 + (y1 - y0) * (fc - x0) as f32 / (x1 - x0) as f32
+}
+
+impl Envelope {
+    // Cycles per step of the envelope, for each 4-bit attack, decay or release rate: attack
+    // takes from 2 ms to 8 s to rise, decay and release three times as long to fall.
+    const RATE_PERIODS: [u16; 16] = [
+        9, 32, 63, 95, 149, 220, 267, 313, 392, 977, 1954, 3126, 3907, 11720, 19532, 31251,
+    ];
+
+    fn clock(&mut self, attack_decay: u8, sustain_release: u8) {
+        let rate = match self.state {
+            EnvState::Attack => attack_decay >> 4,
+            EnvState::DecaySustain => attack_decay & 0x0F,
+            EnvState::Release => sustain_release & 0x0F,
+        };
+        self.rate_counter += 1;
+        if self.rate_counter < Self::RATE_PERIODS[rate as usize] {
+            return;
+        }
+        self.rate_counter = 0;
+
+        if self.state == EnvState::Attack {
+            self.level = self.level.saturating_add(1);
+            if self.level == 0xFF {
+                self.state = EnvState::DecaySustain;
+            }
+            return;
+        }
+
+        // falling levels step exponentially slower
+        self.exp_counter += 1;
+        let period = match self.level {
+            94.. => 1,
+            55..=93 => 2,
+            27..=54 => 4,
+            15..=26 => 8,
+            7..=14 => 16,
+            _ => 30,
+        };
+        if self.exp_counter < period {
+            return;
+        }
+        self.exp_counter = 0;
+        let sustain = (sustain_release >> 4) * 0x11;
+        if self.state == EnvState::DecaySustain && self.level <= sustain {
+            return;
+        }
+        self.level = self.level.saturating_sub(1);
+    }
+
+    fn gate(&mut self, on: bool) {
+        self.state = if on {
+            EnvState::Attack
+        } else {
+            EnvState::Release
+        };
+    }
+}
+
+impl Output {
+    // Emit the average of the cycles since the last sample.
+    fn sample(&mut self) {
+        let x = self.sum as f32 / self.cycles.max(1) as f32 / Sid::FULL_SCALE * 32767.0;
+        self.sum = 0;
+        self.cycles = 0;
+        self.dc_out = x - self.dc_in + self.dc_pole * self.dc_out;
+        self.dc_in = x;
+        self.samples
+            .push(self.dc_out.clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+    }
+}
+
+impl Device for Sid {
+    fn name(&self) -> &str {
+        "SID"
+    }
+
+    fn reset(&mut self) {
+        self.regs = [0x00; Self::WRITE_REGS];
+        for voice in self.voices.iter_mut() {
+            *voice = Voice {
+                noise: self.noise_seed,
+                ..Default::default()
+            };
+        }
+        self.filter = Filter::default();
+        self.update_filter();
+    }
+
+    fn step(&mut self, cycles: u8) {
+        for _ in 0..cycles {
+            self.clock();
+        }
+    }
+
+    fn read(&mut self, reg: u16) -> u8 {
+        self.peek(reg)
+    }
+
+    fn write(&mut self, reg: u16, data: u8) {
+        self.bus_latch = data;
+        let reg = reg as usize;
+        if reg >= Self::WRITE_REGS {
+            return;
+        }
+        if reg % 7 == Self::REG_CONTROL && reg < 3 * 7 {
+            let gate = data & Self::GATE != 0;
+            if gate != (self.regs[reg] & Self::GATE != 0) {
+                self.voices[reg / 7].env.gate(gate);
+            }
+        }
+        self.regs[reg] = data;
+        if matches!(reg, Self::REG_FC_LO | Self::REG_FC_HI | Self::REG_RES_FILT) {
+            self.update_filter();
+        }
+    }
+
+    fn peek(&self, reg: u16) -> u8 {
+        match reg {
+            Self::REG_POTX => self.pots[0],
+            Self::REG_POTY => self.pots[1],
+            // the upper 8 bits of voice 3's waveform
+            Self::REG_OSC3 => (self.waveform(2) >> 4) as u8,
+            Self::REG_ENV3 => self.voices[2].env.level,
+            _ => self.bus_latch,
+        }
+    }
+
+    fn name_for_read(&self, reg: u16) -> Option<&str> {
+        match reg {
+            Self::REG_POTX..=Self::REG_ENV3 => {
+                Some(Self::REG_READ[(reg - Self::REG_POTX) as usize])
+            }
+            _ => None,
+        }
+    }
+
+    fn name_for_write(&self, reg: u16) -> Option<&str> {
+        Self::REG_WRITE.get(reg as usize).copied()
+    }
+}
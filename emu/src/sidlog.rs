@@ -0,0 +1,217 @@
+use std::error;
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::device::Device;
+use crate::sid::Sid;
+
+// A log of the CPU's writes to SID registers, timestamped in clock cycles, for analysing and
+// replaying tunes independently of the SID's synthesis.
+//
+// Logs are saved in a compact binary dump: the magic "SIDW" and a version byte, then a record
+// per write of the cycles since the previous write (LEB128), the register, and the data.
+
+// The SID registers logged: the write-only ones, $D400-$D418.
+pub const REGS: usize = 0x19;
+
+// A frame at the OS's 60 Hz tick: VIA2 T1 free-running from 16666, every N+2 cycles.
+pub const FRAME_CYCLES: u64 = 16668;
+
+const MAGIC: &[u8; 4] = b"SIDW";
+const VERSION: u8 = 1;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SidWrite {
+    pub cycle: u64, // when the instruction making the write started
+    pub reg: u8,
+    pub data: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DumpError {
+    Magic,               // not a SID write dump
+    Version(u8),         // a dump format this doesn't know
+    Truncated(usize),    // the dump ends part way through the record at this offset
+    Register(usize, u8), // the record at this offset writes a register that isn't logged
+    Overflow(usize),     // the record at this offset takes the cycle count past u64
+    Order(u64),          // the write at this cycle comes after a later one
+}
+
+// Writer saves a dump incrementally, so it can be flushed as writes are made.
+pub struct Writer<W: Write> {
+    out: W,
+    last_cycle: u64,
+}
+
+// Create a dump file at path.
+pub fn create(path: &str) -> io::Result<Writer<BufWriter<File>>> {
+    Writer::new(BufWriter::new(File::create(path)?))
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        Ok(Self { out, last_cycle: 0 })
+    }
+
+    // Append writes, which must be in cycle order, after any already written, and flush them.
+    // Nothing is written if they're out of order.
+    pub fn write(&mut self, writes: &[SidWrite]) -> io::Result<()> {
+        let mut record = Vec::new();
+        let mut last_cycle = self.last_cycle;
+        for w in writes {
+            let mut delta = w.cycle.checked_sub(last_cycle).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, DumpError::Order(w.cycle))
+            })?;
+            loop {
+                let byte = (delta & 0x7F) as u8;
+                delta >>= 7;
+                if delta == 0 {
+                    record.push(byte);
+                    break;
+                }
+                record.push(byte | 0x80);
+            }
+            record.extend_from_slice(&[w.reg, w.data]);
+            last_cycle = w.cycle;
+        }
+        self.out.write_all(&record)?;
+        self.last_cycle = last_cycle;
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+// Encode a whole dump.
+pub fn encode(writes: &[SidWrite]) -> Vec<u8> {
+    let mut w = Writer::new(Vec::new()).unwrap();
+    w.write(writes).unwrap();
+    w.into_inner()
+}
+
+pub fn decode(data: &[u8]) -> Result<Vec<SidWrite>, DumpError> {
+    let rest = data.strip_prefix(MAGIC).ok_or(DumpError::Magic)?;
+    let (&version, mut rest) = rest.split_first().ok_or(DumpError::Magic)?;
+    if version != VERSION {
+        return Err(DumpError::Version(version));
+    }
+
+    let mut writes = Vec::new();
+    let mut cycle = 0u64;
+    while !rest.is_empty() {
+        let offset = data.len() - rest.len();
+        let mut delta = 0u64;
+        let mut shift = 0;
+        loop {
+            let (&byte, tail) = rest.split_first().ok_or(DumpError::Truncated(offset))?;
+            rest = tail;
+            let bits = (byte & 0x7F) as u64;
+            if shift >= u64::BITS || bits << shift >> shift != bits {
+                return Err(DumpError::Overflow(offset));
+            }
+            delta |= bits << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let [reg, data, tail @ ..] = rest else {
+            return Err(DumpError::Truncated(offset));
+        };
+        if *reg as usize >= REGS {
+            return Err(DumpError::Register(offset, *reg));
+        }
+        cycle = cycle
+            .checked_add(delta)
+            .ok_or(DumpError::Overflow(offset))?;
+        writes.push(SidWrite {
+            cycle,
+            reg: *reg,
+            data: *data,
+        });
+        rest = tail;
+    }
+    Ok(writes)
+}
+
+// A table of the registers written in each frame of frame_cycles, for frames with writes:
+// the frame number and its first cycle, then each register's last value written in the frame,
+// or ".." if it wasn't.
+pub fn frame_table(writes: &[SidWrite], frame_cycles: u64) -> String {
+    let mut table = String::from(" frame      cycle |");
+    for reg in 0..REGS {
+        write!(table, " {reg:02X}").unwrap();
+    }
+    table.push('\n');
+
+    let mut i = 0;
+    while i < writes.len() {
+        let frame = writes[i].cycle / frame_cycles;
+        let mut regs = [None; REGS];
+        while i < writes.len() && writes[i].cycle / frame_cycles == frame {
+            regs[writes[i].reg as usize] = Some(writes[i].data);
+            i += 1;
+        }
+        write!(table, "{frame:>6} {:>10} |", frame * frame_cycles).unwrap();
+        for data in regs {
+            match data {
+                Some(data) => write!(table, " {data:02X}").unwrap(),
+                None => table.push_str(" .."),
+            }
+        }
+        table.push('\n');
+    }
+    table
+}
+
+// Replay writes into sid, clocking it from cycle 0 up to each write in turn, e.g. to render a
+// tune's audio. Writes out of cycle order stop the replay there.
+pub fn replay(writes: &[SidWrite], sid: &mut Sid) -> Result<(), DumpError> {
+    let mut cycle = 0;
+    for w in writes {
+        let mut n = w
+            .cycle
+            .checked_sub(cycle)
+            .ok_or(DumpError::Order(w.cycle))?;
+        while n > 0 {
+            let step = n.min(u8::MAX as u64);
+            sid.step(step as u8);
+            n -= step;
+        }
+        cycle = w.cycle;
+        sid.write(w.reg as u16, w.data);
+    }
+    Ok(())
+}
+
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpError::Magic => write!(f, "not a SID write dump"),
+            DumpError::Version(v) => write!(f, "unknown SID write dump version {v}"),
+            DumpError::Truncated(offset) => {
+                write!(f, "SID write dump truncated in the record at {offset}")
+            }
+            DumpError::Register(offset, reg) => {
+                write!(
+                    f,
+                    "SID write dump record at {offset} writes register ${reg:02X}"
+                )
+            }
+            DumpError::Overflow(offset) => {
+                write!(f, "SID write dump cycle count overflows at {offset}")
+            }
+            DumpError::Order(cycle) => {
+                write!(f, "SID write at cycle {cycle} is out of order")
+            }
+        }
+    }
+}
+
+impl error::Error for DumpError {}
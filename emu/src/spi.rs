@@ -0,0 +1,128 @@
+use std::any::Any;
+
+// BIFRÖST has 8 active-low chip select lines, one peripheral on each.
+pub const CHIP_SELECTS: usize = 8;
+
+// SpiDevice is a peripheral on BIFRÖST's SPI bus, attached to one of its chip selects.
+pub trait SpiDevice: Any {
+    // Short name, e.g. "FLASH".
+    fn name(&self) -> &str;
+
+    // The chip select went low, starting a transaction.
+    fn select(&mut self) {}
+
+    // The chip select went high, ending the transaction.
+    fn deselect(&mut self) {}
+
+    // Exchange a byte while selected: take the byte shifted in on MOSI, and return the byte
+    // shifted out on MISO at the same time.
+    fn transfer(&mut self, mosi: u8) -> u8;
+}
+
+// Spi is BIFRÖST's SPI master (bifröst/spi.v): writing SPI_CS drives the chip selects, and
+// writing SPI_DATA shifts it out MSB first on MOSI while shifting in MISO in its place.
+//
+// SCK runs at 4 MHz from BIFRÖST's 8 MHz clock, so a byte takes 2 cycles of the 1 MHz system
+// clock. There's no busy flag: reading SPI_DATA mid-transfer gives the bits shifted in so far
+// above those still to go out, and writing it mid-transfer starts the next byte straight away.
+pub struct Spi {
+    cs: u8,       // chip select lines, active low
+    buf: u8,      // the byte written to SPI_DATA, or received once the transfer is done
+    received: u8, // the byte shifting in on MISO
+    ticks: u32,   // SPI clock ticks left in the transfer
+    devices: [Option<Box<dyn SpiDevice>>; CHIP_SELECTS],
+}
+
+impl Spi {
+    // SPI clock ticks per system clock cycle, and to transfer a byte: a bit every 2, one for
+    // each SCK edge.
+    const TICKS_PER_CYCLE: u32 = 8;
+    const TICKS_PER_BYTE: u32 = 16;
+
+    pub fn new() -> Self {
+        // initial values from spi.v
+        Self {
+            cs: 0b11111111,
+            buf: 0x00,
+            received: 0x00,
+            ticks: 0,
+            devices: Default::default(),
+        }
+    }
+
+    // Attach device to chip select cs (0-7), in place of any already there.
+    pub fn attach<D: SpiDevice>(&mut self, cs: usize, device: D) {
+        self.devices[cs] = Some(Box::new(device));
+    }
+
+    // The device attached to chip select cs, if it's a D.
+    pub fn device<D: SpiDevice>(&self, cs: usize) -> Option<&D> {
+        let device = self.devices[cs].as_deref()?;
+        (device as &dyn Any).downcast_ref()
+    }
+
+    pub fn device_mut<D: SpiDevice>(&mut self, cs: usize) -> Option<&mut D> {
+        let device = self.devices[cs].as_deref_mut()?;
+        (device as &mut dyn Any).downcast_mut()
+    }
+
+    // The chip select lines, active low, as written to SPI_CS.
+    pub fn cs(&self) -> u8 {
+        self.cs
+    }
+
+    // Drive the chip select lines, calling select or deselect on the devices whose line changed.
+    pub fn set_cs(&mut self, cs: u8) {
+        let changed = self.cs ^ cs;
+        self.cs = cs;
+        for (i, slot) in self.devices.iter_mut().enumerate() {
+            let Some(device) = slot.as_mut().filter(|_| changed & 1 << i != 0) else {
+                continue;
+            };
+            if cs & 1 << i == 0 {
+                device.select();
+            } else {
+                device.deselect();
+            }
+        }
+    }
+
+    // SPI_DATA as read: the received byte, part way in while a transfer is under way.
+    pub fn data(&self) -> u8 {
+        let pending = self.ticks.div_ceil(2); // bits still to shift in
+        let mask = ((1u16 << pending) - 1) as u8;
+        self.received & !mask | self.buf & mask
+    }
+
+    // Start a transfer of data, exchanging it with every selected device. MISO floats high
+    // with none selected, and is driven low by any that drives it low with several.
+    pub fn write_data(&mut self, data: u8) {
+        let cs = self.cs;
+        self.received = self
+            .devices
+            .iter_mut()
+            .enumerate()
+            .filter(|(i, _)| cs & 1 << i == 0)
+            .filter_map(|(_, slot)| slot.as_mut())
+            .fold(0xFF, |miso, device| miso & device.transfer(data));
+        self.buf = data;
+        self.ticks = Self::TICKS_PER_BYTE;
+    }
+
+    // Whether a transfer is still under way.
+    pub fn busy(&self) -> bool {
+        self.ticks > 0
+    }
+
+    pub fn step(&mut self, cycles: u8) {
+        if self.ticks == 0 {
+            return;
+        }
+        self.ticks = self
+            .ticks
+            .saturating_sub(cycles as u32 * Self::TICKS_PER_CYCLE);
+        if self.ticks == 0 {
+            self.buf = self.received;
+        }
+    }
+}
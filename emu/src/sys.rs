@@ -1,37 +1,361 @@
-use std::fs;
+use std::error::{self, Error};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
 
-use crate::bus::Bus;
-use crate::cpu::Cpu;
+use crate::bus::{Bus, ProtectedWrite};
+use crate::cpu::{Cpu, CpuError, RunState};
+use crate::dbginfo;
+use crate::isa::Variant;
+use crate::loader::{self, Image, LoadError};
+use crate::memconf;
 use crate::mon::Monitor;
+use crate::sid::{self, Sid};
+use crate::sidlog;
+use crate::wav::{self, WavWriter};
+
+// Where the OS is built, by ld65 with os/memory.conf.
+const OS_DIR: &str = "../os";
+
+// The most clock cycles a step runs while the 65C02 is paused by WAI, and so the longest it
+// goes without polling the host for input.
+const WAIT_CYCLES: u64 = 10_000;
+
+// How often to write the SID's rendered samples to the WAV file, and its register writes to
+// the log, while recording them.
+const FLUSH_CYCLES: u64 = 100_000;
 
 pub struct Sys {
     pub bus: Bus,
     cpu: Cpu,
     monitor: Monitor,
+    os: Image, // loaded on reset
+
+    cycles: u64, // clock cycles elapsed since reset
+
+    resb: bool,                // RESB held low, keeping the CPU in reset
+    pin_events: Vec<PinEvent>, // pin changes yet to happen, in cycle order
+
+    break_on_protected_write: bool,
+
+    wav: Option<WavWriter<BufWriter<File>>>, // recording the SID's output
+    sid_log: Option<sidlog::Writer<BufWriter<File>>>, // recording writes to the SID
+}
+
+// A 65C02 input pin driven from outside the CPU, e.g. by BIFRÖST or the reset supervisor.
+// Every one is asserted by pulling it low: RESB holds the CPU in reset until released, IRQB and
+// NMIB interrupt, RDY stalls the CPU, and SOB sets the V flag as it's asserted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pin {
+    Resb,
+    Irqb,
+    Nmib,
+    Rdy,
+    Sob,
+}
+
+// A pin to assert or release once the clock reaches cycle.
+#[derive(Copy, Clone, Debug)]
+struct PinEvent {
+    cycle: u64,
+    pin: Pin,
+    asserted: bool,
+}
+
+// The OS as built by ld65: its image, loaded on reset, its labels for the monitor, and the
+// memory areas of its linker config, protected as that says.
+#[derive(Default)]
+pub struct Os {
+    pub image: Image,
+    pub info: dbginfo::Info,
+    pub areas: Vec<memconf::Area>,
+}
+
+impl Os {
+    // Load the OS built in dir, from its memory.conf, debug.out and the files they name.
+    pub fn load(dir: &str) -> Result<Self, Box<dyn Error>> {
+        let conf = format!("{dir}/memory.conf");
+        let areas = memconf::load(&conf).map_err(|e| format!("{conf}: {e}"))?;
+        let debug = format!("{dir}/debug.out");
+        let info = dbginfo::load(&debug).map_err(|e| format!("{debug}: {e}"))?;
+        let image = loader::from_ld65_debug(&info, Path::new(dir))
+            .map_err(|e| format!("{dir}: loading the OS: {e}"))?;
+        Ok(Self { image, info, areas })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SysError {
+    Cpu(CpuError),
+    // the instruction at pc wrote to protected RAM, with breaking on that enabled
+    ProtectedWrite { pc: u16, write: ProtectedWrite },
+    // writing the recorded WAV file failed
+    Wav(String),
+    // writing the SID write log failed
+    SidLog(String),
 }
 
 impl Sys {
-    pub fn new() -> Self {
+    // A system running the OS built in ../os.
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        Self::with_variant(Variant::default())
+    }
+
+    pub fn with_variant(variant: Variant) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::with_os(variant, Os::load(OS_DIR)?))
+    }
+
+    // A system running os, e.g. Os::default() for none, leaving RAM to whatever's loaded.
+    pub fn with_os(variant: Variant, os: Os) -> Self {
+        let mut bus = Bus::new();
+        for area in &os.areas {
+            if let Some(protection) = area.protection {
+                bus.protect(area.range(), protection);
+            }
+        }
         Self {
-            bus: Bus::new(),
-            cpu: Cpu::new(),
-            monitor: Monitor::new(),
+            bus,
+            cpu: Cpu::with_variant(variant),
+            monitor: Monitor::with_variant(variant, os.info),
+            os: os.image,
+            cycles: 0,
+            resb: false,
+            pin_events: Vec::new(),
+            break_on_protected_write: false,
+            wav: None,
+            sid_log: None,
         }
     }
 
     pub fn reset(&mut self) {
         self.bus.reset();
-        self.bus.load(0xF000, fs::read("../os/os.rom").unwrap());
+        self.os.load(&mut self.bus).expect("loading the OS");
         self.monitor.reset(&mut self.bus);
-        self.cpu.reset(&mut self.bus)
+        self.cpu.reset(&mut self.bus);
+        self.cycles = 0;
+        self.bus.set_cycles(0);
     }
 
-    pub fn step(&mut self) {
-        self.bus.step();
-        if self.bus.is_interrupt() {
-            self.cpu.interrupt(&mut self.bus);
+    // Load a program, e.g. after reset, and start the CPU at its start address if it has one.
+    pub fn load(&mut self, image: &Image) -> Result<(), LoadError> {
+        image.load(&mut self.bus)?;
+        if let Some(start) = image.start {
+            self.cpu.pc = start as u16;
         }
-        self.monitor.step(&mut self.bus, &self.cpu);
-        self.cpu.step(&mut self.bus);
+        Ok(())
+    }
+
+    // Make every bus access the CPU makes on hardware, including dummy reads and writes, so
+    // that devices with side-effecting registers see the same accesses.
+    pub fn set_cycle_accurate(&mut self, enabled: bool) {
+        self.cpu.cycle_accurate = enabled;
+    }
+
+    // Stop with SysError::ProtectedWrite when the CPU writes to read-only or write-trapping
+    // RAM, rather than just reporting it.
+    pub fn set_break_on_protected_write(&mut self, enabled: bool) {
+        self.break_on_protected_write = enabled;
+    }
+
+    // Assert (pull low) or release an input pin of the CPU. Releasing RESB after asserting it
+    // resets the CPU and devices, like the reset button, but leaves RAM and cycles() alone.
+    pub fn set_pin(&mut self, pin: Pin, asserted: bool) {
+        self.monitor.pin(self.cycles, pin, asserted);
+        match pin {
+            Pin::Resb => {
+                if asserted {
+                    self.bus.reset();
+                } else if self.resb {
+                    self.monitor.reset(&mut self.bus);
+                    self.cpu.reset(&mut self.bus);
+                }
+                self.resb = asserted;
+            }
+            Pin::Irqb => self.bus.set_irq(asserted),
+            Pin::Nmib => self.bus.set_nmi(asserted),
+            Pin::Rdy => self.cpu.set_rdy(!asserted),
+            Pin::Sob => self.cpu.set_so(asserted),
+        }
+    }
+
+    // Assert or release an input pin of the CPU once cycles() reaches cycle. Pins change between
+    // instructions, so this happens before the first step starting at or after cycle.
+    pub fn set_pin_at(&mut self, cycle: u64, pin: Pin, asserted: bool) {
+        let i = self.pin_events.partition_point(|e| e.cycle <= cycle);
+        self.pin_events.insert(
+            i,
+            PinEvent {
+                cycle,
+                pin,
+                asserted,
+            },
+        );
+    }
+
+    // Record the SID's output into a WAV file at path, rendered at sample_rate.
+    pub fn record_wav(&mut self, path: &str, sample_rate: u32) -> io::Result<()> {
+        self.wav = Some(wav::create(path, sample_rate)?);
+        self.sid().set_sample_rate(Some(sample_rate));
+        Ok(())
+    }
+
+    // Write the samples rendered since the last flush to the WAV file, if recording. Step
+    // flushes regularly; flush once more when done.
+    pub fn flush_wav(&mut self) -> io::Result<()> {
+        let samples = self.sid().take_samples();
+        match &mut self.wav {
+            Some(wav) => wav.write(&samples),
+            None => Ok(()),
+        }
+    }
+
+    // Log every write to the SID's registers, with its cycle, as a dump at path that
+    // sidlog::decode reads back.
+    pub fn record_sid_log(&mut self, path: &str) -> io::Result<()> {
+        self.sid_log = Some(sidlog::create(path)?);
+        self.bus.set_sid_capture(true);
+        Ok(())
+    }
+
+    // Write the SID writes made since the last flush to the log, if recording. Step flushes
+    // regularly; flush once more when done.
+    pub fn flush_sid_log(&mut self) -> io::Result<()> {
+        let writes = self.bus.take_sid_writes();
+        match &mut self.sid_log {
+            Some(log) => log.write(&writes),
+            None => Ok(()),
+        }
+    }
+
+    // The SID on the bus, e.g. to choose its model.
+    pub fn sid(&mut self) -> &mut Sid {
+        self.bus.device_mut().expect("SID on the bus")
+    }
+
+    // Clock cycles elapsed since reset.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // Describe the state of the system when the CPU returned err, for diagnosing a crash.
+    pub fn crash_report(&mut self, err: &CpuError) -> String {
+        self.monitor
+            .crash_report(&mut self.bus, &self.cpu, self.cycles, err)
+    }
+
+    // Step until STP halts the CPU.
+    pub fn run(&mut self) -> Result<(), SysError> {
+        while self.step()? != RunState::Stopped {}
+        Ok(())
+    }
+
+    // Step the bus and execute one instruction, returning the CPU's resulting RunState.
+    // RunState::Stopped means STP has halted the CPU, and only a reset will resume it.
+    pub fn step(&mut self) -> Result<RunState, SysError> {
+        let due = self.pin_events.partition_point(|e| e.cycle <= self.cycles);
+        for e in self.pin_events.drain(..due).collect::<Vec<_>>() {
+            self.set_pin(e.pin, e.asserted);
+        }
+        if self.resb {
+            self.cycles += 1;
+            self.bus.step(1);
+            return Ok(self.cpu.state);
+        }
+
+        let start = self.cycles;
+        let mut cycles = 0;
+        let pc = self.cpu.pc;
+        self.cpu.set_nmi(self.bus.is_nmi());
+        cycles += self.cpu.nmi(&mut self.bus);
+        if cycles == 0 && self.bus.is_interrupt() {
+            cycles += self.cpu.interrupt(&mut self.bus);
+        }
+        match self.cpu.state {
+            RunState::Running => {
+                self.monitor
+                    .step(&mut self.bus, &self.cpu, self.cycles + cycles as u64);
+                cycles += self.cpu.step(&mut self.bus)?.cycles;
+            }
+            RunState::Waiting => self.wait(),
+            RunState::Stopped => cycles += self.cpu.step(&mut self.bus)?.cycles,
+        }
+        // a wait has stepped the devices itself
+        if cycles > 0 {
+            self.cycles += cycles as u64;
+            self.bus.step(cycles);
+        }
+
+        if self.cycles / FLUSH_CYCLES != start / FLUSH_CYCLES {
+            if self.wav.is_some() {
+                self.flush_wav().map_err(|e| SysError::Wav(e.to_string()))?;
+            }
+            if self.sid_log.is_some() {
+                self.flush_sid_log()
+                    .map_err(|e| SysError::SidLog(e.to_string()))?;
+            }
+        }
+
+        for write in self.bus.take_protected_writes() {
+            self.monitor.protected_write(pc, &write);
+            if self.break_on_protected_write {
+                return Err(SysError::ProtectedWrite { pc, write });
+            }
+        }
+        Ok(self.cpu.state)
+    }
+
+    // Run the clock while WAI pauses the CPU, up to when a device interrupts or a pin is due to
+    // change, WAIT_CYCLES at most, stepping the devices once. Then yield the host CPU for as long
+    // as those cycles take on hardware. At least one cycle passes, even with NMIB held low.
+    fn wait(&mut self) {
+        let next_pin = self.pin_events.first().map_or(u64::MAX, |e| e.cycle);
+        let next_interrupt = self.bus.next_interrupt().map_or(u64::MAX, u64::from);
+        let cycles = (next_pin.saturating_sub(self.cycles))
+            .min(next_interrupt)
+            .clamp(1, WAIT_CYCLES);
+        self.cycles += cycles;
+        self.bus.step_cycles(cycles as u32);
+        thread::sleep(Duration::from_micros(
+            cycles * 1_000_000 / sid::CLOCK_HZ as u64,
+        ));
+    }
+}
+
+impl From<CpuError> for SysError {
+    fn from(e: CpuError) -> Self {
+        SysError::Cpu(e)
+    }
+}
+
+impl fmt::Display for SysError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SysError::Cpu(e) => write!(f, "{e}"),
+            SysError::ProtectedWrite { pc, write } => write!(
+                f,
+                "{:?} write of ${:02X} to ${:04X} by instruction at ${pc:04X}",
+                write.protection, write.data, write.addr
+            ),
+            SysError::Wav(e) => write!(f, "writing WAV: {e}"),
+            SysError::SidLog(e) => write!(f, "writing SID log: {e}"),
+        }
+    }
+}
+
+impl error::Error for SysError {}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Pin::Resb => "RESB",
+            Pin::Irqb => "IRQB",
+            Pin::Nmib => "NMIB",
+            Pin::Rdy => "RDY",
+            Pin::Sob => "SOB",
+        };
+        write!(f, "{name}")
     }
 }
@@ -0,0 +1,217 @@
+// Trap detection runner, for self-checking test programs like Klaus Dormann's 6502/65C02
+// functional, extended opcode and decimal tests. Those run until they either pass or fail,
+// then "trap" by jumping or branching to themselves forever; where they trap says which.
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+
+use crate::bus::Bus;
+use crate::cpu::{Cpu, CpuError, RunState};
+use crate::isa::Variant;
+
+// Cycles to run for before giving up on a program that never traps; the functional test
+// takes about 100 million.
+const DEFAULT_MAX_CYCLES: u64 = 1_000_000_000;
+
+pub struct Runner {
+    pub bus: Bus,
+    pub cpu: Cpu,
+
+    success: Option<u16>,
+    listing: Option<Listing>,
+    max_cycles: u64,
+}
+
+// Where and how a program trapped.
+#[derive(Debug)]
+pub struct Report {
+    pub pc: u16,
+    pub cycles: u64,
+    pub instructions: u64,
+    pub passed: bool,                 // whether the trap was at the success address
+    pub label: Option<(String, u16)>, // nearest listing label and the offset from it
+    pub source: Option<String>,       // listing source line at the trap
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrapError {
+    ImageTooLarge { load: u16, len: usize }, // the image doesn't fit between load and $FFFF
+    Cpu(CpuError),
+    Timeout { pc: u16, cycles: u64 }, // no trap within the cycle limit
+}
+
+impl Runner {
+    // A runner with a raw binary image loaded at load into RAM-only memory, starting at start.
+    pub fn new(variant: Variant, image: &[u8], load: u16, start: u16) -> Result<Self, TrapError> {
+        let mut bus = Bus::new_ram_only();
+        bus.load(load, image.to_vec())
+            .map_err(|_| TrapError::ImageTooLarge {
+                load,
+                len: image.len(),
+            })?;
+        let mut cpu = Cpu::with_variant(variant);
+        cpu.pc = start;
+        cpu.s = 0xFF;
+        Ok(Self {
+            bus,
+            cpu,
+            success: None,
+            listing: None,
+            max_cycles: DEFAULT_MAX_CYCLES,
+        })
+    }
+
+    // The address the program traps at when it passes.
+    pub fn success(&mut self, addr: u16) -> &mut Self {
+        self.success = Some(addr);
+        self
+    }
+
+    // A listing of the program, to describe the trap address.
+    pub fn listing(&mut self, listing: Listing) -> &mut Self {
+        self.listing = Some(listing);
+        self
+    }
+
+    pub fn max_cycles(&mut self, cycles: u64) -> &mut Self {
+        self.max_cycles = cycles;
+        self
+    }
+
+    // Run until the program traps, by an instruction leaving PC unchanged or by STP/WAI
+    // pausing the CPU with nothing to wake it.
+    pub fn run(&mut self) -> Result<Report, TrapError> {
+        let mut cycles: u64 = 0;
+        let mut instructions: u64 = 0;
+        loop {
+            let info = self.cpu.step(&mut self.bus).map_err(TrapError::Cpu)?;
+            cycles += info.cycles as u64;
+            instructions += 1;
+            if info.pc == self.cpu.pc || self.cpu.state != RunState::Running {
+                return Ok(self.report(info.pc, cycles, instructions));
+            }
+            if cycles >= self.max_cycles {
+                return Err(TrapError::Timeout {
+                    pc: self.cpu.pc,
+                    cycles,
+                });
+            }
+        }
+    }
+
+    fn report(&self, pc: u16, cycles: u64, instructions: u64) -> Report {
+        Report {
+            pc,
+            cycles,
+            instructions,
+            passed: self.success == Some(pc),
+            label: self
+                .listing
+                .as_ref()
+                .and_then(|l| l.nearest_label(pc))
+                .map(|(label, offset)| (label.to_string(), offset)),
+            source: self
+                .listing
+                .as_ref()
+                .and_then(|l| l.source(pc))
+                .map(|s| s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let result = if self.passed { "PASS" } else { "FAIL" };
+        write!(f, "{result}: trap at ${:04X}", self.pc)?;
+        match &self.label {
+            Some((label, 0)) => write!(f, " ({label})")?,
+            Some((label, offset)) => write!(f, " ({label}+{offset})")?,
+            None => {}
+        }
+        write!(
+            f,
+            " after {} instructions, {} cycles",
+            self.instructions, self.cycles
+        )?;
+        if let Some(source) = &self.source {
+            write!(f, "\n  {source}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TrapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrapError::ImageTooLarge { load, len } => {
+                write!(f, "{len} byte image doesn't fit at ${load:04X}")
+            }
+            TrapError::Cpu(e) => write!(f, "{e}"),
+            TrapError::Timeout { pc, cycles } => {
+                write!(f, "no trap after {cycles} cycles; PC at ${pc:04X}")
+            }
+        }
+    }
+}
+
+impl error::Error for TrapError {}
+
+// Labels and source lines of an assembler listing, in the format of the as65 listings that
+// come with the Klaus Dormann tests:
+//
+//     0400 : d8               start   cld
+//     0594 : d0fe             >       bne *           ;failed not equal (non zero)
+//
+// i.e. address, colon, code bytes, then source from column 24, where labels start without
+// indentation and a '>' marks a line expanded from a macro.
+#[derive(Debug, Default)]
+pub struct Listing {
+    labels: BTreeMap<u16, String>,
+    sources: BTreeMap<u16, String>,
+}
+
+const LISTING_SOURCE_COLUMN: usize = 24;
+
+impl Listing {
+    pub fn parse(text: &str) -> Self {
+        let mut listing = Listing::default();
+        for line in text.lines() {
+            let Some((addr, rest)) = line.split_once(" : ") else {
+                continue;
+            };
+            let Ok(addr) = u16::from_str_radix(addr.trim(), 16) else {
+                continue;
+            };
+            let has_code = rest.starts_with(|c: char| c.is_ascii_hexdigit());
+            let source = line.get(LISTING_SOURCE_COLUMN..).unwrap_or("");
+            let label = source
+                .split(|c: char| c.is_whitespace() || c == ':' || c == ';')
+                .next()
+                .filter(|token| token.starts_with(|c: char| c.is_alphabetic() || c == '_'));
+            if let Some(label) = label {
+                listing.labels.entry(addr).or_insert(label.to_string());
+            }
+            if has_code {
+                listing
+                    .sources
+                    .entry(addr)
+                    .or_insert(source.trim_start_matches('>').trim().to_string());
+            }
+        }
+        listing
+    }
+
+    // The closest label at or before addr, and addr's offset from it.
+    pub fn nearest_label(&self, addr: u16) -> Option<(&str, u16)> {
+        self.labels
+            .range(..=addr)
+            .next_back()
+            .map(|(a, label)| (label.as_str(), addr - a))
+    }
+
+    // The source line of the instruction at addr.
+    pub fn source(&self, addr: u16) -> Option<&str> {
+        self.sources.get(&addr).map(|s| s.as_str())
+    }
+}
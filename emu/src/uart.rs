@@ -1,5 +1,7 @@
 use std::io::ErrorKind;
-use std::{collections::VecDeque, io::Write, net::UdpSocket};
+use std::{collections::VecDeque, net::UdpSocket};
+
+use crate::device::Device;
 
 pub const SIZE: usize = 16;
 
@@ -88,72 +90,6 @@ impl Uart {
         }
     }
 
-    pub fn reset(&mut self) {
-        self.registers[0x05] |= 0b00000001; // TxRDY
-        self.mrai = 1;
-        self.mrbi = 1;
-        self.enable_tx = false;
-        self.enable_rx = false;
-        self.enable_tx_irq = false;
-    }
-
-    pub fn step(&mut self) {
-        if self.recv_a.is_empty() {
-            let mut buf = [0; 1024];
-            self.socket_a.set_nonblocking(true).unwrap();
-            match self.socket_a.recv(&mut buf) {
-                Ok(amt) => {
-                    self.recv_a.write(&buf[..amt]).unwrap();
-                }
-                Err(e) => match e.kind() {
-                    ErrorKind::WouldBlock => {}
-                    _ => {
-                        panic!("{}", e);
-                    }
-                },
-            }
-        }
-    }
-
-    pub fn read(&mut self, reg: u8) -> u8 {
-        match reg {
-            Self::REG_SRA => self.read_sra(),
-            Self::REG_RXFIFOA => self.read_fifo_a(),
-            _ => self.registers[reg as usize],
-        }
-    }
-
-    pub fn write(&mut self, reg: u8, data: u8) {
-        match reg {
-            Self::REG_MRA => self.write_mra(data),
-            Self::REG_CRA => self.write_cra(data),
-            Self::REG_TXFIFOA => self.tx_a(data),
-            _ => {
-                // eprintln!(
-                //     "UART: {}/{reg:#X} <- {data:#04X}/{data}/{data:#010b}",
-                //     Self::REG_WRITE[reg as usize]
-                // )
-            }
-        }
-        self.registers[reg as usize] = data;
-    }
-
-    pub fn is_interrupt(&self) -> bool {
-        let imr = self.registers[Self::REG_IMR as usize];
-
-        // for now we're assuming Tx buffer is always ready / never full.
-        (self.enable_tx && imr & Self::IRQ_MASK_TXRDYA != 0)
-            || (self.enable_rx && imr & Self::IRQ_MASK_RXRDYA != 0 && !self.recv_a.is_empty())
-    }
-
-    pub fn name_for_read(&mut self, reg: u8) -> String {
-        format!("UART:{}", Self::REG_READ[reg as usize])
-    }
-
-    pub fn name_for_write(&self, reg: u8) -> String {
-        format!("UART:{}", Self::REG_WRITE[reg as usize])
-    }
-
     fn write_cra(&mut self, data: u8) {
         if data & 1 << 3 != 0 {
             eprintln!("UART CRA TODO: Disable channel A transmitter. This command terminates transmitter operation and reset the TxDRY and TxEMT status bits. However, if a character is being transmitted or if a character is in the Tx FIFO when the transmitter is disabled, the transmission of the character(s) is completed before assuming the inactive state.");
@@ -265,4 +201,93 @@ impl Uart {
     fn read_fifo_a(&mut self) -> u8 {
         self.recv_a.pop_front().unwrap_or(0x00)
     }
+
+    // Take whatever the host has sent, once the receive FIFO is empty.
+    fn poll(&mut self) {
+        if self.recv_a.is_empty() {
+            let mut buf = [0; 1024];
+            self.socket_a.set_nonblocking(true).unwrap();
+            match self.socket_a.recv(&mut buf) {
+                Ok(amt) => {
+                    self.recv_a.extend(&buf[..amt]);
+                }
+                Err(e) => match e.kind() {
+                    ErrorKind::WouldBlock => {}
+                    _ => {
+                        panic!("{}", e);
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl Device for Uart {
+    fn name(&self) -> &str {
+        "UART"
+    }
+
+    fn reset(&mut self) {
+        self.registers[0x05] |= 0b00000001; // TxRDY
+        self.mrai = 1;
+        self.mrbi = 1;
+        self.enable_tx = false;
+        self.enable_rx = false;
+        self.enable_tx_irq = false;
+    }
+
+    fn step(&mut self, _cycles: u8) {
+        self.poll();
+    }
+
+    // Receiving depends on the host, not the clock, so poll once however many cycles pass.
+    fn step_cycles(&mut self, _cycles: u32) {
+        self.poll();
+    }
+
+    fn read(&mut self, reg: u16) -> u8 {
+        match reg as u8 {
+            Self::REG_RXFIFOA => self.read_fifo_a(),
+            _ => self.peek(reg),
+        }
+    }
+
+    fn write(&mut self, reg: u16, data: u8) {
+        match reg as u8 {
+            Self::REG_MRA => self.write_mra(data),
+            Self::REG_CRA => self.write_cra(data),
+            Self::REG_TXFIFOA => self.tx_a(data),
+            _ => {
+                // eprintln!(
+                //     "UART: {}/{reg:#X} <- {data:#04X}/{data}/{data:#010b}",
+                //     Self::REG_WRITE[reg as usize]
+                // )
+            }
+        }
+        self.registers[reg as usize] = data;
+    }
+
+    fn peek(&self, reg: u16) -> u8 {
+        match reg as u8 {
+            Self::REG_SRA => self.read_sra(),
+            Self::REG_RXFIFOA => self.recv_a.front().copied().unwrap_or(0x00),
+            _ => self.registers[reg as usize],
+        }
+    }
+
+    fn irq(&self) -> bool {
+        let imr = self.registers[Self::REG_IMR as usize];
+
+        // for now we're assuming Tx buffer is always ready / never full.
+        (self.enable_tx && imr & Self::IRQ_MASK_TXRDYA != 0)
+            || (self.enable_rx && imr & Self::IRQ_MASK_RXRDYA != 0 && !self.recv_a.is_empty())
+    }
+
+    fn name_for_read(&self, reg: u16) -> Option<&str> {
+        Some(Self::REG_READ[reg as usize]).filter(|name| !name.is_empty())
+    }
+
+    fn name_for_write(&self, reg: u16) -> Option<&str> {
+        Some(Self::REG_WRITE[reg as usize]).filter(|name| !name.is_empty())
+    }
 }
@@ -0,0 +1,560 @@
+use crate::device::Device;
+
+pub const SIZE: usize = 16;
+
+// Via is the WDC W65C22 Versatile Interface Adapter: two 8-bit ports A and B, each with a pair
+// of handshake lines (CA1/CA2, CB1/CB2), two 16-bit timers, an 8-bit shift register, and
+// interrupts flagged in IFR and enabled in IER.
+//
+// Outside the bus, the host drives the input pins with set_port_a, set_ca1 etc. and reads back
+// what the VIA drives with port_a, ca2 etc.
+pub struct Via {
+    name: &'static str,
+
+    ora: u8,
+    orb: u8,
+    ddra: u8,
+    ddrb: u8,
+    pa: u8,        // levels driven onto port A from outside
+    pb: u8,        // levels driven onto port B from outside
+    ira_latch: u8, // port A as latched by the last active CA1 edge
+    irb_latch: u8, // port B as latched by the last active CB1 edge
+
+    t1_counter: u16,
+    t1_latch: u16,
+    t1_armed: bool,  // whether the next time-out interrupts
+    t1_reload: bool, // free-running: the counter reloads from the latch on the next cycle
+    pb7: bool,       // level T1 drives onto PB7, when ACR enables it
+
+    t2_counter: u16,
+    t2_latch_lo: u8,
+    t2_armed: bool,
+
+    sr: u8,
+    sr_bits: u8,    // bits left to shift; 0 when idle
+    sr_timer: u16,  // cycles until the next edge of the internal shift clock
+    sr_clock: bool, // level of the internal shift clock, driven onto CB1
+    sr_out: bool,   // last bit shifted out, driven onto CB2
+
+    acr: u8,
+    pcr: u8,
+    ifr: u8,
+    ier: u8,
+
+    // levels of the handshake lines driven from outside
+    ca1: bool,
+    ca2: bool,
+    cb1: bool,
+    cb2: bool,
+
+    // levels of CA2/CB2 in handshake and pulse output modes
+    ca2_out: bool,
+    cb2_out: bool,
+    ca2_pulse: bool, // CA2 returns high on the next cycle
+    cb2_pulse: bool, // CB2 returns high on the next cycle
+}
+
+impl Via {
+    const REG_ORB: u16 = 0x0; // IRB on read
+    const REG_ORA: u16 = 0x1; // IRA on read
+    const REG_DDRB: u16 = 0x2;
+    const REG_DDRA: u16 = 0x3;
+    const REG_T1CL: u16 = 0x4;
+    const REG_T1CH: u16 = 0x5;
+    const REG_T1LL: u16 = 0x6;
+    const REG_T1LH: u16 = 0x7;
+    const REG_T2CL: u16 = 0x8;
+    const REG_T2CH: u16 = 0x9;
+    const REG_SR: u16 = 0xA;
+    const REG_ACR: u16 = 0xB;
+    const REG_PCR: u16 = 0xC;
+    const REG_IFR: u16 = 0xD;
+    const REG_IER: u16 = 0xE;
+    const REG_ORAH: u16 = 0xF; // ORA/IRA without handshake
+
+    const REG_READ: [&'static str; SIZE] = [
+        "IRB", "IRA", "DDRB", "DDRA", "T1CL", "T1CH", "T1LL", "T1LH", "T2CL", "T2CH", "SR", "ACR",
+        "PCR", "IFR", "IER", "IRAH",
+    ];
+
+    const REG_WRITE: [&'static str; SIZE] = [
+        "ORB", "ORA", "DDRB", "DDRA", "T1CL", "T1CH", "T1LL", "T1LH", "T2CL", "T2CH", "SR", "ACR",
+        "PCR", "IFR", "IER", "ORAH",
+    ];
+
+    // IFR and IER bits
+    pub const IRQ_CA2: u8 = 1 << 0;
+    pub const IRQ_CA1: u8 = 1 << 1;
+    pub const IRQ_SR: u8 = 1 << 2;
+    pub const IRQ_CB2: u8 = 1 << 3;
+    pub const IRQ_CB1: u8 = 1 << 4;
+    pub const IRQ_T2: u8 = 1 << 5;
+    pub const IRQ_T1: u8 = 1 << 6;
+
+    // ACR bits
+    const ACR_PA_LATCH: u8 = 1 << 0;
+    const ACR_PB_LATCH: u8 = 1 << 1;
+    const ACR_T2_PULSES: u8 = 1 << 5; // T2 counts PB6 pulses instead of cycles
+    const ACR_T1_FREE_RUN: u8 = 1 << 6;
+    const ACR_T1_PB7: u8 = 1 << 7;
+
+    pub fn new(name: &'static str) -> Self {
+        let mut via = Self {
+            name,
+            ora: 0x00,
+            orb: 0x00,
+            ddra: 0x00,
+            ddrb: 0x00,
+            pa: 0xFF, // inputs float high
+            pb: 0xFF,
+            ira_latch: 0x00,
+            irb_latch: 0x00,
+            t1_counter: 0xFFFF,
+            t1_latch: 0xFFFF,
+            t1_armed: false,
+            t1_reload: false,
+            pb7: true,
+            t2_counter: 0xFFFF,
+            t2_latch_lo: 0xFF,
+            t2_armed: false,
+            sr: 0x00,
+            sr_bits: 0,
+            sr_timer: 0,
+            sr_clock: true,
+            sr_out: true,
+            acr: 0x00,
+            pcr: 0x00,
+            ifr: 0x00,
+            ier: 0x00,
+            ca1: true,
+            ca2: true,
+            cb1: true,
+            cb2: true,
+            ca2_out: true,
+            cb2_out: true,
+            ca2_pulse: false,
+            cb2_pulse: false,
+        };
+        via.reset();
+        via
+    }
+
+    // Port A pin levels: outputs as driven by ORA, inputs as driven from outside.
+    pub fn port_a(&self) -> u8 {
+        (self.ora & self.ddra) | (self.pa & !self.ddra)
+    }
+
+    // Port B pin levels: outputs as driven by ORB (or T1 on PB7), inputs as driven from outside.
+    pub fn port_b(&self) -> u8 {
+        let mut out = self.orb;
+        if self.acr & Self::ACR_T1_PB7 != 0 {
+            out = (out & 0x7F) | (self.pb7 as u8) << 7;
+        }
+        let ddrb = self.ddrb | (self.acr & Self::ACR_T1_PB7);
+        (out & ddrb) | (self.pb & !ddrb)
+    }
+
+    // Drive the port A pins from outside; only pins set as inputs by DDRA are read back.
+    pub fn set_port_a(&mut self, data: u8) {
+        self.pa = data;
+    }
+
+    // Drive the port B pins from outside. T2 counts falling edges on PB6 in pulse counting mode.
+    pub fn set_port_b(&mut self, data: u8) {
+        let pb6_fell = self.pb & !data & 1 << 6 != 0;
+        self.pb = data;
+        if pb6_fell && self.acr & Self::ACR_T2_PULSES != 0 {
+            self.t2_counter = self.t2_counter.wrapping_sub(1);
+            if self.t2_counter == 0 && self.t2_armed {
+                self.ifr |= Self::IRQ_T2;
+                self.t2_armed = false;
+            }
+        }
+    }
+
+    pub fn ca1(&self) -> bool {
+        self.ca1
+    }
+
+    // Level of CA2: driven by the VIA in the PCR output modes, otherwise from outside.
+    pub fn ca2(&self) -> bool {
+        match self.pcr >> 1 & 0b111 {
+            0b100 | 0b101 => self.ca2_out,
+            0b110 => false,
+            0b111 => true,
+            _ => self.ca2,
+        }
+    }
+
+    // Level of CB1: the shift clock while the shift register is clocked internally, otherwise
+    // driven from outside.
+    pub fn cb1(&self) -> bool {
+        match self.sr_mode() {
+            0b001 | 0b010 | 0b100 | 0b101 | 0b110 => self.sr_clock,
+            _ => self.cb1,
+        }
+    }
+
+    // Level of CB2: the shift register's data while shifting out, the PCR output modes, or
+    // otherwise driven from outside.
+    pub fn cb2(&self) -> bool {
+        if self.sr_mode() & 0b100 != 0 {
+            return self.sr_out;
+        }
+        match self.pcr >> 5 & 0b111 {
+            0b100 | 0b101 => self.cb2_out,
+            0b110 => false,
+            0b111 => true,
+            _ => self.cb2,
+        }
+    }
+
+    // Drive CA1 from outside. Its active edge (PCR bit 0) sets IFR CA1, latches port A if ACR
+    // enables it, and completes a CA2 handshake.
+    pub fn set_ca1(&mut self, level: bool) {
+        if level != self.ca1 && level == (self.pcr & 1 << 0 != 0) {
+            self.ifr |= Self::IRQ_CA1;
+            self.ira_latch = self.port_a();
+            if self.pcr >> 1 & 0b111 == 0b100 {
+                self.ca2_out = true;
+            }
+        }
+        self.ca1 = level;
+    }
+
+    // Drive CA2 from outside. In the PCR input modes, its active edge sets IFR CA2.
+    pub fn set_ca2(&mut self, level: bool) {
+        let control = self.pcr >> 1 & 0b111;
+        if level != self.ca2 && control & 0b100 == 0 && level == (control & 0b010 != 0) {
+            self.ifr |= Self::IRQ_CA2;
+        }
+        self.ca2 = level;
+    }
+
+    // Drive CB1 from outside. Its active edge (PCR bit 4) sets IFR CB1, latches port B if ACR
+    // enables it, and completes a CB2 handshake. It also clocks the shift register in the
+    // external clock modes.
+    pub fn set_cb1(&mut self, level: bool) {
+        if level != self.cb1 {
+            if level == (self.pcr & 1 << 4 != 0) {
+                self.ifr |= Self::IRQ_CB1;
+                self.irb_latch = self.pb;
+                if self.pcr >> 5 & 0b111 == 0b100 {
+                    self.cb2_out = true;
+                }
+            }
+            if matches!(self.sr_mode(), 0b011 | 0b111) && self.sr_bits > 0 {
+                self.shift(level);
+            }
+        }
+        self.cb1 = level;
+    }
+
+    // Drive CB2 from outside. In the PCR input modes, its active edge sets IFR CB2. It's also
+    // the data shifted in by the shift register.
+    pub fn set_cb2(&mut self, level: bool) {
+        let control = self.pcr >> 5 & 0b111;
+        if level != self.cb2 && control & 0b100 == 0 && level == (control & 0b010 != 0) {
+            self.ifr |= Self::IRQ_CB2;
+        }
+        self.cb2 = level;
+    }
+
+    // Shift register mode, ACR bits 4:2.
+    fn sr_mode(&self) -> u8 {
+        self.acr >> 2 & 0b111
+    }
+
+    // Whether the shift register is clocked internally, by Φ2 or T2, rather than by CB1.
+    fn sr_internal_clock(&self) -> bool {
+        matches!(self.sr_mode(), 0b001 | 0b010 | 0b100 | 0b101 | 0b110)
+    }
+
+    // Cycles between edges of the internal shift clock: every cycle under Φ2, or every N+2
+    // cycles under T2, where N is the T2 low latch.
+    fn sr_period(&self) -> u16 {
+        match self.sr_mode() {
+            0b010 | 0b110 => 1,
+            _ => self.t2_latch_lo as u16 + 2,
+        }
+    }
+
+    // Start shifting 8 bits, on a read or write of SR.
+    fn start_shift(&mut self) {
+        self.ifr &= !Self::IRQ_SR;
+        if self.sr_mode() != 0b000 {
+            self.sr_bits = 8;
+            self.sr_timer = self.sr_period();
+            self.sr_clock = true;
+        }
+    }
+
+    // An edge of the shift clock. Bits are shifted out MSB first onto CB2 on the falling edge,
+    // and shifted in from CB2 on the rising edge, which also counts the bit.
+    fn shift(&mut self, rising: bool) {
+        let out = self.sr_mode() & 0b100 != 0;
+        if !rising {
+            if out {
+                self.sr_out = self.sr & 0x80 != 0;
+                self.sr = self.sr.rotate_left(1);
+            }
+            return;
+        }
+        if !out {
+            self.sr = self.sr << 1 | self.cb2 as u8;
+        }
+        self.sr_bits -= 1;
+        if self.sr_bits == 0 {
+            if self.sr_mode() == 0b100 {
+                self.sr_bits = 8; // free-running output recirculates without interrupting
+            } else {
+                self.ifr |= Self::IRQ_SR;
+            }
+        }
+    }
+
+    // Handshake on a read or write of ORA/IRA.
+    fn port_a_access(&mut self) {
+        let control = self.pcr >> 1 & 0b111;
+        self.ifr &= !Self::IRQ_CA1;
+        if control & 0b101 != 0b001 {
+            self.ifr &= !Self::IRQ_CA2; // except in the independent interrupt input modes
+        }
+        match control {
+            0b100 => self.ca2_out = false, // until the active CA1 edge
+            0b101 => {
+                self.ca2_out = false;
+                self.ca2_pulse = true;
+            }
+            _ => {}
+        }
+    }
+
+    // Interrupt flags cleared by a read or write of ORB/IRB.
+    fn port_b_access(&mut self) {
+        self.ifr &= !Self::IRQ_CB1;
+        if self.pcr >> 5 & 0b101 != 0b001 {
+            self.ifr &= !Self::IRQ_CB2;
+        }
+    }
+
+    // Handshake on a write of ORB.
+    fn port_b_write(&mut self) {
+        match self.pcr >> 5 & 0b111 {
+            0b100 => self.cb2_out = false,
+            0b101 => {
+                self.cb2_out = false;
+                self.cb2_pulse = true;
+            }
+            _ => {}
+        }
+    }
+
+    // Advance by a single clock cycle.
+    fn tick(&mut self) {
+        if self.ca2_pulse {
+            self.ca2_out = true;
+            self.ca2_pulse = false;
+        }
+        if self.cb2_pulse {
+            self.cb2_out = true;
+            self.cb2_pulse = false;
+        }
+
+        // T1 time-out sets IFR T1 as the counter rolls over from zero: N+1 cycles after
+        // writing T1CH. Free-running, it reloads from the latch on the next cycle, every N+2.
+        if self.t1_reload {
+            self.t1_counter = self.t1_latch;
+            self.t1_reload = false;
+        } else {
+            let (counter, timeout) = self.t1_counter.overflowing_sub(1);
+            self.t1_counter = counter;
+            if timeout {
+                let free_running = self.acr & Self::ACR_T1_FREE_RUN != 0;
+                if self.t1_armed {
+                    self.ifr |= Self::IRQ_T1;
+                    self.pb7 = !free_running || !self.pb7;
+                    self.t1_armed = free_running;
+                }
+                self.t1_reload = free_running;
+            }
+        }
+
+        // T2 is one-shot only, counting down cycles unless it's counting PB6 pulses
+        if self.acr & Self::ACR_T2_PULSES == 0 {
+            let (counter, timeout) = self.t2_counter.overflowing_sub(1);
+            self.t2_counter = counter;
+            if timeout && self.t2_armed {
+                self.ifr |= Self::IRQ_T2;
+                self.t2_armed = false;
+            }
+        }
+
+        // the internally clocked shift register modes
+        if self.sr_bits > 0 && self.sr_internal_clock() {
+            self.sr_timer -= 1;
+            if self.sr_timer == 0 {
+                self.sr_timer = self.sr_period();
+                self.sr_clock = !self.sr_clock;
+                self.shift(self.sr_clock);
+            }
+        }
+    }
+}
+
+impl Device for Via {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    // Registers are cleared on reset, but not the timers or the shift register.
+    fn reset(&mut self) {
+        self.ora = 0x00;
+        self.orb = 0x00;
+        self.ddra = 0x00;
+        self.ddrb = 0x00;
+        self.acr = 0x00;
+        self.pcr = 0x00;
+        self.ifr = 0x00;
+        self.ier = 0x00;
+        self.sr_bits = 0;
+        self.ca2_out = true;
+        self.cb2_out = true;
+        self.ca2_pulse = false;
+        self.cb2_pulse = false;
+    }
+
+    fn step(&mut self, cycles: u8) {
+        for _ in 0..cycles {
+            self.tick();
+        }
+    }
+
+    fn read(&mut self, reg: u16) -> u8 {
+        let data = self.peek(reg);
+        match reg {
+            Self::REG_ORB => self.port_b_access(),
+            Self::REG_ORA => self.port_a_access(),
+            Self::REG_T1CL => self.ifr &= !Self::IRQ_T1,
+            Self::REG_T2CL => self.ifr &= !Self::IRQ_T2,
+            Self::REG_SR => self.start_shift(),
+            _ => {}
+        }
+        data
+    }
+
+    fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            Self::REG_ORB => {
+                self.orb = data;
+                self.port_b_access();
+                self.port_b_write();
+            }
+            Self::REG_ORA => {
+                self.ora = data;
+                self.port_a_access();
+            }
+            Self::REG_DDRB => self.ddrb = data,
+            Self::REG_DDRA => self.ddra = data,
+            Self::REG_T1CL | Self::REG_T1LL => {
+                self.t1_latch = (self.t1_latch & 0xFF00) | data as u16;
+            }
+            Self::REG_T1CH => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | (data as u16) << 8;
+                self.t1_counter = self.t1_latch;
+                self.t1_armed = true;
+                self.t1_reload = false;
+                self.pb7 = false;
+                self.ifr &= !Self::IRQ_T1;
+            }
+            Self::REG_T1LH => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | (data as u16) << 8;
+                self.ifr &= !Self::IRQ_T1;
+            }
+            Self::REG_T2CL => self.t2_latch_lo = data,
+            Self::REG_T2CH => {
+                self.t2_counter = (data as u16) << 8 | self.t2_latch_lo as u16;
+                self.t2_armed = true;
+                self.ifr &= !Self::IRQ_T2;
+            }
+            Self::REG_SR => {
+                self.sr = data;
+                self.start_shift();
+            }
+            Self::REG_ACR => self.acr = data,
+            Self::REG_PCR => {
+                self.pcr = data;
+                self.ca2_out = true;
+                self.cb2_out = true;
+            }
+            // writing 1 to a flag clears it
+            Self::REG_IFR => self.ifr &= !(data & 0x7F),
+            // bit 7 selects whether the 1 bits set or clear their enables
+            Self::REG_IER if data & 0x80 != 0 => self.ier |= data & 0x7F,
+            Self::REG_IER => self.ier &= !data,
+            Self::REG_ORAH => self.ora = data,
+            _ => {}
+        }
+    }
+
+    fn peek(&self, reg: u16) -> u8 {
+        match reg {
+            Self::REG_ORB if self.acr & Self::ACR_PB_LATCH != 0 => {
+                (self.orb & self.ddrb) | (self.irb_latch & !self.ddrb)
+            }
+            Self::REG_ORB => self.port_b(),
+            Self::REG_ORA | Self::REG_ORAH => {
+                if self.acr & Self::ACR_PA_LATCH != 0 {
+                    self.ira_latch
+                } else {
+                    self.port_a()
+                }
+            }
+            Self::REG_DDRB => self.ddrb,
+            Self::REG_DDRA => self.ddra,
+            Self::REG_T1CL => self.t1_counter as u8,
+            Self::REG_T1CH => (self.t1_counter >> 8) as u8,
+            Self::REG_T1LL => self.t1_latch as u8,
+            Self::REG_T1LH => (self.t1_latch >> 8) as u8,
+            Self::REG_T2CL => self.t2_counter as u8,
+            Self::REG_T2CH => (self.t2_counter >> 8) as u8,
+            Self::REG_SR => self.sr,
+            Self::REG_ACR => self.acr,
+            Self::REG_PCR => self.pcr,
+            // bit 7 is set while any enabled interrupt is flagged
+            Self::REG_IFR => self.ifr | (self.irq() as u8) << 7,
+            Self::REG_IER => self.ier | 0x80,
+            _ => 0x00,
+        }
+    }
+
+    // The timers time out, and the shift register finishes on an edge of its clock; the other
+    // interrupts come from the pins.
+    fn next_interrupt(&self) -> Option<u32> {
+        if self.irq() {
+            return Some(0);
+        }
+        let enabled = |irq| self.ier & irq != 0;
+        let t1 = (self.t1_armed && enabled(Self::IRQ_T1)).then(|| match self.t1_reload {
+            true => self.t1_latch as u32 + 2,
+            false => self.t1_counter as u32 + 1,
+        });
+        let t2 = (self.t2_armed && enabled(Self::IRQ_T2) && self.acr & Self::ACR_T2_PULSES == 0)
+            .then(|| self.t2_counter as u32 + 1);
+        let sr = (self.sr_bits > 0 && enabled(Self::IRQ_SR) && self.sr_internal_clock())
+            .then_some(self.sr_timer as u32);
+        [t1, t2, sr].into_iter().flatten().min()
+    }
+
+    fn irq(&self) -> bool {
+        self.ifr & self.ier & 0x7F != 0
+    }
+
+    fn name_for_read(&self, reg: u16) -> Option<&str> {
+        Self::REG_READ.get(reg as usize).copied()
+    }
+
+    fn name_for_write(&self, reg: u16) -> Option<&str> {
+        Self::REG_WRITE.get(reg as usize).copied()
+    }
+}
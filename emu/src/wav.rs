@@ -0,0 +1,71 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+// WavWriter writes 16-bit mono PCM samples as a RIFF WAVE file. The header's sizes are
+// rewritten after every write, so the file stays playable while it's still being recorded,
+// e.g. if the emulator is interrupted.
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    sample_rate: u32,
+    samples: u32, // written so far
+}
+
+const HEADER_SIZE: u32 = 44;
+
+// Create a WAV file at path.
+pub fn create(path: &str, sample_rate: u32) -> io::Result<WavWriter<BufWriter<File>>> {
+    WavWriter::new(BufWriter::new(File::create(path)?), sample_rate)
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(out: W, sample_rate: u32) -> io::Result<Self> {
+        let mut wav = Self {
+            out,
+            sample_rate,
+            samples: 0,
+        };
+        wav.write_header()?;
+        Ok(wav)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Append samples, then update the header to include them.
+    pub fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        self.out
+            .seek(SeekFrom::Start((HEADER_SIZE + self.samples * 2) as u64))?;
+        for s in samples {
+            self.out.write_all(&s.to_le_bytes())?;
+        }
+        self.samples += samples.len() as u32;
+        self.write_header()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let data_size = self.samples * 2;
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(HEADER_SIZE - 8 + data_size).to_le_bytes());
+        header.extend_from_slice(b"WAVE");
+        header.extend_from_slice(b"fmt ");
+        header.extend_from_slice(&16u32.to_le_bytes()); // fmt chunk size
+        header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        header.extend_from_slice(&1u16.to_le_bytes()); // mono
+        header.extend_from_slice(&self.sample_rate.to_le_bytes());
+        header.extend_from_slice(&(self.sample_rate * 2).to_le_bytes()); // bytes per second
+        header.extend_from_slice(&2u16.to_le_bytes()); // bytes per sample
+        header.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&data_size.to_le_bytes());
+
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&header)?;
+        self.out.flush()
+    }
+}
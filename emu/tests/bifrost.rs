@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+
+use pda6502v2emu::asm::{branch, label, val, Assembler, Operand};
+use pda6502v2emu::bifrost::Bifrost;
+use pda6502v2emu::bus::Bus;
+use pda6502v2emu::mon;
+use pda6502v2emu::spi::SpiDevice;
+use pda6502v2emu::via::Via;
+
+mod common;
+
+const LEDS: u16 = 0xDE00;
+const LEDS_SRC: u16 = 0xDE01;
+const SPI_CS: u16 = 0xDE10;
+const SPI_DATA: u16 = 0xDE11;
+
+// An SPI peripheral recording what it sees, and replying with queued bytes, then $00.
+#[derive(Default)]
+struct Recorder {
+    events: Vec<Event>,
+    replies: VecDeque<u8>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Event {
+    Select,
+    Deselect,
+    Byte(u8),
+}
+
+impl SpiDevice for Recorder {
+    fn name(&self) -> &str {
+        "RECORDER"
+    }
+
+    fn select(&mut self) {
+        self.events.push(Event::Select);
+    }
+
+    fn deselect(&mut self) {
+        self.events.push(Event::Deselect);
+    }
+
+    fn transfer(&mut self, mosi: u8) -> u8 {
+        self.events.push(Event::Byte(mosi));
+        self.replies.pop_front().unwrap_or(0x00)
+    }
+}
+
+fn recorder(bus: &mut Bus, cs: usize) -> &mut Recorder {
+    let bifrost = bus.device_mut::<Bifrost>().unwrap();
+    bifrost.spi_mut().device_mut(cs).unwrap()
+}
+
+// A bus with the LEDs' power-up animation done.
+fn bus() -> Bus {
+    let mut bus = Bus::new();
+    bus.step_cycles(10 * 65537);
+    bus
+}
+
+#[test]
+fn test_leds_animation() {
+    let mut bus = Bus::new();
+    assert!(bus.device::<Bifrost>().unwrap().animating());
+
+    // dark, then a light sweeping from left to right, a step every 65537 cycles
+    let mut shown = vec![bus.leds().unwrap()];
+    for _ in 0..9 {
+        bus.step_cycles(65537);
+        shown.push(bus.leds().unwrap());
+    }
+    assert_eq!(
+        shown,
+        [0x00, 0x80, 0x40, 0x20, 0x10, 0x08, 0x04, 0x02, 0x01, 0b11000011]
+    );
+    assert!(!bus.device::<Bifrost>().unwrap().animating());
+
+    // writes made while animating show once it's done, and a CPU reset doesn't restart it
+    let mut bus = Bus::new();
+    bus.write(LEDS, 0x5A);
+    bus.step_cycles(9 * 65537);
+    assert_eq!(bus.leds(), Some(0x5A));
+    bus.reset();
+    assert_eq!(bus.leds(), Some(0x5A));
+
+    // no BIFRÖST, no LEDs
+    assert_eq!(Bus::new_ram_only().leds(), None);
+}
+
+#[test]
+fn test_leds_sources() {
+    let mut bus = bus();
+    bus.write(LEDS, 0x81);
+    assert_eq!(bus.read(LEDS), 0x81);
+    assert_eq!(bus.leds(), Some(0x81));
+
+    // the data bus, and the address bus low and high bytes, as of the last access
+    bus.write(LEDS_SRC, Bifrost::SRC_DATA);
+    assert_eq!(bus.read(LEDS_SRC), 0x01);
+    bus.write(0x1234, 0x42);
+    assert_eq!(bus.leds(), Some(0x42));
+    bus.write(LEDS_SRC, Bifrost::SRC_ADDR_LO);
+    bus.read(0x1234);
+    assert_eq!(bus.leds(), Some(0x34));
+    bus.write(LEDS_SRC, Bifrost::SRC_ADDR_HI);
+    bus.read(0xABCD);
+    assert_eq!(bus.leds(), Some(0xAB));
+
+    // interrupt lines, active low: lit while released
+    bus.write(LEDS_SRC, Bifrost::SRC_IRQ);
+    assert_eq!(bus.leds(), Some(0xFF));
+    bus.write(0xDC1E, 0xC0); // VIA2 IER: T1
+    bus.write(0xDC14, 0x00);
+    bus.write(0xDC15, 0x00);
+    bus.step(1);
+    assert_eq!(bus.leds(), Some(0b1011_1110));
+    bus.write(0xDC1D, 0x40);
+    bus.set_irq(true);
+    assert_eq!(bus.leds(), Some(0b1111_1110));
+
+    // other sources show nothing
+    bus.write(LEDS_SRC, 0x05);
+    assert_eq!(bus.leds(), Some(0x00));
+}
+
+// The IRQ lines come from whatever's mapped where the VIAs and UART are.
+#[test]
+fn test_leds_irq_by_address() {
+    let mut bus = Bus::new_ram_only();
+    bus.map(0xDE00..=0xDEFF, Bifrost::new()).unwrap();
+    bus.map(0xDC10..=0xDC1F, Via::new("TIMERS")).unwrap();
+    bus.step_cycles(10 * 65537);
+    bus.write(LEDS_SRC, Bifrost::SRC_IRQ);
+    assert_eq!(bus.leds(), Some(0xFF));
+    bus.write(0xDC1E, 0xC0); // IER: T1
+    bus.write(0xDC14, 0x00);
+    bus.write(0xDC15, 0x00);
+    bus.step(1);
+    assert_eq!(bus.leds(), Some(0b1011_1110));
+}
+
+#[test]
+fn test_leds_status_line() {
+    let bus = bus();
+    assert_eq!(mon::leds(bus.leds().unwrap()), "●●○○○○●●");
+    assert_eq!(mon::leds(0x01), "○○○○○○○●");
+}
+
+#[test]
+fn test_spi_chip_selects() {
+    let mut bus = Bus::new();
+    let spi = bus.device_mut::<Bifrost>().unwrap().spi_mut();
+    spi.attach(0, Recorder::default());
+    spi.attach(3, Recorder::default());
+    assert_eq!(bus.read(SPI_CS), 0xFF);
+
+    // each device sees its own line's edges
+    bus.write(SPI_CS, 0b1111_1110);
+    bus.write(SPI_CS, 0b1111_0110);
+    bus.write(SPI_CS, 0b1111_0111);
+    bus.write(SPI_CS, 0b1111_1111);
+    assert_eq!(bus.read(SPI_CS), 0xFF);
+    use Event::*;
+    assert_eq!(recorder(&mut bus, 0).events, [Select, Deselect]);
+    assert_eq!(recorder(&mut bus, 3).events, [Select, Deselect]);
+}
+
+#[test]
+fn test_spi_transfer() {
+    let mut bus = Bus::new();
+    let spi = bus.device_mut::<Bifrost>().unwrap().spi_mut();
+    spi.attach(0, Recorder::default());
+    spi.attach(1, Recorder::default());
+    recorder(&mut bus, 0).replies.extend([0xA5, 0x5A]);
+    recorder(&mut bus, 1).replies.extend([0x0F]);
+
+    // MSB first, a bit every 2 SPI clock ticks, 8 to a system clock cycle
+    bus.write(SPI_CS, 0b1111_1110);
+    bus.write(SPI_DATA, 0x3C);
+    let busy = |bus: &Bus| bus.device::<Bifrost>().unwrap().spi().busy();
+    assert!(busy(&bus));
+    assert_eq!(bus.peek(SPI_DATA), 0x3C);
+    bus.step(1);
+    assert!(busy(&bus));
+    assert_eq!(bus.peek(SPI_DATA), 0xAC);
+    bus.step(1);
+    assert!(!busy(&bus));
+    assert_eq!(bus.read(SPI_DATA), 0xA5);
+
+    // with several selected, any driving MISO low wins
+    bus.write(SPI_CS, 0b1111_1100);
+    bus.write(SPI_DATA, 0x42);
+    bus.step(2);
+    assert_eq!(bus.read(SPI_DATA), 0x0A);
+
+    // and with none, MISO floats high
+    bus.write(SPI_CS, 0b1111_1111);
+    bus.write(SPI_DATA, 0x99);
+    bus.step(2);
+    assert_eq!(bus.read(SPI_DATA), 0xFF);
+
+    use Event::*;
+    assert_eq!(
+        recorder(&mut bus, 0).events,
+        [Select, Byte(0x3C), Byte(0x42), Deselect]
+    );
+    assert_eq!(recorder(&mut bus, 1).events, [Select, Byte(0x42), Deselect]);
+}
+
+// Like ShellSPI in shell.s: stream a string to the device on CS0 as fast as the CPU can.
+#[test]
+fn test_spi_shell() {
+    let code = Assembler::new()
+        .org(0x0400)
+        .lda(Operand::Imm(1 << 0))
+        .trb(Operand::Abs(val(SPI_CS)))
+        .ldx(Operand::Imm(0))
+        .label("eachchar")
+        .lda(Operand::AbsX(label("text")))
+        .beq(Operand::Rel(branch("done")))
+        .sta(Operand::Abs(val(SPI_DATA)))
+        .inx()
+        .jmp(Operand::Abs(label("eachchar")))
+        .label("done")
+        .lda(Operand::Imm(1 << 0))
+        .tsb(Operand::Abs(val(SPI_CS)))
+        .stp()
+        .label("text")
+        .data("hi!\0".into())
+        .assemble()
+        .unwrap();
+    let mut sys = common::sys(code);
+    let spi = sys.bus.device_mut::<Bifrost>().unwrap().spi_mut();
+    spi.attach(0, Recorder::default());
+    sys.run().unwrap();
+    use Event::*;
+    assert_eq!(
+        recorder(&mut sys.bus, 0).events,
+        [Select, Byte(b'h'), Byte(b'i'), Byte(b'!'), Deselect]
+    );
+}
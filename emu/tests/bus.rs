@@ -0,0 +1,446 @@
+use std::fs;
+
+use regex::Regex;
+
+use pda6502v2emu::asm::{val, Assembler, Operand};
+use pda6502v2emu::bus::{Bus, BusError, ProtectedWrite, Protection, Select};
+use pda6502v2emu::cpu::Cpu;
+use pda6502v2emu::device::Device;
+use pda6502v2emu::uart::Uart;
+
+// A device with a data register that counts its reads, and an IRQ raised by writing to a
+// control register, for checking how the bus dispatches to devices.
+#[derive(Default)]
+struct Counter {
+    count: u8,
+    irq: bool,
+    cycles: u64,
+}
+
+impl Device for Counter {
+    fn name(&self) -> &str {
+        "COUNTER"
+    }
+
+    fn read(&mut self, reg: u16) -> u8 {
+        let data = self.peek(reg);
+        if reg == 0 {
+            self.count += 1;
+        }
+        data
+    }
+
+    fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            0 => self.count = data,
+            _ => self.irq = data != 0,
+        }
+    }
+
+    fn peek(&self, reg: u16) -> u8 {
+        match reg {
+            0 => self.count,
+            _ => self.irq as u8,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn step(&mut self, cycles: u8) {
+        self.cycles += cycles as u64;
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+
+    fn name_for_read(&self, reg: u16) -> Option<&str> {
+        (reg == 0).then_some("COUNT")
+    }
+}
+
+#[test]
+fn test_device_dispatch() {
+    let mut bus = Bus::new_ram_only();
+    bus.map(0xD000..=0xD001, Counter::default()).unwrap();
+
+    // registers are addressed by their offset into the range
+    bus.write(0xD000, 0x10);
+    assert_eq!(bus.read(0xD000), 0x10);
+    assert_eq!(bus.read(0xD000), 0x11);
+    assert_eq!(bus.device::<Counter>().unwrap().count, 0x12);
+
+    // peeking has no side effects
+    assert_eq!(bus.peek(0xD000), 0x12);
+    assert_eq!(bus.peek_u16(0xD000), 0x0012);
+    assert_eq!(bus.device::<Counter>().unwrap().count, 0x12);
+
+    // addresses outside the range are still RAM
+    bus.write(0xCFFF, 0xAA);
+    bus.write(0xD002, 0xBB);
+    assert_eq!((bus.read(0xCFFF), bus.read(0xD002)), (0xAA, 0xBB));
+
+    bus.step(3);
+    bus.step(4);
+    assert_eq!(bus.device::<Counter>().unwrap().cycles, 7);
+    bus.device_mut::<Counter>().unwrap().count = 0x42;
+    assert_eq!(bus.peek(0xD000), 0x42);
+    bus.reset();
+    assert_eq!(bus.peek(0xD000), 0x00);
+    assert!(bus.device::<Uart>().is_none());
+}
+
+#[test]
+fn test_device_interrupts() {
+    let mut bus = Bus::new_ram_only();
+    bus.map(0xD000..=0xD001, Counter::default()).unwrap();
+    bus.map(0xD010..=0xD011, Counter::default()).unwrap();
+    assert!(!bus.is_interrupt());
+
+    // any device can pull the shared IRQ line low
+    bus.write(0xD011, 1);
+    assert!(bus.is_interrupt());
+    bus.write(0xD011, 0);
+    assert!(!bus.is_interrupt());
+    assert!(!bus.is_nmi());
+
+    // and so can something outside the bus
+    bus.set_irq(true);
+    assert!(bus.is_interrupt());
+    bus.set_irq(false);
+    assert!(!bus.is_interrupt());
+}
+
+#[test]
+fn test_device_overlap() {
+    let mut bus = Bus::new_ram_only();
+    bus.map(0xD000..=0xD00F, Counter::default()).unwrap();
+    assert_eq!(
+        bus.map(0xD00F..=0xD010, Counter::default()),
+        Err(BusError::Overlap {
+            range: 0xD00F..=0xD010,
+            name: "COUNTER".to_string(),
+            existing: 0xD000..=0xD00F
+        })
+    );
+    assert_eq!(
+        bus.map(0xCFF0..=0xD0FF, Counter::default())
+            .unwrap_err()
+            .to_string(),
+        "$CFF0-$D0FF overlaps COUNTER at $D000-$D00F"
+    );
+    #[allow(clippy::reversed_empty_ranges)]
+    let empty = 0xD020..=0xD01F;
+    assert_eq!(
+        bus.map(empty.clone(), Counter::default()),
+        Err(BusError::EmptyRange(empty))
+    );
+    bus.map(0xD010..=0xD010, Counter::default()).unwrap();
+}
+
+#[test]
+fn test_register_names() {
+    let mut bus = Bus::new_ram_only();
+    bus.map(0xD000..=0xD001, Counter::default()).unwrap();
+    bus.write(0x0200, 0x5A);
+    assert_eq!(bus.name_for_read(0x0200), "#$5A");
+    assert_eq!(bus.name_for_read(0xD000), "COUNTER:COUNT");
+    assert_eq!(bus.name_for_read(0xD001), "COUNTER:$01");
+    assert_eq!(bus.name_for_write(0x0200), "");
+
+    // the UART is mapped on the default bus
+    let bus = Bus::new();
+    assert!(bus.device::<Uart>().is_some());
+    assert_eq!(bus.name_for_read(0xDC21), "UART:SRA");
+    assert_eq!(bus.name_for_write(0xDC21), "UART:CSRA");
+    assert_eq!(bus.name_for_read(0xDC22), "UART:$02");
+}
+
+// The chip selects decoded by bifröst/adec.v, as (name, predicate) parsed from its
+// assign statements, e.g.
+//
+//     assign via1_cs    = ~(addr >= 16'hDC00 && addr <= 16'hDC0F);
+//
+// Each is a single range, or for ram_cs, everything outside one.
+type ChipSelect = (String, Box<dyn Fn(u16) -> bool>);
+
+fn adec_chip_selects() -> Vec<ChipSelect> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../bifröst/adec.v");
+    let source = fs::read_to_string(path).unwrap();
+    let assign = Regex::new(r"assign\s+(\w+)_cs\s*=\s*~\((.*)\);").unwrap();
+    let compare = Regex::new(r"addr\s*(>=|<=|<|>)\s*16'h([0-9A-Fa-f]+)").unwrap();
+
+    let mut selects: Vec<ChipSelect> = Vec::new();
+    for line in source.lines() {
+        let Some(caps) = assign.captures(line) else {
+            continue;
+        };
+        let expr = caps[2].to_string();
+        let terms: Vec<(String, u16)> = compare
+            .captures_iter(&expr)
+            .map(|c| (c[1].to_string(), u16::from_str_radix(&c[2], 16).unwrap()))
+            .collect();
+        assert_eq!(terms.len(), 2, "unexpected adec.v expression: {expr}");
+        let any = expr.contains("||");
+        let matches = move |addr: u16| {
+            let mut results = terms.iter().map(|(op, val)| match op.as_str() {
+                ">=" => addr >= *val,
+                "<=" => addr <= *val,
+                "<" => addr < *val,
+                _ => addr > *val,
+            });
+            if any {
+                results.any(|r| r)
+            } else {
+                results.all(|r| r)
+            }
+        };
+        selects.push((caps[1].to_uppercase(), Box::new(matches)));
+    }
+    selects
+}
+
+#[test]
+fn test_memory_map_matches_adec() {
+    let selects = adec_chip_selects();
+    let names: Vec<&str> = selects.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["RAM", "SID", "VIA1", "VIA2", "UART", "BIFROST"]);
+
+    let bus = Bus::new();
+    for addr in 0..=0xFFFF {
+        let selected: Vec<&str> = selects
+            .iter()
+            .filter(|(_, matches)| matches(addr))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let got = match bus.select(addr) {
+            Select::Ram => vec!["RAM"],
+            Select::Device { name, .. } => vec![name],
+            Select::OpenBus => vec![],
+        };
+        assert_eq!(got, selected, "${addr:04X}");
+    }
+
+    // devices see the register offsets their address lines decode
+    let reg = |addr| match bus.select(addr) {
+        Select::Device { reg, .. } => reg,
+        other => panic!("${addr:04X} selects {other:?}"),
+    };
+    assert_eq!(reg(0xD400), 0x00);
+    assert_eq!(reg(0xD41B), 0x1B);
+    assert_eq!(reg(0xD43B), 0x1B);
+    assert_eq!(reg(0xD7FF), 0x1F);
+    assert_eq!(reg(0xDC1F), 0x0F);
+    assert_eq!(reg(0xDE11), 0x11);
+}
+
+#[test]
+fn test_open_bus() {
+    let mut bus = Bus::new();
+
+    // writes to holes in the I/O area go nowhere, and reads see the last value on the bus
+    bus.write(0xD000, 0x42);
+    assert_eq!(bus.read(0xDD00), 0x42);
+    bus.write(0xD000, 0x17);
+    assert_eq!(bus.peek(0xD000), 0x17);
+    assert_eq!(bus.name_for_read(0xDF00), "#$17");
+
+    // which for LDA abs is the high byte of the address
+    bus.load(0x0200, vec![0xAD, 0x00, 0xD8]).unwrap(); // LDA $D800
+    let mut cpu = Cpu::new();
+    cpu.pc = 0x0200;
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(cpu.a, 0xD8);
+
+    // RAM either side of the I/O area
+    bus.write(0xCFFF, 0x01);
+    bus.write(0xE000, 0x02);
+    assert_eq!((bus.read(0xCFFF), bus.read(0xE000)), (0x01, 0x02));
+}
+
+#[test]
+fn test_bank_registers() {
+    let mut bus = Bus::new();
+
+    // windows start out mapped straight through to the first 64 KiB
+    assert_eq!(bus.read(0xDE28), 0x08);
+    assert_eq!(bus.physical(0x8123), Some(0x08123));
+    assert_eq!(bus.physical(0xDC00), None);
+    assert_eq!(bus.name_for_write(0xDE2F), "BIFROST:BANK15");
+
+    // map $8000-$8FFF to the top of RAM
+    bus.write(0x8000, 0x11);
+    bus.write(0xDE28, 0x7F);
+    assert_eq!(bus.physical(0x8123), Some(0x7F123));
+    assert_eq!(bus.read(0x8000), 0x00);
+    bus.write(0x8000, 0x22);
+    assert_eq!(bus.read_physical(0x7F000), 0x22);
+    assert_eq!(bus.read_physical(0x08000), 0x11);
+
+    // the same bank can be seen through two windows; bank numbers are 7 bits
+    bus.write(0xDE21, 0xFF);
+    assert_eq!(bus.read(0x1000), 0x22);
+    assert_eq!(bus.peek(0xDE21), 0x7F);
+
+    // the I/O area isn't banked
+    bus.write(0xDE2D, 0x40);
+    assert_eq!(
+        bus.select(0xDC21),
+        Select::Device {
+            name: "UART",
+            reg: 1
+        }
+    );
+    assert_eq!(bus.physical(0xD000), None);
+    assert_eq!(bus.peek(0xDE28), 0x7F);
+
+    // reset maps the first 64 KiB again, so the CPU boots from the same ROM
+    bus.reset();
+    assert_eq!(bus.read(0x8000), 0x11);
+    assert_eq!(bus.peek(0xDE21), 0x01);
+}
+
+// Code that remaps the window it's running from carries on from the same address in the new
+// bank.
+#[test]
+fn test_bank_switch_under_running_code() {
+    let mut bus = Bus::new();
+
+    // in bank $02: switch $2000-$2FFF to bank $41, then store $55 if still running from here
+    let code = Assembler::new()
+        .org(0x2000)
+        .lda(Operand::Imm(0x41))
+        .sta(Operand::Abs(val(0xDE22)))
+        .lda(Operand::Imm(0x55))
+        .sta(Operand::Abs(val(0x0300)))
+        .stp()
+        .assemble()
+        .unwrap();
+    bus.load(0x2000, code).unwrap();
+
+    // in bank $41, after the STA: store $AA, then switch back to bank $02
+    let code = Assembler::new()
+        .org(0x2005)
+        .lda(Operand::Imm(0xAA))
+        .sta(Operand::Abs(val(0x0300)))
+        .lda(Operand::Imm(0x02))
+        .sta(Operand::Abs(val(0xDE22)))
+        .assemble()
+        .unwrap();
+    for (i, byte) in code.iter().enumerate() {
+        bus.write_physical(0x41005 + i as u32, *byte);
+    }
+
+    let mut cpu = Cpu::new();
+    cpu.pc = 0x2000;
+    for _ in 0..4 {
+        cpu.step(&mut bus).unwrap();
+    }
+    assert_eq!(cpu.pc, 0x200A);
+    assert_eq!(bus.physical(cpu.pc), Some(0x4100A));
+    assert_eq!(bus.read(0x0300), 0xAA);
+
+    // and switching back returns to bank $02, past the code that would have stored $55
+    for _ in 0..2 {
+        cpu.step(&mut bus).unwrap();
+    }
+    assert_eq!(cpu.pc, 0x200F);
+    assert_eq!(bus.physical(cpu.pc), Some(0x0200F));
+    assert_eq!(bus.peek(0xDE22), 0x02);
+    assert_eq!(bus.read(0x0300), 0xAA);
+}
+
+#[test]
+fn test_protected_regions() {
+    let mut bus = Bus::new();
+    bus.protect(0xF000..=0xFFFF, Protection::ReadOnly);
+    bus.protect(0x0300..=0x03FF, Protection::WriteTrap);
+    bus.protect(0x0380..=0x03FF, Protection::WriteIgnored);
+
+    // loading isn't a CPU write, so goes through
+    bus.load(0xFFFC, vec![0x00, 0xF0]).unwrap();
+    assert_eq!(bus.read_u16(0xFFFC), 0xF000);
+    assert_eq!(bus.take_protected_writes(), vec![]);
+
+    // a stray store to ROM is dropped, a trapped one goes through, and both are recorded
+    bus.load(
+        0x0200,
+        vec![0x8D, 0xFC, 0xFF, 0x8D, 0x00, 0x03, 0x8D, 0x80, 0x03],
+    )
+    .unwrap();
+    let mut cpu = Cpu::new();
+    cpu.pc = 0x0200;
+    cpu.a = 0x42;
+    for _ in 0..3 {
+        cpu.step(&mut bus).unwrap();
+    }
+    assert_eq!(bus.read(0xFFFC), 0x00);
+    assert_eq!(bus.read(0x0300), 0x42);
+    assert_eq!(bus.read(0x0380), 0x00);
+    assert_eq!(
+        bus.take_protected_writes(),
+        vec![
+            ProtectedWrite {
+                addr: 0xFFFC,
+                data: 0x42,
+                protection: Protection::ReadOnly
+            },
+            ProtectedWrite {
+                addr: 0x0300,
+                data: 0x42,
+                protection: Protection::WriteTrap
+            },
+        ]
+    );
+    assert_eq!(bus.take_protected_writes(), vec![]);
+
+    // protection is for RAM; devices and holes in the I/O area are unaffected
+    bus.protect(0xD000..=0xDFFF, Protection::ReadOnly);
+    bus.write(0xDE00, 0x81);
+    assert_eq!(bus.read(0xDE00), 0x81);
+    assert_eq!(bus.protection(0x0390), Some(Protection::WriteIgnored));
+    assert_eq!(bus.protection(0x0300), Some(Protection::WriteTrap));
+    assert_eq!(bus.protection(0x0400), None);
+    assert_eq!(bus.take_protected_writes(), vec![]);
+}
+
+// Protection belongs to the physical RAM, wherever its bank is mapped.
+#[test]
+fn test_protection_follows_banks() {
+    let mut bus = Bus::new();
+    bus.protect(0xF000..=0xFFFF, Protection::ReadOnly);
+
+    // the ROM's bank $0F seen through the window at $2000 is still read-only
+    bus.load(0xF000, vec![0x11]).unwrap();
+    bus.write(0xDE22, 0x0F);
+    assert_eq!(bus.protection(0x2000), Some(Protection::ReadOnly));
+    bus.write(0x2000, 0x42);
+    assert_eq!(bus.read(0x2000), 0x11);
+    assert_eq!(
+        bus.take_protected_writes(),
+        vec![ProtectedWrite {
+            addr: 0x2000,
+            data: 0x42,
+            protection: Protection::ReadOnly
+        }]
+    );
+
+    // and another bank mapped at $F000 isn't
+    bus.write(0xDE2F, 0x20);
+    assert_eq!(bus.protection(0xF000), None);
+    bus.write(0xF000, 0x42);
+    assert_eq!(bus.read(0xF000), 0x42);
+    assert_eq!(bus.take_protected_writes(), vec![]);
+
+    // a range spanning windows is protected where each of them is mapped when protecting
+    bus.protect(0x1F00..=0x20FF, Protection::WriteIgnored);
+    assert_eq!(bus.protection(0x1EFF), None);
+    bus.write(0xDE22, 0x02);
+    assert_eq!(bus.protection(0x2000), None);
+    bus.write(0xDE22, 0x0F);
+    assert_eq!(bus.protection(0x1F00), Some(Protection::WriteIgnored));
+    assert_eq!(bus.protection(0x2000), Some(Protection::WriteIgnored));
+    assert_eq!(bus.protection(0x2100), Some(Protection::ReadOnly));
+}
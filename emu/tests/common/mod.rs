@@ -0,0 +1,22 @@
+use pda6502v2emu::isa::Variant;
+use pda6502v2emu::loader::{Image, Segment};
+use pda6502v2emu::sys::{Os, Sys};
+
+// Code to load at $0400 and start from there.
+pub fn image(code: Vec<u8>) -> Image {
+    Image {
+        segments: vec![Segment {
+            name: None,
+            addr: 0x0400,
+            data: code,
+        }],
+        start: Some(0x0400),
+    }
+}
+
+// A system without an OS, about to run code from $0400.
+pub fn sys(code: Vec<u8>) -> Sys {
+    let mut sys = Sys::with_os(Variant::default(), Os::default());
+    sys.load(&image(code)).unwrap();
+    sys
+}
@@ -1,5 +1,5 @@
 use pda6502v2emu::asm::BranchTarget;
-use pda6502v2emu::asm::{label, val};
+use pda6502v2emu::asm::{branch, label, val};
 use pda6502v2emu::asm::{Assembler, Operand};
 use pda6502v2emu::bus::Bus;
 use pda6502v2emu::cpu::stat;
@@ -30,7 +30,7 @@ macro_rules! assert_eq_hex16 {
 
 macro_rules! step_and_assert {
     ($cpu:expr, $bus:expr, $reg:ident, $val:expr, $stat:literal) => {
-        $cpu.step($bus);
+        $cpu.step($bus).unwrap();
         println!("{:?}", $cpu);
         assert_eq_hex!($cpu.$reg, $val);
         assert_eq!(stat(&$cpu.p), $stat);
@@ -39,7 +39,7 @@ macro_rules! step_and_assert {
 
 macro_rules! step_and_assert_mem {
     ($cpu:expr, $bus:expr, $addr:expr, $val:expr, $stat:literal) => {
-        $cpu.step($bus);
+        $cpu.step($bus).unwrap();
         println!("{:?}", $cpu);
         assert_eq_hex!($bus.read($addr), $val);
         assert_eq!(stat(&$cpu.p), $stat);
@@ -80,7 +80,8 @@ fn test_adc() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     step_and_assert!(cpu, bus, a, 0x21, "nv-bdizc"); // ADC #$11
     step_and_assert!(cpu, bus, a, 0x10, "nv-bdizC"); // ADC $F0
@@ -92,6 +93,116 @@ fn test_adc() {
     step_and_assert!(cpu, bus, a, 0xFF, "Nv-bdizc"); // ADC ($F4),Y
 }
 
+// Result of a decimal mode instruction: (A, N, V, Z, C).
+type DecimalResult = (u8, bool, bool, bool, bool);
+
+// Digits of a valid BCD byte, and back.
+fn bcd_value(x: u8) -> i16 {
+    (x >> 4) as i16 * 10 + (x & 0x0F) as i16
+}
+
+fn to_bcd(x: i16) -> u8 {
+    let x = x.rem_euclid(100) as u8;
+    (x / 10) << 4 | (x % 10)
+}
+
+// A digit as a signed 4-bit number, as the high digit's sign bit is bit 7 of the byte.
+fn signed_digit(x: u8) -> i16 {
+    (x >> 4) as i16 - if x & 0x80 != 0 { 16 } else { 0 }
+}
+
+// Decimal ADC of valid BCD operands, worked as on paper. The W65C02S sets N and Z from the
+// decimal result; V is signed overflow of the high digits plus the carry from the low digits,
+// as Bruce Clark's "Decimal Mode" (6502.org) describes.
+fn bcd_adc(a: u8, b: u8, c: bool) -> DecimalResult {
+    let low_carry = (a & 0x0F) + (b & 0x0F) + c as u8 > 9;
+    let sum = bcd_value(a) + bcd_value(b) + c as i16;
+    let result = to_bcd(sum);
+    let high = signed_digit(a) + signed_digit(b) + low_carry as i16;
+    let overflow = !(-8..=7).contains(&high);
+    (result, result & 0x80 != 0, overflow, result == 0, sum > 99)
+}
+
+// Decimal SBC of valid BCD operands. C is set when nothing was borrowed, and V is the signed
+// overflow of the equivalent binary subtraction, as on every 6502.
+fn bcd_sbc(a: u8, b: u8, c: bool) -> DecimalResult {
+    let diff = bcd_value(a) - bcd_value(b) - !c as i16;
+    let result = to_bcd(diff);
+    let signed = a as i8 as i16 - b as i8 as i16 - !c as i16;
+    let overflow = !(-128..=127).contains(&signed);
+    (result, result & 0x80 != 0, overflow, result == 0, diff >= 0)
+}
+
+// Load a decimal mode instruction with an immediate operand at $1000.
+fn load_decimal(bus: &mut Bus, opcode: fn(&mut Assembler, Operand) -> &mut Assembler) {
+    let mut asm = Assembler::new();
+    let code = opcode(&mut asm, Operand::Imm(0x00)).assemble().unwrap();
+    bus.load(0x1000, code).unwrap();
+}
+
+// Run the instruction load_decimal loaded with the given A, operand and carry.
+fn run_decimal(bus: &mut Bus, cpu: &mut Cpu, (a, b, c): (u8, u8, bool)) -> DecimalResult {
+    use pda6502v2emu::cpu::StatusMask;
+
+    bus.write(0x1001, b);
+    cpu.pc = 0x1000;
+    cpu.a = a;
+    cpu.p = StatusMask::Decimal as u8;
+    cpu.set_p_bit(StatusMask::Carry, c);
+    cpu.step(bus).unwrap();
+    (
+        cpu.a,
+        cpu.get_p_bit(StatusMask::Negative),
+        cpu.get_p_bit(StatusMask::Overflow),
+        cpu.get_p_bit(StatusMask::Zero),
+        cpu.get_p_bit(StatusMask::Carry),
+    )
+}
+
+// Check every combination of valid BCD A and operand, and carry, against a digit model.
+fn assert_decimal_mode(
+    opcode: fn(&mut Assembler, Operand) -> &mut Assembler,
+    model: fn(u8, u8, bool) -> DecimalResult,
+) {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    load_decimal(bus, opcode);
+    for a in (0..100).map(to_bcd) {
+        for b in (0..100).map(to_bcd) {
+            for c in [false, true] {
+                assert_eq!(
+                    run_decimal(bus, &mut cpu, (a, b, c)),
+                    model(a, b, c),
+                    "A:{a:02X} operand:{b:02X} C:{c}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_adc_decimal_valid_bcd() {
+    assert_decimal_mode(Assembler::adc, bcd_adc);
+}
+
+#[test]
+fn test_sbc_decimal_valid_bcd() {
+    assert_decimal_mode(Assembler::sbc, bcd_sbc);
+}
+
+// The well known trick converting a hex digit to BCD with a decimal ADC #0, which works on
+// every 6502 although $0A-$0F aren't valid BCD.
+#[test]
+fn test_adc_decimal_hex_digit_to_bcd() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    load_decimal(bus, Assembler::adc);
+    for digit in 0x00..=0x0F {
+        let (a, ..) = run_decimal(bus, &mut cpu, (digit, 0x00, false));
+        assert_eq!(a, to_bcd(digit as i16), "digit {digit:X}");
+    }
+}
+
 #[test]
 fn test_and() {
     let bus = &mut Bus::new();
@@ -103,7 +214,8 @@ fn test_and() {
     bus.load(
         cpu.pc,
         asm.and(Imm(0b11110000)).print_listing().assemble().unwrap(),
-    );
+    )
+    .unwrap();
 
     step_and_assert!(cpu, bus, a, 0b10010000, "Nv-bdizc"); // ADC #$A0
 }
@@ -125,7 +237,8 @@ fn test_asl() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     use pda6502v2emu::cpu::StatusMask;
 
@@ -135,13 +248,42 @@ fn test_asl() {
     cpu.set_p_bit(StatusMask::Carry, true);
     step_and_assert!(cpu, bus, a, 0b00000000, "nv-bdiZC"); // ASL A
 
-    cpu.step(bus); // ASL $F0,X
+    cpu.step(bus).unwrap(); // ASL $F0,X
     println!("{:?}", cpu);
     let val = bus.read(0xF1);
     assert_eq_hex!(val, 0b10110110);
     assert_eq!(stat(&cpu.p), "Nv-bdizC");
 }
 
+#[test]
+fn test_bbr_bbs() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x4000;
+    bus.write(0x0010, 0b00100000);
+    bus.load(
+        cpu.pc,
+        asm.org(cpu.pc)
+            .label("start")
+            .bbr(5, Operand::ZRel(0x10, branch("start"))) // bit 5 set: don't branch
+            .bbs(4, Operand::ZRel(0x10, branch("start"))) // bit 4 reset: don't branch
+            .bbs(5, Operand::ZRel(0x10, branch("forward"))) // bit 5 set: branch
+            .nop()
+            .label("forward")
+            .bbr(0, Operand::ZRel(0x10, branch("start"))) // bit 0 reset: branch
+            .print_listing()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+
+    step_and_assert!(cpu, bus, pc, 0x4003, "nv-bdizc"); // BBR5 $10,start
+    step_and_assert!(cpu, bus, pc, 0x4006, "nv-bdizc"); // BBS4 $10,start
+    step_and_assert!(cpu, bus, pc, 0x400A, "nv-bdizc"); // BBS5 $10,forward
+    step_and_assert!(cpu, bus, pc, 0x4000, "nv-bdizc"); // BBR0 $10,start
+}
+
 #[test]
 fn test_bcc() {
     let bus = &mut Bus::new();
@@ -155,18 +297,19 @@ fn test_bcc() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     use pda6502v2emu::cpu::StatusMask;
 
     cpu.set_p_bit(StatusMask::Carry, true);
-    cpu.step(bus); // BCC 0x10 (don't branch)
+    cpu.step(bus).unwrap(); // BCC 0x10 (don't branch)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0002);
     assert_eq!(stat(&cpu.p), "nv-bdizC");
 
     cpu.set_p_bit(StatusMask::Carry, false);
-    cpu.step(bus); // BCC 0x20 (do branch)
+    cpu.step(bus).unwrap(); // BCC 0x20 (do branch)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0024);
     assert_eq!(stat(&cpu.p), "nv-bdizc");
@@ -184,18 +327,19 @@ fn test_bcs() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     use pda6502v2emu::cpu::StatusMask;
 
     cpu.set_p_bit(StatusMask::Carry, false);
-    cpu.step(bus); // BCS 0x10 (don't branch)
+    cpu.step(bus).unwrap(); // BCS 0x10 (don't branch)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0002);
     assert_eq!(stat(&cpu.p), "nv-bdizc");
 
     cpu.set_p_bit(StatusMask::Carry, true);
-    cpu.step(bus); // BCS 0x20 (do branch)
+    cpu.step(bus).unwrap(); // BCS 0x20 (do branch)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0024);
     assert_eq!(stat(&cpu.p), "nv-bdizC");
@@ -213,18 +357,19 @@ fn test_beq() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     use pda6502v2emu::cpu::StatusMask;
 
     cpu.set_p_bit(StatusMask::Zero, false);
-    cpu.step(bus); // BEQ 0x10 (don't branch)
+    cpu.step(bus).unwrap(); // BEQ 0x10 (don't branch)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0002);
     assert_eq!(stat(&cpu.p), "nv-bdizc");
 
     cpu.set_p_bit(StatusMask::Zero, true);
-    cpu.step(bus); // BEQ 0x20 (do branch)
+    cpu.step(bus).unwrap(); // BEQ 0x20 (do branch)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0024);
     assert_eq!(stat(&cpu.p), "nv-bdiZc");
@@ -242,26 +387,57 @@ fn test_bit() {
             .data(vec![0xFF, 0x00])
             .label("prog")
             .bit(Operand::Z(cpu.pc.try_into().unwrap()))
-            .bit(Operand::Abs(val(cpu.pc)))
+            .bit(Operand::Abs(val(cpu.pc + 1)))
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
     cpu.pc += 2; // skip the data
 
     cpu.a = 0xFF;
-    cpu.step(bus); // BIT $00 (#$FF)
+    cpu.step(bus).unwrap(); // BIT $00 (#$FF)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0004);
     assert_eq!(stat(&cpu.p), "NV-bdizc"); // 0b11111111 AND 0b11111111 = 0b11111111 = z
 
     cpu.a = 0x00;
-    cpu.step(bus); // BIT $0001 (#$00)
+    cpu.step(bus).unwrap(); // BIT $0001 (#$00)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0007);
     assert_eq!(stat(&cpu.p), "nv-bdiZc"); // 0b00000000 AND 0b00000000 = 0b00000000 = Z
 }
 
+#[test]
+fn test_bit_immediate_and_zeropage_x() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.x = 0x02;
+    bus.write(0x0012, 0b11000000);
+    bus.load(
+        cpu.pc,
+        asm.bit(Operand::Imm(0b11000000))
+            .bit(Operand::ZX(0x10))
+            .print_listing()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+
+    cpu.a = 0b00111111;
+    cpu.step(bus).unwrap(); // BIT #%11000000
+    println!("{:?}", cpu);
+    assert_eq_hex16!(cpu.pc, 0x0002);
+    assert_eq!(stat(&cpu.p), "nv-bdiZc"); // immediate mode only affects Z
+
+    cpu.a = 0b01000000;
+    cpu.step(bus).unwrap(); // BIT $10,X
+    println!("{:?}", cpu);
+    assert_eq_hex16!(cpu.pc, 0x0004);
+    assert_eq!(stat(&cpu.p), "NV-bdizc"); // N & V from operand, Z from A AND operand
+}
+
 #[test]
 fn test_bmi() {
     let bus = &mut Bus::new();
@@ -280,15 +456,16 @@ fn test_bmi() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
-    cpu.step(bus); // LDX #$FF
+    cpu.step(bus).unwrap(); // LDX #$FF
     println!("{cpu:?}");
-    cpu.step(bus); // BMI b
+    cpu.step(bus).unwrap(); // BMI b
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x0005);
-    cpu.step(bus); // LDX #$10
-    cpu.step(bus); // BMI a
+    cpu.step(bus).unwrap(); // LDX #$10
+    cpu.step(bus).unwrap(); // BMI a
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x0009);
 }
@@ -311,15 +488,16 @@ fn test_bne() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
-    cpu.step(bus); // LDX #$01
-    cpu.step(bus); // BNE b
+    cpu.step(bus).unwrap(); // LDX #$01
+    cpu.step(bus).unwrap(); // BNE b
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x0005);
 
-    cpu.step(bus); // LDX #$00
-    cpu.step(bus); // BNE a
+    cpu.step(bus).unwrap(); // LDX #$00
+    cpu.step(bus).unwrap(); // BNE a
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x0009);
 }
@@ -342,18 +520,45 @@ fn test_bpl() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
-    cpu.step(bus); // LDX #$10
-    cpu.step(bus); // BPL b
+    cpu.step(bus).unwrap(); // LDX #$10
+    cpu.step(bus).unwrap(); // BPL b
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x0005);
-    cpu.step(bus); // LDX #$F0
-    cpu.step(bus); // BPL a
+    cpu.step(bus).unwrap(); // LDX #$F0
+    cpu.step(bus).unwrap(); // BPL a
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x0009);
 }
 
+#[test]
+fn test_bra() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x4000;
+    bus.load(
+        cpu.pc,
+        asm.org(cpu.pc)
+            .label("start")
+            .bra(Operand::Rel(branch("forward")))
+            .nop()
+            .label("forward")
+            .bra(Operand::Rel(branch("start")))
+            .print_listing()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+
+    cpu.p = 0xFF;
+    step_and_assert!(cpu, bus, pc, 0x4003, "NV-BDIZC"); // BRA forward
+    cpu.p = 0x00;
+    step_and_assert!(cpu, bus, pc, 0x4000, "nv-bdizc"); // BRA start
+}
+
 #[test]
 fn test_brk_rti() {
     let bus = &mut Bus::new();
@@ -373,7 +578,8 @@ fn test_brk_rti() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     // set the interrupt vector to 0x2000 which is the "irq" label
     bus.write(0xFFFE, 0x00); // IRQ vector (lo)
@@ -388,7 +594,8 @@ fn test_brk_rti() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     cpu.s = 0xF8;
 
@@ -417,18 +624,19 @@ fn test_bvc() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     use pda6502v2emu::cpu::StatusMask;
 
     cpu.set_p_bit(StatusMask::Overflow, true);
-    cpu.step(bus); // BVC 0x10 (don't branch)
+    cpu.step(bus).unwrap(); // BVC 0x10 (don't branch)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0002);
     assert_eq!(stat(&cpu.p), "nV-bdizc");
 
     cpu.set_p_bit(StatusMask::Overflow, false);
-    cpu.step(bus); // BVC 0x20 (do branch)
+    cpu.step(bus).unwrap(); // BVC 0x20 (do branch)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0024);
     assert_eq!(stat(&cpu.p), "nv-bdizc");
@@ -447,18 +655,19 @@ fn test_bvs() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     use pda6502v2emu::cpu::StatusMask;
 
     cpu.set_p_bit(StatusMask::Overflow, false);
-    cpu.step(bus); // BVS 0x10 (don't branch)
+    cpu.step(bus).unwrap(); // BVS 0x10 (don't branch)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0002);
     assert_eq!(stat(&cpu.p), "nv-bdizc");
 
     cpu.set_p_bit(StatusMask::Overflow, true);
-    cpu.step(bus); // BVS 0x20 (do branch)
+    cpu.step(bus).unwrap(); // BVS 0x20 (do branch)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x0024);
     assert_eq!(stat(&cpu.p), "nV-bdizc");
@@ -483,13 +692,14 @@ fn test_cmp() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
-    cpu.step(bus); // CMP data
+    cpu.step(bus).unwrap(); // CMP data
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x0203);
     assert_eq!(stat(&cpu.p), "nv-bdizC");
-    cpu.step(bus); // CMP data,X
+    cpu.step(bus).unwrap(); // CMP data,X
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x0206);
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
@@ -512,16 +722,17 @@ fn test_cpx_and_cpy() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
-    cpu.step(bus); // CPX #$04
+    cpu.step(bus).unwrap(); // CPX #$04
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x0202);
-    assert_eq!(stat(&cpu.p), "nv-bdiZc");
-    cpu.step(bus); // CPY #$08
+    assert_eq!(stat(&cpu.p), "nv-bdiZC"); // X >= $04: no borrow
+    cpu.step(bus).unwrap(); // CPY #$08
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x0204);
-    assert_eq!(stat(&cpu.p), "Nv-bdizC");
+    assert_eq!(stat(&cpu.p), "Nv-bdizc"); // Y < $08: borrow
 }
 
 #[test]
@@ -545,27 +756,28 @@ fn test_dec() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
-    cpu.step(bus); // DEC 0x10
+    cpu.step(bus).unwrap(); // DEC 0x10
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x1002);
     assert_eq!(stat(&cpu.p), "nv-bdizc");
     assert_eq!(bus.read(0x0010), 99);
 
-    cpu.step(bus); // DEC 0x10,X where X=10
+    cpu.step(bus).unwrap(); // DEC 0x10,X where X=10
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x1004);
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
     assert_eq!(bus.read(0x0020), 199);
 
-    cpu.step(bus); // DEC 0x2000
+    cpu.step(bus).unwrap(); // DEC 0x2000
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x1007);
     assert_eq!(stat(&cpu.p), "nv-bdiZc");
     assert_eq!(bus.read(0x2000), 0);
 
-    cpu.step(bus); // DEC 0x2000,X where X=10
+    cpu.step(bus).unwrap(); // DEC 0x2000,X where X=10
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x100A);
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
@@ -589,27 +801,28 @@ fn test_dex_and_dey() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
-    cpu.step(bus); // DEX
+    cpu.step(bus).unwrap(); // DEX
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x2001);
     assert_eq!(cpu.x, 0x00);
     assert_eq!(stat(&cpu.p), "nv-bdiZc");
 
-    cpu.step(bus); // DEX
+    cpu.step(bus).unwrap(); // DEX
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x2002);
     assert_eq!(cpu.x, 0xFF);
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
 
-    cpu.step(bus); // DEY
+    cpu.step(bus).unwrap(); // DEY
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x2003);
     assert_eq!(cpu.y, 0x00);
     assert_eq!(stat(&cpu.p), "nv-bdiZc");
 
-    cpu.step(bus); // DEY
+    cpu.step(bus).unwrap(); // DEY
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x2004);
     assert_eq!(cpu.y, 0xFF);
@@ -669,44 +882,45 @@ fn test_eor() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
-    cpu.step(bus); // EOR immediate
+    cpu.step(bus).unwrap(); // EOR immediate
     println!("{cpu:?}");
     assert_eq_hex!(cpu.a, 0b00000001); // [1a]
     assert_eq!(stat(&cpu.p), "nv-bdizc");
 
-    cpu.step(bus); // EOR zeropage
+    cpu.step(bus).unwrap(); // EOR zeropage
     println!("{cpu:?}");
     assert_eq_hex!(cpu.a, 0b11111110); // [2a]
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
 
-    cpu.step(bus); // EOR zeropage,X
+    cpu.step(bus).unwrap(); // EOR zeropage,X
     println!("{cpu:?}");
     assert_eq_hex!(cpu.a, 0b01010100); // [3a]
     assert_eq!(stat(&cpu.p), "nv-bdizc");
 
-    cpu.step(bus); // EOR absolute
+    cpu.step(bus).unwrap(); // EOR absolute
     println!("{cpu:?}");
     assert_eq_hex!(cpu.a, 0b00000000); // [4a]
     assert_eq!(stat(&cpu.p), "nv-bdiZc");
 
-    cpu.step(bus); // EOR absolute,X
+    cpu.step(bus).unwrap(); // EOR absolute,X
     println!("{cpu:?}");
     assert_eq_hex!(cpu.a, 0b11110000); // [5a]
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
 
-    cpu.step(bus); // EOR absolute,Y
+    cpu.step(bus).unwrap(); // EOR absolute,Y
     println!("{cpu:?}");
     assert_eq_hex!(cpu.a, 0b11111111); // [6a]
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
 
-    cpu.step(bus); // EOR (indirect,X)
+    cpu.step(bus).unwrap(); // EOR (indirect,X)
     println!("{cpu:?}");
     assert_eq_hex!(cpu.a, 0b11000011); // [7a]
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
 
-    cpu.step(bus); // EOR (indirect),Y
+    cpu.step(bus).unwrap(); // EOR (indirect),Y
     println!("{cpu:?}");
     assert_eq_hex!(cpu.a, 0b00000000); // [8a]
     assert_eq!(stat(&cpu.p), "nv-bdiZc");
@@ -731,29 +945,56 @@ fn test_inc() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
-    cpu.step(bus); // INC zeropage
+    cpu.step(bus).unwrap(); // INC zeropage
     println!("{cpu:?}");
     assert_eq_hex!(bus.read(0x0040), 0x01);
     assert_eq!(stat(&cpu.p), "nv-bdizc");
 
-    cpu.step(bus); // INC zeropage,X
+    cpu.step(bus).unwrap(); // INC zeropage,X
     println!("{cpu:?}");
     assert_eq_hex!(bus.read(0x0050), 0x00);
     assert_eq!(stat(&cpu.p), "nv-bdiZc");
 
-    cpu.step(bus); // INC absolute
+    cpu.step(bus).unwrap(); // INC absolute
     println!("{cpu:?}");
     assert_eq_hex!(bus.read(0x8000), 0x80);
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
 
-    cpu.step(bus); // INC absolute,X
+    cpu.step(bus).unwrap(); // INC absolute,X
     println!("{cpu:?}");
     assert_eq_hex!(bus.read(0x8010), 0x81);
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
 }
 
+#[test]
+fn test_inc_and_dec_accumulator() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    bus.load(
+        cpu.pc,
+        asm.inc(Operand::A)
+            .inc(Operand::A)
+            .dec(Operand::A)
+            .dec(Operand::A)
+            .print_listing()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+
+    cpu.a = 0x7F;
+    step_and_assert!(cpu, bus, a, 0x80, "Nv-bdizc"); // INC A
+    cpu.a = 0xFF;
+    step_and_assert!(cpu, bus, a, 0x00, "nv-bdiZc"); // INC A
+    step_and_assert!(cpu, bus, a, 0xFF, "Nv-bdizc"); // DEC A
+    cpu.a = 0x01;
+    step_and_assert!(cpu, bus, a, 0x00, "nv-bdiZc"); // DEC A
+}
+
 #[test]
 fn test_inx_and_iny() {
     let bus = &mut Bus::new();
@@ -770,21 +1011,22 @@ fn test_inx_and_iny() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
-    cpu.step(bus); // INX
+    cpu.step(bus).unwrap(); // INX
     assert_eq!(cpu.x, 0xFF);
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
 
-    cpu.step(bus); // INX
+    cpu.step(bus).unwrap(); // INX
     assert_eq!(cpu.x, 0x00);
     assert_eq!(stat(&cpu.p), "nv-bdiZc");
 
-    cpu.step(bus); // INY
+    cpu.step(bus).unwrap(); // INY
     assert_eq!(cpu.y, 0xFF);
     assert_eq!(stat(&cpu.p), "Nv-bdizc");
 
-    cpu.step(bus); // INY
+    cpu.step(bus).unwrap(); // INY
     assert_eq!(cpu.y, 0x00);
     assert_eq!(stat(&cpu.p), "nv-bdiZc");
 }
@@ -808,20 +1050,43 @@ fn test_jmp() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
-    cpu.step(bus); // JMP testlabel
+    cpu.step(bus).unwrap(); // JMP testlabel
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x8004);
     assert_eq!(stat(&cpu.p), "nv-BdIzc"); // unchanged
 
     cpu.p = !cpu.p;
-    cpu.step(bus); // JMP ($FFFC)
+    cpu.step(bus).unwrap(); // JMP ($FFFC)
     println!("{:?}", cpu);
     assert_eq_hex16!(cpu.pc, 0x8000);
     assert_eq!(stat(&cpu.p), "NV-bDiZC"); // unchanged
 }
 
+#[test]
+fn test_jmp_absolute_indexed_indirect() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x4000;
+    cpu.x = 0x04;
+    bus.load(
+        cpu.pc,
+        asm.org(cpu.pc)
+            .jmp(Operand::AbsXInd(label("table")))
+            .label("table")
+            .data(vec![0x00, 0x10, 0x00, 0x20, 0x00, 0x30])
+            .print_listing()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+
+    step_and_assert!(cpu, bus, pc, 0x3000, "nv-bdizc"); // JMP (table,X)
+}
+
 #[test]
 fn test_jsr_and_rts() {
     let bus = &mut Bus::new();
@@ -842,26 +1107,27 @@ fn test_jsr_and_rts() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     println!("{cpu:?}");
-    cpu.step(bus); // JP first
+    cpu.step(bus).unwrap(); // JP first
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x4017);
     assert_eq_hex!(cpu.s, 0xFD);
     assert_eq_hex!(bus.read(0x01FF), 0x40); // HH
-    assert_eq_hex!(bus.read(0x01FE), 0x03); // LL
-    cpu.step(bus); // JP second
+    assert_eq_hex!(bus.read(0x01FE), 0x02); // LL
+    cpu.step(bus).unwrap(); // JP second
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x401B);
     assert_eq_hex!(cpu.s, 0xFB);
     assert_eq_hex!(bus.read(0x01FD), 0x40); // HH
-    assert_eq_hex!(bus.read(0x01FC), 0x1A); // LL (TODO: wrong?)
-    cpu.step(bus); // RTS (from second)
+    assert_eq_hex!(bus.read(0x01FC), 0x19); // LL
+    cpu.step(bus).unwrap(); // RTS (from second)
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x401A);
     assert_eq_hex!(cpu.s, 0xFD);
-    cpu.step(bus); // RTS (from first)
+    cpu.step(bus).unwrap(); // RTS (from first)
     println!("{cpu:?}");
     assert_eq_hex16!(cpu.pc, 0x4003);
     assert_eq_hex!(cpu.s, 0xFF);
@@ -889,7 +1155,8 @@ fn test_lda() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     cpu.x = 0x04;
     cpu.y = 0x05;
@@ -936,7 +1203,8 @@ fn test_ldx() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     step_and_assert!(cpu, bus, x, 0xAA, "Nv-bdizc"); // LDX #$AA
     step_and_assert!(cpu, bus, x, 0x00, "nv-bdiZc"); // LDX #$00
@@ -965,7 +1233,8 @@ fn test_ldy() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     cpu.x = 0x04;
     bus.write(0x00B0, 0x22);
@@ -997,7 +1266,8 @@ fn test_lsr() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     cpu.a = 0b11110001;
     cpu.x = 0xAA;
@@ -1035,7 +1305,8 @@ fn test_ora() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     cpu.a = 0b00000000;
     cpu.x = 0x02;
@@ -1068,7 +1339,8 @@ fn test_pha_pla() {
     let bus = &mut Bus::new();
     let mut cpu = Cpu::new();
     let mut asm = Assembler::new();
-    bus.load(0, asm.pha().pla().print_listing().assemble().unwrap());
+    bus.load(0, asm.pha().pla().print_listing().assemble().unwrap())
+        .unwrap();
 
     cpu.s = 0xA8;
     cpu.a = 0xF0;
@@ -1087,7 +1359,8 @@ fn test_php_plp() {
     let bus = &mut Bus::new();
     let mut cpu = Cpu::new();
     let mut asm = Assembler::new();
-    bus.load(0, asm.php().plp().print_listing().assemble().unwrap());
+    bus.load(0, asm.php().plp().print_listing().assemble().unwrap())
+        .unwrap();
 
     cpu.p = 0b00000100;
     cpu.s = 0xA8;
@@ -1101,6 +1374,131 @@ fn test_php_plp() {
     step_and_assert!(cpu, bus, s, 0xA8, "nv-bdIzc"); // PLP
 }
 
+#[test]
+fn test_reserved_nops() {
+    use pda6502v2emu::cpu::CpuError;
+
+    // (opcodes, length in bytes, cycles) for each group of reserved W65C02S opcodes
+    let groups: [(Vec<u8>, u16, u8); 7] = [
+        ((0..16).map(|hi| hi << 4 | 0x03).collect(), 1, 1),
+        (
+            (0..16)
+                .filter(|hi| *hi != 0xC && *hi != 0xD) // WAI, STP
+                .map(|hi| hi << 4 | 0x0B)
+                .collect(),
+            1,
+            1,
+        ),
+        (vec![0x02, 0x22, 0x42, 0x62, 0x82, 0xC2, 0xE2], 2, 2),
+        (vec![0x44], 2, 3),
+        (vec![0x54, 0xD4, 0xF4], 2, 4),
+        (vec![0xDC, 0xFC], 3, 4),
+        (vec![0x5C], 3, 8),
+    ];
+
+    for (codes, length, cycles) in groups {
+        for code in codes {
+            let bus = &mut Bus::new();
+            let mut cpu = Cpu::new();
+            cpu.pc = 0x4000;
+            bus.load(cpu.pc, vec![code, 0x12, 0x34]).unwrap();
+            let p = cpu.p;
+
+            assert_eq!(cpu.step(bus).unwrap().cycles, cycles, "${code:02X}");
+            assert_eq_hex16!(cpu.pc, 0x4000 + length);
+            assert_eq_hex!(cpu.p, p);
+
+            // strict mode reports the reserved opcode instead of executing it
+            cpu.strict = true;
+            cpu.pc = 0x4000;
+            assert_eq!(
+                cpu.step(bus),
+                Err(CpuError::UndefinedOpcode { pc: 0x4000, code })
+            );
+            assert_eq_hex16!(cpu.pc, 0x4000);
+        }
+    }
+}
+
+#[test]
+fn test_step_info() {
+    use pda6502v2emu::cpu::{CpuError, StepInfo};
+    use pda6502v2emu::isa::{AddressMode, Mnemonic};
+
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    cpu.pc = 0x4000;
+    cpu.x = 0x10;
+    bus.load(
+        cpu.pc,
+        Assembler::new()
+            .org(cpu.pc)
+            .lda(Operand::AbsX(val(0x20F0)))
+            .wai()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let info = cpu.step(bus).unwrap(); // LDA $20F0,X
+    assert_eq_hex16!(info.pc, 0x4000);
+    let opcode = info.opcode.unwrap();
+    assert_eq!(opcode.mnemonic, Mnemonic::Lda);
+    assert_eq!(opcode.mode, AddressMode::AbsoluteX);
+    assert_eq!(info.cycles, 5); // page crossed
+    cpu.step(bus).unwrap(); // WAI
+    assert_eq!(
+        cpu.step(bus),
+        Ok(StepInfo {
+            pc: 0x4004,
+            opcode: None,
+            cycles: 1
+        })
+    );
+
+    let err = CpuError::IllegalAddressMode {
+        pc: 0x1234,
+        code: 0xA9,
+        mnemonic: Mnemonic::Lda,
+        mode: AddressMode::Implied,
+    };
+    assert_eq_hex16!(err.pc(), 0x1234);
+    assert_eq_hex!(err.code(), 0xA9);
+    assert_eq!(
+        err.to_string(),
+        "illegal address mode Implied for Lda (opcode $A9) at $1234"
+    );
+}
+
+#[test]
+#[should_panic(expected = "BBS8: bit number must be 0-7")]
+fn test_bit_number_out_of_range() {
+    Assembler::new().bbs(8, Operand::ZRel(0x10, branch("start")));
+}
+
+#[test]
+fn test_rmb_smb() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x4000;
+    for bit in 0..8 {
+        asm.smb(bit, Operand::Z(0x10)).rmb(bit, Operand::Z(0x20));
+    }
+    bus.load(cpu.pc, asm.print_listing().assemble().unwrap())
+        .unwrap();
+    bus.write(0x0020, 0xFF);
+
+    let mut expect_set = 0x00;
+    let mut expect_reset = 0xFF;
+    for bit in 0..8 {
+        expect_set |= 1 << bit;
+        expect_reset &= !(1 << bit);
+        step_and_assert_mem!(cpu, bus, 0x0010, expect_set, "nv-bdizc"); // SMBn $10
+        step_and_assert_mem!(cpu, bus, 0x0020, expect_reset, "nv-bdizc"); // RMBn $20
+    }
+}
+
 #[test]
 fn test_rol_ror() {
     let bus = &mut Bus::new();
@@ -1123,7 +1521,8 @@ fn test_rol_ror() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     cpu.a = 0b10000000;
     cpu.x = 0x42;
@@ -1167,7 +1566,8 @@ fn test_sbc() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     cpu.a = 0x00;
     cpu.x = 0x02;
@@ -1200,13 +1600,232 @@ fn test_sbc() {
     step_and_assert!(cpu, bus, a, 0x00, "nv-bdiZC"); // SBC ($20),Y  ; 0x3F - 0x3F     = 0x00
 }
 
+#[test]
+fn test_interrupt_entry() {
+    use pda6502v2emu::cpu::StatusMask;
+
+    // BRK, IRQ and NMI push the same frame, differing only in B and the return address.
+    for (path, vector, ret, pushed) in [
+        ("BRK", 0x8000, 0x4002, 0b11111011), // B set; return address skips the break mark
+        ("IRQ", 0x8000, 0x4000, 0b11101011), // B clear
+        ("NMI", 0x9000, 0x4000, 0b11101011), // B clear
+    ] {
+        let bus = &mut Bus::new();
+        let mut cpu = Cpu::new();
+        bus.write(0xFFFA, 0x00); // VEC_NMI LL
+        bus.write(0xFFFB, 0x90); // VEC_NMI HH
+        bus.write(0xFFFE, 0x00); // VEC_IRQ LL
+        bus.write(0xFFFF, 0x80); // VEC_IRQ HH
+        bus.load(0x4000, Assembler::new().brk().assemble().unwrap())
+            .unwrap();
+        cpu.pc = 0x4000;
+        cpu.s = 0xFF;
+        cpu.p = 0b11001011; // N V D Z C set; I and B clear
+
+        let cycles = match path {
+            "BRK" => cpu.step(bus).unwrap().cycles,
+            "IRQ" => cpu.interrupt(bus),
+            _ => {
+                cpu.set_nmi(true);
+                cpu.nmi(bus)
+            }
+        };
+        println!("{path}: {:?}", cpu);
+
+        assert_eq!(cycles, 7, "{path}");
+        assert_eq_hex16!(cpu.pc, vector);
+        assert_eq_hex!(cpu.s, 0xFC);
+        assert_eq_hex!(bus.read(0x01FF), (ret >> 8) as u8); // return address HH
+        assert_eq_hex!(bus.read(0x01FE), ret as u8); // return address LL
+        assert_eq_hex!(bus.read(0x01FD), pushed); // status
+        assert!(cpu.get_p_bit(StatusMask::Interrupt), "{path} sets I");
+        assert!(!cpu.get_p_bit(StatusMask::Decimal), "{path} clears D");
+        assert_eq!(stat(&cpu.p), "NV-bdIZC", "{path}");
+    }
+}
+
+#[test]
+fn test_interrupt_latency() {
+    use pda6502v2emu::cpu::StatusMask;
+
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    bus.write(0xFFFE, 0x00); // VEC_IRQ LL
+    bus.write(0xFFFF, 0x80); // VEC_IRQ HH
+    bus.write(0x01A0, 0b00000000); // status for PLP to pull, I clear
+    bus.load(
+        0x4000,
+        asm.org(0x4000)
+            .cli()
+            .nop()
+            .sei()
+            .nop()
+            .plp()
+            .nop()
+            .print_listing()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+    bus.load(0x8000, Assembler::new().rti().assemble().unwrap())
+        .unwrap();
+    cpu.pc = 0x4000;
+    cpu.s = 0xFF;
+    cpu.set_p_bit(StatusMask::Interrupt, true);
+
+    // IRQ is still masked for the instruction following CLI...
+    cpu.step(bus).unwrap(); // CLI
+    assert_eq!(cpu.interrupt(bus), 0);
+    cpu.step(bus).unwrap(); // NOP
+    assert_eq!(cpu.interrupt(bus), 7);
+    assert_eq_hex16!(cpu.pc, 0x8000);
+
+    // RTI restores I immediately, so the still-asserted IRQ is taken straight away.
+    cpu.step(bus).unwrap(); // RTI
+    assert_eq_hex16!(cpu.pc, 0x4002);
+    assert_eq!(cpu.interrupt(bus), 7);
+    cpu.step(bus).unwrap(); // RTI
+
+    // ...and can still be taken immediately after SEI.
+    cpu.step(bus).unwrap(); // SEI
+    assert_eq!(cpu.interrupt(bus), 7);
+    cpu.step(bus).unwrap(); // RTI, which pulls I set
+    cpu.step(bus).unwrap(); // NOP
+    assert_eq!(cpu.interrupt(bus), 0);
+
+    // PLP behaves like CLI.
+    cpu.s = 0x9F;
+    cpu.step(bus).unwrap(); // PLP
+    assert!(!cpu.get_p_bit(StatusMask::Interrupt));
+    assert_eq!(cpu.interrupt(bus), 0);
+    cpu.step(bus).unwrap(); // NOP
+    assert_eq!(cpu.interrupt(bus), 7);
+}
+
+#[test]
+fn test_nmi() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x4000;
+    cpu.s = 0xFF;
+    bus.write(0xFFFA, 0x00); // VEC_NMI LL
+    bus.write(0xFFFB, 0x90); // VEC_NMI HH
+    bus.write(0xFFFE, 0x00); // VEC_IRQ LL
+    bus.write(0xFFFF, 0x80); // VEC_IRQ HH
+    bus.load(cpu.pc, asm.wai().print_listing().assemble().unwrap())
+        .unwrap();
+
+    use pda6502v2emu::cpu::{RunState, StatusMask};
+
+    cpu.set_p_bit(StatusMask::Interrupt, true);
+    cpu.step(bus).unwrap(); // WAI
+    assert_eq!(cpu.state, RunState::Waiting);
+
+    // Nothing to service until NMI is asserted.
+    assert_eq!(cpu.nmi(bus), 0);
+    assert_eq!(cpu.state, RunState::Waiting);
+
+    // NMI is serviced despite I being set, waking the CPU from WAI.
+    cpu.set_nmi(true);
+    assert_eq!(cpu.nmi(bus), 7);
+    assert_eq!(cpu.state, RunState::Running);
+    assert_eq_hex16!(cpu.pc, 0x9000);
+    assert_eq_hex!(cpu.s, 0xFC);
+    assert_eq_hex!(bus.read(0x01FF), 0x40); // return address HH
+    assert_eq_hex!(bus.read(0x01FE), 0x01); // return address LL
+
+    // NMI is edge-triggered; holding the line asserted doesn't interrupt again.
+    cpu.set_nmi(true);
+    assert_eq!(cpu.nmi(bus), 0);
+
+    // Releasing and re-asserting does, taking priority over IRQ.
+    cpu.set_nmi(false);
+    cpu.set_nmi(true);
+    cpu.set_p_bit(StatusMask::Interrupt, false);
+    assert_eq!(cpu.nmi(bus), 7);
+    assert_eq!(cpu.interrupt(bus), 0); // IRQ is now masked by the NMI entry setting I
+    assert_eq_hex16!(cpu.pc, 0x9000);
+}
+
+#[test]
+fn test_rdy() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x4000;
+    cpu.s = 0xFF;
+    bus.write(0xFFFA, 0x00); // VEC_NMI LL
+    bus.write(0xFFFB, 0x90); // VEC_NMI HH
+    bus.write(0xFFFE, 0x00); // VEC_IRQ LL
+    bus.write(0xFFFF, 0x80); // VEC_IRQ HH
+    bus.load(cpu.pc, asm.nop().print_listing().assemble().unwrap())
+        .unwrap();
+
+    use pda6502v2emu::cpu::StatusMask;
+
+    // Pulled low, RDY stalls the CPU a cycle at a time, without executing anything.
+    cpu.set_rdy(false);
+    let info = cpu.step(bus).unwrap();
+    assert_eq!(info.cycles, 1);
+    assert!(info.opcode.is_none());
+    assert_eq_hex16!(cpu.pc, 0x4000);
+
+    // Interrupts wait for RDY too.
+    cpu.set_p_bit(StatusMask::Interrupt, false);
+    cpu.set_nmi(true);
+    assert_eq!(cpu.nmi(bus), 0);
+    assert_eq!(cpu.interrupt(bus), 0);
+    assert_eq_hex16!(cpu.pc, 0x4000);
+
+    // Released, the pending NMI is serviced, then execution continues.
+    cpu.set_rdy(true);
+    assert_eq!(cpu.nmi(bus), 7);
+    assert_eq_hex16!(cpu.pc, 0x9000);
+}
+
+#[test]
+fn test_so() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x4000;
+    bus.load(
+        cpu.pc,
+        asm.clv()
+            .bvc(Operand::Rel(branch("clear")))
+            .stp()
+            .label("clear")
+            .clv()
+            .print_listing()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+
+    // Asserting SOB sets V, as a branch can see.
+    cpu.set_so(true);
+    assert_eq!(stat(&cpu.p), "nV-bdizc");
+    step_and_assert!(cpu, bus, pc, 0x4001, "nv-bdizc"); // CLV
+    step_and_assert!(cpu, bus, pc, 0x4004, "nv-bdizc"); // BVC
+
+    // Only its falling edge does; holding it asserted doesn't set V again.
+    cpu.set_so(true);
+    assert_eq!(stat(&cpu.p), "nv-bdizc");
+    cpu.set_so(false);
+    cpu.set_so(true);
+    assert_eq!(stat(&cpu.p), "nV-bdizc");
+    step_and_assert!(cpu, bus, pc, 0x4005, "nv-bdizc"); // CLV
+}
+
 #[test]
 fn test_nop() {
     let bus = &mut Bus::new();
     let mut cpu = Cpu::new();
     let mut asm = Assembler::new();
-    bus.load(cpu.pc, asm.nop().assemble().unwrap());
-    cpu.step(bus);
+    bus.load(cpu.pc, asm.nop().assemble().unwrap()).unwrap();
+    cpu.step(bus).unwrap();
     assert_eq_hex16!(cpu.pc, 0x0001);
 }
 
@@ -1227,32 +1846,33 @@ fn test_set_and_clear_flags() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     use pda6502v2emu::cpu::StatusMask;
     cpu.set_p_bit(StatusMask::Overflow, true);
 
     assert_eq!(stat(&cpu.p), "nV-bdizc");
 
-    cpu.step(bus); // SEC
+    cpu.step(bus).unwrap(); // SEC
     println!("{cpu:?}");
     assert_eq!(stat(&cpu.p), "nV-bdizC");
-    cpu.step(bus); // SED
+    cpu.step(bus).unwrap(); // SED
     println!("{cpu:?}");
     assert_eq!(stat(&cpu.p), "nV-bDizC");
-    cpu.step(bus); // SEI
+    cpu.step(bus).unwrap(); // SEI
     println!("{cpu:?}");
     assert_eq!(stat(&cpu.p), "nV-bDIzC");
-    cpu.step(bus); // CLC
+    cpu.step(bus).unwrap(); // CLC
     println!("{cpu:?}");
     assert_eq!(stat(&cpu.p), "nV-bDIzc");
-    cpu.step(bus); // CLD
+    cpu.step(bus).unwrap(); // CLD
     println!("{cpu:?}");
     assert_eq!(stat(&cpu.p), "nV-bdIzc");
-    cpu.step(bus); // CLI
+    cpu.step(bus).unwrap(); // CLI
     println!("{cpu:?}");
     assert_eq!(stat(&cpu.p), "nV-bdizc");
-    cpu.step(bus); // CLV
+    cpu.step(bus).unwrap(); // CLV
     println!("{cpu:?}");
     assert_eq!(stat(&cpu.p), "nv-bdizc");
 }
@@ -1281,7 +1901,8 @@ fn test_sta_stx_sty() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     cpu.a = 0xAA;
     cpu.x = 0x02;
@@ -1312,6 +1933,34 @@ fn test_sta_stx_sty() {
     step_and_assert_mem!(cpu, bus, 0x5000, 0x04, "nv-bdizc"); // STY $5000
 }
 
+#[test]
+fn test_stz() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x4000;
+    cpu.x = 0x02;
+    for addr in [0x0010, 0x0012, 0x2000, 0x2002] {
+        bus.write(addr, 0xFF);
+    }
+    bus.load(
+        cpu.pc,
+        asm.stz(Operand::Z(0x10))
+            .stz(Operand::ZX(0x10))
+            .stz(Operand::Abs(val(0x2000)))
+            .stz(Operand::AbsX(val(0x2000)))
+            .print_listing()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+
+    step_and_assert_mem!(cpu, bus, 0x0010, 0x00, "nv-bdizc"); // STZ $10
+    step_and_assert_mem!(cpu, bus, 0x0012, 0x00, "nv-bdizc"); // STZ $10,X
+    step_and_assert_mem!(cpu, bus, 0x2000, 0x00, "nv-bdizc"); // STZ $2000
+    step_and_assert_mem!(cpu, bus, 0x2002, 0x00, "nv-bdizc"); // STZ $2000,X
+}
+
 #[test]
 fn test_tax_tay_tsx_txa_txs_tya() {
     let bus = &mut Bus::new();
@@ -1329,7 +1978,8 @@ fn test_tax_tay_tsx_txa_txs_tya() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     cpu.a = 0x00;
     cpu.x = 0x7F;
@@ -1347,6 +1997,197 @@ fn test_tax_tay_tsx_txa_txs_tya() {
     step_and_assert!(cpu, bus, a, 0xBF, "Nv-bdizc"); // TYA
 }
 
+#[test]
+fn test_trb_tsb() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x4000;
+    bus.write(0x0010, 0b11110000);
+    bus.write(0xDE10, 0b11111111); // e.g. ShellSPI's SPI chip select
+    bus.load(
+        cpu.pc,
+        asm.trb(Operand::Z(0x10))
+            .tsb(Operand::Z(0x10))
+            .trb(Operand::Abs(val(0xDE10)))
+            .tsb(Operand::Abs(val(0xDE10)))
+            .print_listing()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+
+    cpu.a = 0b00001111;
+    step_and_assert_mem!(cpu, bus, 0x0010, 0b11110000, "nv-bdiZc"); // TRB $10
+    step_and_assert_mem!(cpu, bus, 0x0010, 0b11111111, "nv-bdiZc"); // TSB $10
+    cpu.a = 0b00000001;
+    step_and_assert_mem!(cpu, bus, 0xDE10, 0b11111110, "nv-bdizc"); // TRB $DE10
+    step_and_assert_mem!(cpu, bus, 0xDE10, 0b11111111, "nv-bdiZc"); // TSB $DE10
+}
+
+#[test]
+fn test_zeropage_indirect() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x4000;
+    bus.write(0x0020, 0x34); // LL
+    bus.write(0x0021, 0x12); // HH
+    bus.write(0x00FF, 0x78); // LL
+    bus.write(0x0000, 0x56); // HH, wrapped within zero page
+    bus.write(0x1234, 0x0F);
+    bus.load(
+        cpu.pc,
+        asm.lda(Operand::ZInd(0x20))
+            .ora(Operand::ZInd(0x20))
+            .and(Operand::ZInd(0x20))
+            .eor(Operand::ZInd(0x20))
+            .cmp(Operand::ZInd(0x20))
+            .adc(Operand::ZInd(0x20))
+            .sbc(Operand::ZInd(0x20))
+            .sta(Operand::ZInd(0xFF))
+            .print_listing()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+
+    step_and_assert!(cpu, bus, a, 0x0F, "nv-bdizc"); // LDA ($20)
+    step_and_assert!(cpu, bus, a, 0x0F, "nv-bdizc"); // ORA ($20)
+    step_and_assert!(cpu, bus, a, 0x0F, "nv-bdizc"); // AND ($20)
+    step_and_assert!(cpu, bus, a, 0x00, "nv-bdiZc"); // EOR ($20)
+    step_and_assert!(cpu, bus, a, 0x00, "Nv-bdizc"); // CMP ($20)
+    step_and_assert!(cpu, bus, a, 0x0F, "nv-bdizc"); // ADC ($20)
+    cpu.set_p_bit(pda6502v2emu::cpu::StatusMask::Carry, true);
+    step_and_assert!(cpu, bus, a, 0x00, "nv-bdiZC"); // SBC ($20)
+    step_and_assert_mem!(cpu, bus, 0x5678, 0x00, "nv-bdiZC"); // STA ($FF)
+}
+
+#[test]
+fn test_wai() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x4000;
+    cpu.s = 0xFF;
+    bus.write(0xFFFE, 0x00); // VEC_IRQ LL
+    bus.write(0xFFFF, 0x80); // VEC_IRQ HH
+    bus.load(
+        cpu.pc,
+        asm.wai().nop().wai().print_listing().assemble().unwrap(),
+    )
+    .unwrap();
+
+    use pda6502v2emu::cpu::{RunState, StatusMask};
+
+    cpu.set_p_bit(StatusMask::Interrupt, true);
+    cpu.step(bus).unwrap(); // WAI
+    assert_eq!(cpu.state, RunState::Waiting);
+    assert_eq_hex16!(cpu.pc, 0x4001);
+    cpu.step(bus).unwrap(); // still waiting
+    assert_eq_hex16!(cpu.pc, 0x4001);
+
+    // IRQ wakes the CPU despite I being set, continuing after WAI without servicing it.
+    cpu.interrupt(bus);
+    assert_eq!(cpu.state, RunState::Running);
+    assert_eq_hex16!(cpu.pc, 0x4001);
+    assert_eq_hex!(cpu.s, 0xFF);
+    step_and_assert!(cpu, bus, pc, 0x4002, "nv-bdIzc"); // NOP
+
+    // With I clear, the IRQ is serviced as usual.
+    cpu.set_p_bit(StatusMask::Interrupt, false);
+    cpu.step(bus).unwrap(); // WAI
+    assert_eq!(cpu.state, RunState::Waiting);
+    cpu.interrupt(bus);
+    assert_eq!(cpu.state, RunState::Running);
+    assert_eq_hex16!(cpu.pc, 0x8000);
+}
+
+#[test]
+fn test_stp() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    bus.write(0xFFFC, 0x00); // VEC_RES LL
+    bus.write(0xFFFD, 0x40); // VEC_RES HH
+    cpu.reset(bus);
+    bus.load(cpu.pc, asm.stp().print_listing().assemble().unwrap())
+        .unwrap();
+
+    use pda6502v2emu::cpu::RunState;
+
+    cpu.step(bus).unwrap(); // STP
+    assert_eq!(cpu.state, RunState::Stopped);
+    assert_eq_hex16!(cpu.pc, 0x4001);
+
+    // Neither stepping nor interrupts resume a stopped CPU.
+    cpu.step(bus).unwrap();
+    cpu.interrupt(bus);
+    assert_eq!(cpu.state, RunState::Stopped);
+    assert_eq_hex16!(cpu.pc, 0x4001);
+
+    // Reset does.
+    cpu.reset(bus);
+    assert_eq!(cpu.state, RunState::Running);
+    assert_eq_hex16!(cpu.pc, 0x4000);
+}
+
+#[test]
+fn test_cycles() {
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    let mut asm = Assembler::new();
+    cpu.pc = 0x40E5; // puts BEQ next_page on a page boundary
+    bus.load(
+        cpu.pc,
+        asm.org(cpu.pc)
+            .lda(Operand::Imm(0x00)) //         2
+            .lda(Operand::AbsX(val(0x2000))) // 4: same page
+            .lda(Operand::AbsX(val(0x20FF))) // 5: page crossed
+            .sta(Operand::AbsX(val(0x20FF))) // 5: stores always take the extra cycle
+            .asl(Operand::AbsX(val(0x2000))) // 6: 65C02 shifts are one cycle shorter than NMOS
+            .asl(Operand::AbsX(val(0x20FF))) // 7: ...unless the page is crossed
+            .inc(Operand::AbsX(val(0x2010))) // 7: INC/DEC abs,X always take 7
+            .beq(Operand::Rel(branch("taken"))) // 3: taken, same page
+            .label("taken")
+            .bne(Operand::Rel(branch("taken"))) // 2: not taken
+            .beq(Operand::Rel(branch("next_page"))) // 4: taken across a page
+            .nop()
+            .label("next_page")
+            .sed() //                           2
+            .adc(Operand::Imm(0x01)) //         3: decimal mode takes an extra cycle
+            .bbr(0, Operand::ZRel(0x10, branch("bbr"))) // 6: taken, same page
+            .label("bbr")
+            .jmp(Operand::Ind(val(0x3000))) //  6
+            .print_listing()
+            .assemble()
+            .unwrap(),
+    )
+    .unwrap();
+    cpu.x = 0x01;
+    bus.write(0x2011, 0xFF); // INC sets Z for the BEQs
+
+    for (cycles, instruction) in [
+        (2, "LDA #$00"),
+        (4, "LDA $2000,X"),
+        (5, "LDA $20FF,X"),
+        (5, "STA $20FF,X"),
+        (6, "ASL $2000,X"),
+        (7, "ASL $20FF,X"),
+        (7, "INC $2010,X"),
+        (3, "BEQ taken"),
+        (2, "BNE taken"),
+        (4, "BEQ next_page"),
+        (2, "SED"),
+        (3, "ADC #$01"),
+        (6, "BBR0 $10,bbr"),
+        (6, "JMP ($3000)"),
+    ] {
+        assert_eq!(cpu.step(bus).unwrap().cycles, cycles, "{instruction}");
+        println!("{:?}", cpu);
+    }
+}
+
 #[test]
 fn test_address_modes_at_page_boundaries() {
     let bus = &mut Bus::new();
@@ -1368,7 +2209,8 @@ fn test_address_modes_at_page_boundaries() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
 
     cpu.x = 0x02;
     cpu.y = 0x04;
@@ -1394,7 +2236,8 @@ fn test_address_modes_at_page_boundaries() {
             .print_listing()
             .assemble()
             .unwrap(),
-    );
+    )
+    .unwrap();
     step_and_assert!(cpu, bus, pc, 0x3101, "nv-bdizc"); // JMP ($30FF)
     step_and_assert!(cpu, bus, pc, 0x110A, "nv-bdizc"); // JMP $110A (back to where we were)
 
@@ -1421,3 +2264,294 @@ fn test_address_modes_at_page_boundaries() {
     bus.write(0x0003, 0x88);
     step_and_assert!(cpu, bus, x, 0x88, "Nv-bdizc"); // LDX $FF,Y
 }
+
+#[test]
+fn test_nmos_jmp_indirect_page_wrap() {
+    use pda6502v2emu::isa::Variant;
+
+    for (variant, target, cycles) in [
+        (Variant::Nmos6502, 0x1234, 5),
+        (Variant::Wdc65C02S, 0x5634, 6),
+    ] {
+        let bus = &mut Bus::new();
+        let mut cpu = Cpu::with_variant(variant);
+        cpu.pc = 0x4000;
+        bus.load(cpu.pc, vec![0x6C, 0xFF, 0x20]).unwrap(); // JMP ($20FF)
+        bus.write(0x20FF, 0x34);
+        bus.write(0x2000, 0x12); // NMOS reads the high byte from the start of the page
+        bus.write(0x2100, 0x56);
+
+        assert_eq!(cpu.step(bus).unwrap().cycles, cycles, "{variant:?}");
+        assert_eq_hex16!(cpu.pc, target);
+    }
+}
+
+#[test]
+fn test_assemble_nmos_only_mnemonic() {
+    use pda6502v2emu::isa::{AddressMode, Mnemonic, OpcodeByMnemonicAndAddressMode};
+
+    // the assembler targets the W65C02S, which has no undocumented NMOS instructions
+    let opcodes = OpcodeByMnemonicAndAddressMode::build();
+    assert!(opcodes.get(Mnemonic::Alr, AddressMode::Immediate).is_err());
+    assert!(opcodes.get(Mnemonic::Lda, AddressMode::Immediate).is_ok());
+}
+
+#[test]
+fn test_rockwell_without_wai_stp() {
+    use pda6502v2emu::cpu::RunState;
+    use pda6502v2emu::isa::Variant;
+
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::with_variant(Variant::Rockwell65C02);
+    cpu.pc = 0x4000;
+    bus.write(0x0010, 0xFF);
+    bus.load(cpu.pc, vec![0xCB, 0xDB, 0x17, 0x10]).unwrap(); // WAI, STP, RMB1 $10
+
+    assert_eq!(cpu.step(bus).unwrap().cycles, 1); // WAI is a reserved NOP
+    assert_eq!(cpu.state, RunState::Running);
+    assert_eq!(cpu.step(bus).unwrap().cycles, 1); // STP is a reserved NOP
+    assert_eq!(cpu.state, RunState::Running);
+    assert_eq_hex16!(cpu.pc, 0x4002);
+    cpu.step(bus).unwrap(); // RMB1 $10
+    assert_eq_hex!(bus.read(0x0010), 0xFD);
+}
+
+#[test]
+fn test_nmos_undocumented_opcodes() {
+    use pda6502v2emu::cpu::RunState;
+    use pda6502v2emu::isa::Variant;
+
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::with_variant(Variant::Nmos6502);
+    cpu.pc = 0x4000;
+    bus.write(0x0010, 0x81);
+    bus.write(0x0011, 0x40);
+    bus.load(
+        cpu.pc,
+        vec![
+            0xA7, 0x10, // LAX $10
+            0x87, 0x12, // SAX $12
+            0x07, 0x11, // SLO $11
+            0xC7, 0x11, // DCP $11
+            0xE7, 0x10, // ISC $10
+            0x0B, 0x80, // ANC #$80
+            0xCB, 0x01, // SBX #$01
+            0x64, 0x10, // NOP $10 (STZ on the 65C02)
+            0x02, // JAM
+        ],
+    )
+    .unwrap();
+
+    step_and_assert!(cpu, bus, a, 0x81, "Nv-bdizc");
+    assert_eq_hex!(cpu.x, 0x81);
+    step_and_assert_mem!(cpu, bus, 0x0012, 0x81, "Nv-bdizc");
+    step_and_assert_mem!(cpu, bus, 0x0011, 0x80, "Nv-bdizc");
+    assert_eq_hex!(cpu.a, 0x81);
+    step_and_assert_mem!(cpu, bus, 0x0011, 0x7F, "nv-bdizC"); // $81 >= $7F
+    step_and_assert_mem!(cpu, bus, 0x0010, 0x82, "Nv-bdizc"); // $81 - $82
+    assert_eq_hex!(cpu.a, 0xFF);
+    step_and_assert!(cpu, bus, a, 0x80, "Nv-bdizC");
+    step_and_assert!(cpu, bus, x, 0x7F, "nv-bdizC"); // ($80 & $81) - 1
+    assert_eq!(cpu.step(bus).unwrap().cycles, 3);
+    assert_eq_hex!(bus.read(0x0010), 0x82);
+    cpu.step(bus).unwrap(); // JAM
+    assert_eq!(cpu.state, RunState::Stopped);
+    assert_eq_hex16!(cpu.pc, 0x4011);
+}
+
+#[test]
+fn test_nmos_decimal_mode() {
+    use pda6502v2emu::isa::Variant;
+
+    // $99 + $01 = $00 carry: the NMOS 6502 takes Z from the binary sum ($9A) and N from the
+    // sum before the high digit was adjusted ($A0); the 65C02 takes a cycle to fix them.
+    for (variant, stat_after, cycles) in [
+        (Variant::Nmos6502, "Nv-BDIzC", 2),
+        (Variant::Wdc65C02S, "nv-BDIZC", 3),
+    ] {
+        let bus = &mut Bus::new();
+        let mut cpu = Cpu::with_variant(variant);
+        cpu.reset(bus);
+        cpu.pc = 0x4000;
+        cpu.s = 0xFF;
+        cpu.a = 0x99;
+        bus.load(cpu.pc, vec![0xF8, 0x69, 0x01, 0x00, 0x00])
+            .unwrap(); // SED, ADC #$01, BRK
+        bus.load(0xFFFE, vec![0x00, 0x80]).unwrap();
+
+        cpu.step(bus).unwrap(); // SED
+        assert_eq!(cpu.step(bus).unwrap().cycles, cycles, "{variant:?}");
+        assert_eq_hex!(cpu.a, 0x00);
+        assert_eq!(stat(&cpu.p), stat_after, "{variant:?}");
+
+        // only the 65C02 clears decimal mode on entering an interrupt handler
+        cpu.step(bus).unwrap(); // BRK
+        assert_eq_hex16!(cpu.pc, 0x8000);
+        assert_eq!(
+            cpu.get_p_bit(pda6502v2emu::cpu::StatusMask::Decimal),
+            variant == Variant::Nmos6502
+        );
+    }
+}
+
+#[test]
+fn test_cycle_accurate_access_count() {
+    use pda6502v2emu::dec::Decoder;
+    use pda6502v2emu::isa::Variant;
+
+    // In cycle-accurate mode every cycle is a bus access, with or without page crossings,
+    // taken branches and decimal mode.
+    for variant in [
+        Variant::Nmos6502,
+        Variant::Rockwell65C02,
+        Variant::Wdc65C02S,
+    ] {
+        let decoder = Decoder::new(variant);
+        for code in 0..=255 {
+            if decoder.opcode(code).is_none() {
+                continue;
+            }
+            for (index, p) in [(0x00, 0x00), (0xFF, 0xFF)] {
+                let bus = &mut Bus::new();
+                let mut cpu = Cpu::with_variant(variant);
+                cpu.cycle_accurate = true;
+                cpu.pc = 0x4000;
+                cpu.s = 0xF0;
+                cpu.x = index;
+                cpu.y = index;
+                cpu.p = p;
+                bus.load(cpu.pc, vec![code, 0x80, 0x20]).unwrap();
+                bus.load(0x0080, vec![0x80, 0x20]).unwrap();
+
+                bus.set_trace(true);
+                let cycles = cpu.step(bus).unwrap().cycles;
+                let trace = bus.take_trace();
+                assert_eq!(
+                    trace.len(),
+                    cycles as usize,
+                    "{variant:?} ${code:02X} X=Y=${index:02X} P=${p:02X}: {trace:?}"
+                );
+            }
+        }
+    }
+}
+
+fn read_access(addr: u16, data: u8) -> pda6502v2emu::bus::Access {
+    use pda6502v2emu::bus::{Access, AccessKind};
+    Access {
+        addr,
+        data,
+        kind: AccessKind::Read,
+    }
+}
+
+fn write_access(addr: u16, data: u8) -> pda6502v2emu::bus::Access {
+    use pda6502v2emu::bus::{Access, AccessKind};
+    Access {
+        addr,
+        data,
+        kind: AccessKind::Write,
+    }
+}
+
+#[test]
+fn test_cycle_accurate_indexed_read() {
+    use pda6502v2emu::isa::Variant;
+
+    // LDA $20F0,X across a page: the 65C02 re-reads the operand's high byte, the NMOS 6502
+    // reads the address before its high byte is fixed.
+    for (variant, dummy) in [
+        (Variant::Wdc65C02S, read_access(0x4002, 0x20)),
+        (Variant::Nmos6502, read_access(0x2000, 0x00)),
+    ] {
+        let bus = &mut Bus::new();
+        let mut cpu = Cpu::with_variant(variant);
+        cpu.cycle_accurate = true;
+        cpu.pc = 0x4000;
+        cpu.x = 0x10;
+        bus.load(cpu.pc, vec![0xBD, 0xF0, 0x20]).unwrap();
+        bus.write(0x2100, 0x42);
+        bus.set_trace(true);
+        cpu.step(bus).unwrap();
+        assert_eq!(
+            bus.take_trace(),
+            vec![
+                read_access(0x4000, 0xBD),
+                read_access(0x4001, 0xF0),
+                read_access(0x4002, 0x20),
+                dummy,
+                read_access(0x2100, 0x42),
+            ],
+            "{variant:?}"
+        );
+    }
+}
+
+#[test]
+fn test_cycle_accurate_read_modify_write() {
+    use pda6502v2emu::isa::Variant;
+
+    // INC $10: the 65C02 reads the operand twice, the NMOS 6502 writes it back unmodified.
+    // Without cycle-accurate mode, only the accesses the instruction needs are made.
+    for (variant, accurate, dummy) in [
+        (Variant::Wdc65C02S, true, Some(read_access(0x0010, 0x7F))),
+        (Variant::Nmos6502, true, Some(write_access(0x0010, 0x7F))),
+        (Variant::Wdc65C02S, false, None),
+    ] {
+        let bus = &mut Bus::new();
+        let mut cpu = Cpu::with_variant(variant);
+        cpu.cycle_accurate = accurate;
+        cpu.pc = 0x4000;
+        bus.load(cpu.pc, vec![0xE6, 0x10]).unwrap();
+        bus.write(0x0010, 0x7F);
+        bus.set_trace(true);
+        assert_eq!(cpu.step(bus).unwrap().cycles, 5);
+        let mut expected = vec![
+            read_access(0x4000, 0xE6),
+            read_access(0x4001, 0x10),
+            read_access(0x0010, 0x7F),
+        ];
+        expected.extend(dummy);
+        expected.push(write_access(0x0010, 0x80));
+        assert_eq!(bus.take_trace(), expected, "{variant:?} {accurate}");
+    }
+}
+
+#[test]
+fn test_cycle_accurate_jsr_rts() {
+    // JSR pushes the address of its last byte before reading it, and RTS reads the byte
+    // at that address before returning past it
+    let bus = &mut Bus::new();
+    let mut cpu = Cpu::new();
+    cpu.cycle_accurate = true;
+    cpu.pc = 0x4000;
+    cpu.s = 0xFF;
+    bus.load(cpu.pc, vec![0x20, 0x00, 0x50]).unwrap();
+    bus.write(0x5000, 0x60);
+    bus.set_trace(true);
+    assert_eq!(cpu.step(bus).unwrap().cycles, 6);
+    assert_eq!(
+        bus.take_trace(),
+        vec![
+            read_access(0x4000, 0x20),
+            read_access(0x4001, 0x00),
+            read_access(0x01FF, 0x00),
+            write_access(0x01FF, 0x40),
+            write_access(0x01FE, 0x02),
+            read_access(0x4002, 0x50),
+        ]
+    );
+    assert_eq!(cpu.step(bus).unwrap().cycles, 6);
+    assert_eq!(
+        bus.take_trace(),
+        vec![
+            read_access(0x5000, 0x60),
+            read_access(0x5001, 0x00),
+            read_access(0x01FD, 0x00),
+            read_access(0x01FE, 0x02),
+            read_access(0x01FF, 0x40),
+            read_access(0x4002, 0x50),
+        ]
+    );
+    assert_eq_hex16!(cpu.pc, 0x4003);
+}
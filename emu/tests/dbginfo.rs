@@ -28,10 +28,27 @@ type	id=1,val="801A20"
     let info = dbginfo::parse(sample)?;
 
     assert_eq!(info.label(0xF000).unwrap(), "BlinkenStart");
+
+    assert_eq!(info.segments.len(), 2);
+    assert_eq!(info.segments[0].output, None);
+    assert_eq!(
+        info.segments[1],
+        dbginfo::Segment {
+            name: "os".to_string(),
+            start: 0xF000,
+            size: 0x0758,
+            output: Some(("os.rom".to_string(), 0)),
+        }
+    );
     assert!(info.label(0x1234).is_none());
 
     assert_eq!(info.addr("SidTunes").unwrap(), 0xF5CE);
     assert!(info.addr("NopeNotHere").is_none());
 
+    assert_eq!(info.nearest_label(0xF000), Some(("BlinkenStart", 0)));
+    assert_eq!(info.nearest_label(0xF123), Some(("BlinkenStart", 0x123)));
+    assert_eq!(info.nearest_label(0xF5D0), Some(("SidTunes", 2)));
+    assert!(info.nearest_label(0x1234).is_none());
+
     Ok(())
 }
@@ -0,0 +1,227 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use pda6502v2emu::bus::Bus;
+use pda6502v2emu::dbginfo;
+use pda6502v2emu::loader::{self, Format, Image, LoadError, Segment};
+use pda6502v2emu::memconf;
+
+fn segment(addr: u32, data: &[u8]) -> Segment {
+    Segment {
+        name: None,
+        addr,
+        data: data.to_vec(),
+    }
+}
+
+#[test]
+fn test_detect() {
+    assert_eq!(loader::detect("a.hex", b""), Format::IntelHex);
+    assert_eq!(loader::detect("a.s19", b""), Format::SRecord);
+    assert_eq!(loader::detect("a.PRG", b"\x01\x08"), Format::Prg);
+    assert_eq!(loader::detect("a.out", b":00000001FF"), Format::IntelHex);
+    assert_eq!(
+        loader::detect("a.out", b"S00600004844521B"),
+        Format::SRecord
+    );
+    assert_eq!(loader::detect("os.rom", b"\x4C\x00\xF0"), Format::Raw);
+}
+
+#[test]
+fn test_raw_and_prg() -> Result<(), Box<dyn Error>> {
+    let image = loader::parse(Format::Raw, &[0xEA, 0xEA], Some(0x0400))?;
+    assert_eq!(image.segments, vec![segment(0x0400, &[0xEA, 0xEA])]);
+    assert_eq!(
+        loader::parse(Format::Raw, &[0xEA], None),
+        Err(LoadError::NoAddress)
+    );
+
+    // PRG files start with their load address, little-endian
+    let image = loader::parse(Format::Prg, &[0x01, 0x08, 0x0B, 0x08], None)?;
+    assert_eq!(image.segments, vec![segment(0x0801, &[0x0B, 0x08])]);
+    assert_eq!(
+        loader::parse(Format::Prg, &[0x01], None),
+        Err(LoadError::NoAddress)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_intel_hex() -> Result<(), Box<dyn Error>> {
+    let hex = "\
+:0402000001020304F0
+:020204000506ED
+:020000040000FA
+:01030000AA52
+:0400000500000200F5
+:00000001FF
+:01000000EA15
+";
+    let image = loader::parse(Format::IntelHex, hex.as_bytes(), None)?;
+    assert_eq!(
+        image.segments,
+        vec![
+            segment(0x0200, &[1, 2, 3, 4, 5, 6]),
+            segment(0x0300, &[0xAA])
+        ]
+    );
+    assert_eq!(image.start, Some(0x0200));
+
+    // extended segment and start segment addresses
+    let hex = ":020000021000EC\n:01000000EA15\n:0400000300000200F7\n";
+    let image = loader::parse(Format::IntelHex, hex.as_bytes(), None)?;
+    assert_eq!(image.segments, vec![segment(0x10000, &[0xEA])]);
+    assert_eq!(image.start, Some(0x0200));
+
+    assert_eq!(
+        loader::parse(Format::IntelHex, b":01030000AA53\n", None),
+        Err(LoadError::Checksum { line: 1 })
+    );
+    assert!(matches!(
+        loader::parse(Format::IntelHex, b"\n:0103AA52\n", None),
+        Err(LoadError::Syntax { line: 2, .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_srecord() -> Result<(), Box<dyn Error>> {
+    let srec = "\
+S00600004844521B
+S107020001020304EC
+S2060002040506E8
+S30600000300AA4C
+S504000003F8
+S9030200FA
+";
+    let image = loader::parse(Format::SRecord, srec.as_bytes(), None)?;
+    assert_eq!(
+        image.segments,
+        vec![
+            segment(0x0200, &[1, 2, 3, 4, 5, 6]),
+            segment(0x0300, &[0xAA])
+        ]
+    );
+    assert_eq!(image.start, Some(0x0200));
+
+    assert_eq!(
+        loader::parse(Format::SRecord, b"S9030200FB", None),
+        Err(LoadError::Checksum { line: 1 })
+    );
+    assert!(matches!(
+        loader::parse(Format::SRecord, b"SX030200FA", None),
+        Err(LoadError::Syntax { line: 1, .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_range_errors() -> Result<(), Box<dyn Error>> {
+    let mut bus = Bus::new_ram_only();
+
+    // an image past $FFFF is reported, and nothing of it loaded
+    let image = Image {
+        segments: vec![segment(0x0200, &[0xAA]), segment(0xFFFF, &[0xEA, 0xEA])],
+        start: None,
+    };
+    assert_eq!(
+        image.load(&mut bus),
+        Err(LoadError::Range {
+            addr: 0xFFFF,
+            len: 2
+        })
+    );
+    assert_eq!(bus.read(0x0200), 0x00);
+    assert_eq!(
+        loader::parse(Format::IntelHex, b":020000040001F9\n:01000000EA15\n", None)?
+            .load(&mut bus)
+            .unwrap_err()
+            .to_string(),
+        "1 bytes at $10000 don't fit below $10000"
+    );
+
+    // and so is Bus::load's
+    assert!(bus.load(0xFFFF, vec![0xEA, 0xEA]).is_err());
+    bus.load(0xFFFE, vec![0xEA, 0xEA])?;
+    Ok(())
+}
+
+// An ld65 build, as written to a temporary directory: os.rom from the os memory area at
+// $F000, with the os segment at its start and vectors at its end.
+fn ld65_output(name: &str) -> Result<std::path::PathBuf, Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("pda6502v2emu-loader-{name}"));
+    fs::create_dir_all(&dir)?;
+    let mut rom = vec![0xFF; 0x1000];
+    rom[..3].copy_from_slice(&[0x4C, 0x00, 0xF0]);
+    rom[0xFFA..].copy_from_slice(&[0x00, 0xF0, 0x00, 0xF0, 0x00, 0xF0]);
+    fs::write(dir.join("os.rom"), rom)?;
+    Ok(dir)
+}
+
+fn assert_os_segments(image: &Image) {
+    let names: Vec<_> = image.segments.iter().map(|s| s.name.as_deref()).collect();
+    assert_eq!(names, [Some("os"), Some("vectors")]);
+    assert_eq!(image.segments[0].addr, 0xF000);
+    assert_eq!(image.segments[0].data, [0x4C, 0x00, 0xF0]);
+    assert_eq!(image.segments[1].addr, 0xFFFA);
+    assert_eq!(image.segments[1].data, [0x00, 0xF0, 0x00, 0xF0, 0x00, 0xF0]);
+
+    let mut bus = Bus::new_ram_only();
+    image.load(&mut bus).unwrap();
+    assert_eq!(bus.read_u16(0xFFFC), 0xF000);
+    assert_eq!(bus.read(0xF000), 0x4C);
+    assert_eq!(bus.read(0xF003), 0x00); // not part of any segment
+}
+
+#[test]
+fn test_ld65_debug() -> Result<(), Box<dyn Error>> {
+    let dir = ld65_output("debug")?;
+    let info = dbginfo::parse(
+        r#"
+seg	id=0,name="ZEROPAGE",start=0x000000,size=0x001C,addrsize=zeropage,type=zp
+seg	id=1,name="os",start=0x00F000,size=0x0003,addrsize=absolute,type=rw,oname="os.rom",ooffs=0
+seg	id=2,name="vectors",start=0x00FFFA,size=0x0006,addrsize=absolute,type=rw,oname="os.rom",ooffs=4090
+"#,
+    )?;
+    let image = loader::from_ld65_debug(&info, &dir)?;
+    assert_os_segments(&image);
+
+    assert!(matches!(
+        loader::from_ld65_debug(&info, Path::new("/nonexistent")),
+        Err(LoadError::Io { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_ld65_map() -> Result<(), Box<dyn Error>> {
+    let dir = ld65_output("map")?;
+    let map = "\
+Modules list:
+-------------
+os.o:
+    CODE              Offs=000000  Size=000003  Align=00001  Fill=0000
+
+Segment list:
+-------------
+Name                   Start     End    Size  Align
+----------------------------------------------------
+ZEROPAGE              000000  00001B  00001C  00001
+os                    00F000  00F002  000003  00001
+vectors               00FFFA  00FFFF  000006  00001
+
+
+Exports list by name:
+---------------------
+";
+    let areas = memconf::parse(
+        r#"MEMORY {
+          ram: start $0000, size $F000, file "/dev/null";
+          os: start $F000, size 4096, type ro, file "os.rom";
+        }"#,
+    )?;
+    let image = loader::from_ld65_map(map, &areas, &dir)?;
+    assert_os_segments(&image);
+    Ok(())
+}
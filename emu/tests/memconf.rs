@@ -0,0 +1,73 @@
+use std::error::Error;
+
+use pda6502v2emu::bus::Protection;
+use pda6502v2emu::memconf::{self, Area};
+
+#[test]
+fn test_parse() -> Result<(), Box<dyn Error>> {
+    let sample = r#"
+# a comment
+MEMORY {
+  zp:   start $0000, size $0100, type rw, define yes;
+  ram:
+    start $0200,
+    size $CE00, # emu: write-trap
+    file "/dev/null";
+  io:   start = $D000, size = 4096;   # emu: write-ignored
+  os:
+    start $F000,
+    size %1000000000000,
+    type = ro,
+    file = "os.rom";
+}
+
+SEGMENTS {
+  os: load os;
+}
+"#;
+
+    let areas = memconf::parse(sample)?;
+    assert_eq!(
+        areas[0],
+        Area {
+            name: "zp".to_string(),
+            start: 0x0000,
+            size: 0x0100,
+            file: None,
+            protection: None
+        }
+    );
+    assert_eq!(areas[1].protection, Some(Protection::WriteTrap));
+    assert_eq!(areas[1].file.as_deref(), Some("/dev/null"));
+    assert_eq!(areas[1].range(), 0x0200..=0xCFFF);
+
+    // a comment after an area's semicolon belongs to that area, not the next
+    assert_eq!(areas[2].protection, Some(Protection::WriteIgnored));
+    assert_eq!(areas[3].protection, Some(Protection::ReadOnly));
+    assert_eq!(areas[3].name, "os");
+    assert_eq!(areas[3].range(), 0xF000..=0xFFFF);
+    assert_eq!(areas[3].file.as_deref(), Some("os.rom"));
+    assert_eq!(areas.len(), 4);
+
+    // and one on a line of its own, to the area after it
+    let areas = memconf::parse(
+        "MEMORY {\n ram: start $0000, size 16;\n # emu: write-trap\n os: start $F000, size 16, type ro; }",
+    )?;
+    assert_eq!(areas[0].protection, None);
+    assert_eq!(areas[1].protection, Some(Protection::WriteTrap));
+
+    assert!(memconf::parse("MEMORY { ram: start $0000, size 16; # emu: read-mostly\n }").is_err());
+    assert!(memconf::parse("MEMORY { ram: start $10000, size 16; }").is_err());
+    Ok(())
+}
+
+// The OS's ROM is protected from stray stores.
+#[test]
+fn test_os_memory_conf() -> Result<(), Box<dyn Error>> {
+    let areas = memconf::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../os/memory.conf"))?;
+    let os = areas.iter().find(|a| a.name == "os").unwrap();
+    assert_eq!(os.range(), 0xF000..=0xFFFF);
+    assert_eq!(os.protection, Some(Protection::ReadOnly));
+    assert_eq!(os.file.as_deref(), Some("os.rom"));
+    Ok(())
+}
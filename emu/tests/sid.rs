@@ -0,0 +1,345 @@
+use std::io::Cursor;
+
+use pda6502v2emu::device::Device;
+use pda6502v2emu::sid::{Model, Sid, CLOCK_HZ};
+use pda6502v2emu::wav::WavWriter;
+
+const FRELO1: u16 = 0x00;
+const FREHI1: u16 = 0x01;
+const PWLO1: u16 = 0x02;
+const PWHI1: u16 = 0x03;
+const VCREG1: u16 = 0x04;
+const ATDCY1: u16 = 0x05;
+const SUREL1: u16 = 0x06;
+const FRELO3: u16 = 0x0E;
+const FREHI3: u16 = 0x0F;
+const VCREG3: u16 = 0x12;
+const ATDCY3: u16 = 0x13;
+const SUREL3: u16 = 0x14;
+const CUTLO: u16 = 0x15;
+const CUTHI: u16 = 0x16;
+const RESON: u16 = 0x17;
+const SIGVOL: u16 = 0x18;
+const POTX: u16 = 0x19;
+const POTY: u16 = 0x1A;
+const OSC3: u16 = 0x1B;
+const ENV3: u16 = 0x1C;
+
+const RATE: u32 = 44100;
+
+// The frequency register value for hz.
+fn freq(hz: f64) -> u16 {
+    (hz * (1 << 24) as f64 / CLOCK_HZ as f64).round() as u16
+}
+
+fn play_voice1(sid: &mut Sid, hz: f64, control: u8) {
+    let f = freq(hz);
+    sid.write(FRELO1, f as u8);
+    sid.write(FREHI1, (f >> 8) as u8);
+    sid.write(PWLO1, 0x00);
+    sid.write(PWHI1, 0x08); // 50% duty
+    sid.write(ATDCY1, 0x00);
+    sid.write(SUREL1, 0xF0);
+    sid.write(VCREG1, control | 0x01);
+}
+
+// Run for seconds, returning the samples rendered.
+fn render(sid: &mut Sid, seconds: f64) -> Vec<i16> {
+    for _ in 0..(seconds * CLOCK_HZ as f64 / 250.0) as usize {
+        sid.step(250);
+    }
+    sid.take_samples()
+}
+
+fn rms(samples: &[i16]) -> f64 {
+    let sum: f64 = samples.iter().map(|&s| (s as f64).powi(2)).sum();
+    (sum / samples.len() as f64).sqrt()
+}
+
+fn rising_zero_crossings(samples: &[i16]) -> usize {
+    samples.windows(2).filter(|w| w[0] < 0 && w[1] >= 0).count()
+}
+
+fn sid() -> Sid {
+    let mut sid = Sid::with_model(Model::Mos8580);
+    sid.set_sample_rate(Some(RATE));
+    sid.write(SIGVOL, 0x0F);
+    sid
+}
+
+#[test]
+fn test_waveform_frequency() {
+    for control in [0x10, 0x20, 0x40] {
+        let mut sid = sid();
+        play_voice1(&mut sid, 440.0, control);
+        let samples = render(&mut sid, 0.5);
+        assert_eq!(samples.len(), RATE as usize / 2);
+        let crossings = rising_zero_crossings(&samples[RATE as usize / 10..]);
+        assert!(
+            (175..=177).contains(&crossings),
+            "control {control:#04X}: {crossings} crossings"
+        );
+        assert!(rms(&samples) > 3000.0);
+    }
+}
+
+#[test]
+fn test_noise() {
+    let mut sid = sid();
+    play_voice1(&mut sid, 2000.0, 0x80);
+    let samples = render(&mut sid, 0.1);
+    assert!(rms(&samples) > 3000.0);
+
+    // noise doesn't repeat like a waveform, so takes on many distinct levels
+    let mut levels = samples.clone();
+    levels.sort();
+    levels.dedup();
+    assert!(levels.len() > 1000);
+}
+
+#[test]
+fn test_envelope() {
+    let mut sid = sid();
+    play_voice1(&mut sid, 440.0, 0x20);
+    sid.write(ATDCY1, 0xA9); // attack 500 ms, decay 750 ms
+    sid.write(SUREL1, 0x40); // sustain at 4/15, release 6 ms
+
+    // rising through the attack
+    let attack: Vec<f64> = (0..5).map(|_| rms(&render(&mut sid, 0.1))).collect();
+    assert!(attack.windows(2).all(|w| w[1] > w[0]), "{attack:?}");
+
+    // then decaying to sustain, and holding there
+    let decay = rms(&render(&mut sid, 0.3));
+    assert!(decay < attack[4]);
+    let sustain: Vec<f64> = (0..3).map(|_| rms(&render(&mut sid, 0.5))).collect();
+    assert!(
+        (sustain[1] - sustain[2]).abs() < sustain[2] * 0.05,
+        "{sustain:?}"
+    );
+    assert!(sustain[2] < attack[4] * 0.5);
+
+    // until the gate closes
+    sid.write(VCREG1, 0x20);
+    render(&mut sid, 0.1);
+    assert!(rms(&render(&mut sid, 0.1)) < 50.0);
+}
+
+#[test]
+fn test_filter() {
+    let unfiltered = {
+        let mut sid = sid();
+        play_voice1(&mut sid, 3000.0, 0x40);
+        rms(&render(&mut sid, 0.2))
+    };
+
+    // a low-pass at about 500 Hz cuts a 3 kHz pulse
+    let mut sid = sid();
+    play_voice1(&mut sid, 3000.0, 0x40);
+    sid.write(CUTLO, 0x00);
+    sid.write(CUTHI, 0x0A);
+    sid.write(RESON, 0x01); // filter voice 1
+    sid.write(SIGVOL, 0x1F);
+    let lowpass = rms(&render(&mut sid, 0.2));
+    assert!(lowpass < unfiltered * 0.3, "{lowpass} vs {unfiltered}");
+
+    // while a high-pass lets most of it through
+    sid.write(SIGVOL, 0x4F);
+    render(&mut sid, 0.1);
+    let highpass = rms(&render(&mut sid, 0.2));
+    assert!(highpass > unfiltered * 0.7, "{highpass} vs {unfiltered}");
+
+    // resonance boosts the cutoff frequency
+    sid.write(CUTHI, 0xFF);
+    sid.write(SIGVOL, 0x2F);
+    let bandpass = rms(&render(&mut sid, 0.2));
+    sid.write(RESON, 0xF1);
+    let resonant = rms(&render(&mut sid, 0.2));
+    assert!(resonant > bandpass * 1.5, "{resonant} vs {bandpass}");
+}
+
+#[test]
+fn test_sync_and_ring_modulation() {
+    // a rate with a whole number of samples in 4096 cycles: voice 3's period at $1000
+    let rate: u32 = 15625;
+    let period: usize = 64;
+    let periodic = |sid: &mut Sid| {
+        let samples = render(sid, 0.2);
+        let tail = &samples[samples.len() - 4 * period..];
+        tail.iter()
+            .zip(&tail[period..])
+            .all(|(a, b)| (a - b).abs() < 4)
+    };
+
+    for (control, synced) in [(0x20, false), (0x22, true)] {
+        let mut sid = Sid::with_model(Model::Mos8580);
+        sid.set_sample_rate(Some(rate));
+        sid.write(SIGVOL, 0x0F);
+        sid.write(FRELO3, 0x00);
+        sid.write(FREHI3, 0x10);
+        play_voice1(&mut sid, 1234.5, control);
+        assert_eq!(periodic(&mut sid), synced, "control {control:#04X}");
+    }
+
+    // ring modulation flips voice 1's triangle with voice 3's MSB, so only does anything while
+    // voice 3 is running
+    let triangle = |control, voice3: u8| {
+        let mut sid = sid();
+        sid.write(FREHI3, voice3);
+        play_voice1(&mut sid, 1234.5, control);
+        render(&mut sid, 0.1)
+    };
+    assert_eq!(triangle(0x14, 0x00), triangle(0x10, 0x00));
+    assert_ne!(triangle(0x14, 0x10), triangle(0x10, 0x10));
+}
+
+#[test]
+fn test_6581_volume_clicks() {
+    // with no voices playing, volume changes are only heard from the 6581's DC offset
+    let mut sid = Sid::with_model(Model::Mos6581);
+    sid.set_sample_rate(Some(RATE));
+    render(&mut sid, 0.5);
+    sid.write(SIGVOL, 0x0F);
+    let click = render(&mut sid, 0.01);
+    assert!(click.iter().map(|s| s.abs()).max().unwrap() > 1000);
+
+    let mut sid = Sid::with_model(Model::Mos8580);
+    sid.set_sample_rate(Some(RATE));
+    render(&mut sid, 0.5);
+    sid.write(SIGVOL, 0x0F);
+    let click = render(&mut sid, 0.01);
+    assert!(click.iter().map(|s| s.abs()).max().unwrap() < 10);
+}
+
+#[test]
+fn test_voice3_readback() {
+    let mut sid = Sid::new();
+    assert_eq!(sid.read(OSC3), 0x00);
+    assert_eq!(sid.read(ENV3), 0x00);
+
+    // OSC3 is the top of the oscillator: a sawtooth rises 1/256 per step of $10000
+    sid.write(FRELO3, 0x00);
+    sid.write(FREHI3, 0x01);
+    sid.write(VCREG3, 0x20);
+    sid.step(0x80);
+    assert_eq!(sid.read(OSC3), 0x00);
+    sid.step(0x80);
+    assert_eq!(sid.read(OSC3), 0x01);
+    sid.step_cycles(0x1000);
+    assert_eq!(sid.read(OSC3), 0x11);
+
+    // ENV3 follows the envelope, rising a step every 9 cycles at the fastest attack
+    sid.write(ATDCY3, 0x00);
+    sid.write(SUREL3, 0xF0);
+    sid.write(VCREG3, 0x21);
+    sid.step_cycles(9 * 100);
+    assert_eq!(sid.read(ENV3), 100);
+    sid.step_cycles(9 * 200);
+    assert_eq!(sid.read(ENV3), 0xFF);
+
+    // even while voice 3 is switched off from the output
+    sid.write(SIGVOL, 0x80);
+    sid.write(VCREG3, 0x20);
+    sid.step(50);
+    assert!(sid.read(ENV3) < 0xFF);
+}
+
+// OSC3 reads noise from the same shift register bits as on a real SID, starting from its
+// reset value of $7FFFF8.
+#[test]
+fn test_noise_matches_hardware() {
+    let mut sid = Sid::new();
+    sid.write(FRELO3, 0x00);
+    sid.write(FREHI3, 0x80); // bit 19 of the oscillator rises after 16 cycles, then every 32
+    sid.write(VCREG3, 0x80);
+    let mut numbers = vec![sid.read(OSC3)];
+    sid.step(16);
+    for _ in 0..15 {
+        numbers.push(sid.read(OSC3));
+        sid.step(32);
+    }
+    assert_eq!(
+        numbers,
+        [
+            0xFE, 0xFE, 0xFC, 0xFC, 0xFC, 0xF8, 0xF8, 0xF8, 0xF8, 0xF0, 0xF0, 0xE0, 0xE0, 0xE0,
+            0xC0, 0xC0
+        ]
+    );
+}
+
+#[test]
+fn test_noise_random_numbers() {
+    // like life.s: noise at the highest frequency, read through OSC3
+    let numbers = |sid: &mut Sid| -> Vec<u8> {
+        sid.write(FREHI3, 0xFF);
+        sid.write(VCREG3, 0x80);
+        (0..1000)
+            .map(|_| {
+                sid.step(20);
+                sid.read(OSC3)
+            })
+            .collect()
+    };
+
+    // the same from every reset, as on hardware
+    let first = numbers(&mut Sid::new());
+    assert_eq!(first, numbers(&mut Sid::new()));
+    let mut counts = [0; 256];
+    for &n in first.iter() {
+        counts[n as usize] += 1;
+    }
+    assert!(counts.iter().all(|&c| c < 20), "{counts:?}");
+    assert!(first.iter().filter(|&&n| n >= 0x80).count().abs_diff(500) < 60);
+
+    // different for a different seed, and the same again for the same seed
+    let mut sid = Sid::new();
+    sid.seed_noise(12345);
+    let seeded = numbers(&mut sid);
+    assert_ne!(seeded, first);
+    let mut sid = Sid::new();
+    sid.seed_noise(12345);
+    sid.reset();
+    assert_eq!(numbers(&mut sid), seeded);
+
+    // and every byte comes up eventually, unlike fastrand::u8(0..255)
+    let mut sid = Sid::new();
+    sid.write(FREHI3, 0xFF);
+    sid.write(VCREG3, 0x80);
+    let mut seen = [false; 256];
+    for _ in 0..20000 {
+        sid.step(20);
+        seen[sid.read(OSC3) as usize] = true;
+    }
+    assert!(seen.iter().all(|&s| s));
+}
+
+#[test]
+fn test_pots() {
+    let mut sid = Sid::new();
+    assert_eq!((sid.read(POTX), sid.read(POTY)), (0xFF, 0xFF));
+    sid.set_pots(0x12, 0x34);
+    assert_eq!((sid.read(POTX), sid.read(POTY)), (0x12, 0x34));
+}
+
+#[test]
+fn test_wav() {
+    let mut wav = WavWriter::new(Cursor::new(Vec::new()), 22050).unwrap();
+    wav.write(&[0, 1, -1]).unwrap();
+    wav.write(&[i16::MAX, i16::MIN]).unwrap();
+    let data = wav.into_inner().into_inner();
+
+    let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+    let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+    assert_eq!(&data[0..4], b"RIFF");
+    assert_eq!(u32_at(4), 36 + 10);
+    assert_eq!(&data[8..16], b"WAVEfmt ");
+    assert_eq!(u16_at(20), 1); // PCM
+    assert_eq!(u16_at(22), 1); // mono
+    assert_eq!(u32_at(24), 22050);
+    assert_eq!(u16_at(34), 16);
+    assert_eq!(&data[36..40], b"data");
+    assert_eq!(u32_at(40), 10);
+    assert_eq!(
+        &data[44..],
+        [0x00, 0x00, 0x01, 0x00, 0xFF, 0xFF, 0xFF, 0x7F, 0x00, 0x80]
+    );
+}
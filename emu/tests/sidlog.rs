@@ -0,0 +1,184 @@
+use pda6502v2emu::asm::{val, Assembler, Operand};
+use pda6502v2emu::sid::Sid;
+use pda6502v2emu::sidlog::{self, DumpError, SidWrite};
+
+mod common;
+
+fn write(cycle: u64, reg: u8, data: u8) -> SidWrite {
+    SidWrite { cycle, reg, data }
+}
+
+#[test]
+fn test_capture() {
+    let code = Assembler::new()
+        .org(0x0400)
+        .lda(Operand::Imm(0x0F)) // 2 cycles
+        .sta(Operand::Abs(val(0xD418))) // 4
+        .sta(Operand::Abs(val(0xD000))) // 4, not the SID
+        .lda(Operand::Imm(0x21)) // 2
+        .sta(Operand::Abs(val(0xD424))) // 4, a mirror of $D404
+        .sta(Operand::Abs(val(0xD41B))) // 4, OSC3 is read-only
+        .ldx(Operand::Imm(0x01)) // 2
+        .stx(Operand::Abs(val(0xD7E1))) // 4, a mirror of $D401
+        .stp() // 3
+        .assemble()
+        .unwrap();
+    let mut sys = common::sys(code);
+    sys.bus.set_sid_capture(true);
+    sys.run().unwrap();
+    let bus = &mut sys.bus;
+    assert_eq!(
+        bus.take_sid_writes(),
+        [
+            write(2, 0x18, 0x0F),
+            write(12, 0x04, 0x21),
+            write(22, 0x01, 0x01)
+        ]
+    );
+    assert_eq!(bus.cycles(), 29);
+    assert_eq!(bus.take_sid_writes(), []);
+
+    // nothing is captured once disabled
+    bus.set_sid_capture(false);
+    bus.write(0xD400, 0x12);
+    assert_eq!(bus.take_sid_writes(), []);
+}
+
+#[test]
+fn test_dump() {
+    let writes = [
+        write(0, 0x18, 0x0F),
+        write(100, 0x00, 0x12),
+        write(100, 0x01, 0x34),
+        write(1_000_000, 0x04, 0x41),
+    ];
+    let data = sidlog::encode(&writes);
+    assert_eq!(&data[..5], b"SIDW\x01");
+    // cycle deltas take a byte per 7 bits
+    assert_eq!(data.len(), 5 + 3 + 3 + 3 + 5);
+    assert_eq!(sidlog::decode(&data).unwrap(), writes);
+
+    // a Writer saving in parts makes the same dump
+    let mut w = sidlog::Writer::new(Vec::new()).unwrap();
+    w.write(&writes[..2]).unwrap();
+    w.write(&writes[2..]).unwrap();
+    assert_eq!(w.into_inner(), data);
+
+    assert_eq!(sidlog::decode(b"RIFF\x01"), Err(DumpError::Magic));
+    assert_eq!(sidlog::decode(b"SIDW\x02"), Err(DumpError::Version(2)));
+    assert_eq!(
+        sidlog::decode(&data[..data.len() - 1]),
+        Err(DumpError::Truncated(14))
+    );
+    assert_eq!(
+        sidlog::decode(b"SIDW\x01\x00\x18\x0F\x80"),
+        Err(DumpError::Truncated(8))
+    );
+    assert_eq!(
+        sidlog::decode(b"SIDW\x01\x00\x19\x00"),
+        Err(DumpError::Register(5, 0x19))
+    );
+
+    // a delta of more than 64 bits, or deltas adding up to more, overflow
+    let mut data = b"SIDW\x01".to_vec();
+    data.extend([0xFF; 12]);
+    data.extend([0x7F, 0x00, 0x00]);
+    assert_eq!(sidlog::decode(&data), Err(DumpError::Overflow(5)));
+    let mut data = b"SIDW\x01".to_vec();
+    data.extend([0xFF; 9]);
+    data.extend([0x02, 0x00, 0x00]);
+    assert_eq!(sidlog::decode(&data), Err(DumpError::Overflow(5)));
+    let max = sidlog::encode(&[write(u64::MAX, 0x00, 0x00)]);
+    assert_eq!(sidlog::decode(&max).unwrap(), [write(u64::MAX, 0x00, 0x00)]);
+    let mut data = max.clone();
+    data.extend_from_slice(&sidlog::encode(&[write(1, 0x00, 0x00)])[5..]);
+    assert_eq!(sidlog::decode(&data), Err(DumpError::Overflow(17)));
+}
+
+// Writes going back in time, e.g. after the clock is reset, are refused rather than logged.
+#[test]
+fn test_out_of_order() {
+    let mut w = sidlog::Writer::new(Vec::new()).unwrap();
+    w.write(&[write(100, 0x00, 0x12)]).unwrap();
+    let e = w
+        .write(&[write(150, 0x00, 0x34), write(50, 0x01, 0x56)])
+        .unwrap_err();
+    assert_eq!(e.to_string(), "SID write at cycle 50 is out of order");
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+
+    // and what was written before still decodes, carrying on from there
+    w.write(&[write(150, 0x00, 0x34)]).unwrap();
+    assert_eq!(
+        sidlog::decode(&w.into_inner()).unwrap(),
+        [write(100, 0x00, 0x12), write(150, 0x00, 0x34)]
+    );
+
+    let mut sid = Sid::new();
+    let writes = [write(100, 0x00, 0x12), write(50, 0x01, 0x56)];
+    assert_eq!(sidlog::replay(&writes, &mut sid), Err(DumpError::Order(50)));
+}
+
+#[test]
+fn test_frame_table() {
+    let writes = [
+        write(10, 0x18, 0x0F),
+        write(20, 0x00, 0x12),
+        write(30, 0x00, 0x34),
+        write(3 * sidlog::FRAME_CYCLES + 5, 0x04, 0x41),
+    ];
+    let table = sidlog::frame_table(&writes, sidlog::FRAME_CYCLES);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[0],
+        " frame      cycle | 00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F 10 11 12 13 14 15 16 17 18"
+    );
+    // the last value written in the frame
+    assert_eq!(
+        lines[1],
+        "     0          0 | 34 .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. 0F"
+    );
+    assert_eq!(
+        lines[2],
+        "     3      50004 | .. .. .. .. 41 .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .. .."
+    );
+}
+
+#[test]
+fn test_replay() {
+    // a voice playing, written a register at a time with the timing the CPU gave it
+    let mut asm = Assembler::new();
+    asm.org(0x0400);
+    for (reg, data) in [
+        (0x18, 0x0F),
+        (0x00, 0x00),
+        (0x01, 0x1D),
+        (0x06, 0xF0),
+        (0x04, 0x21),
+    ] {
+        asm.lda(Operand::Imm(data))
+            .sta(Operand::Abs(val(0xD400 + reg)));
+    }
+    for _ in 0..200 {
+        asm.nop();
+    }
+    asm.lda(Operand::Imm(0x20))
+        .sta(Operand::Abs(val(0xD404)))
+        .stp();
+    let mut sys = common::sys(asm.assemble().unwrap());
+    sys.bus.set_sid_capture(true);
+    sys.sid().set_sample_rate(Some(200_000));
+    sys.run().unwrap();
+    let writes = sys.bus.take_sid_writes();
+    assert_eq!(writes.len(), 6);
+
+    // replaying the writes into another SID renders the same samples, up to the last write
+    let live = sys.sid().take_samples();
+    let mut sid = Sid::new();
+    sid.set_sample_rate(Some(200_000));
+    sidlog::replay(&sidlog::decode(&sidlog::encode(&writes)).unwrap(), &mut sid).unwrap();
+    let replayed = sid.take_samples();
+    assert!(!replayed.is_empty());
+    assert_eq!(replayed, live[..replayed.len()]);
+    assert!(replayed.iter().any(|&s| s != replayed[0]));
+}
@@ -0,0 +1,263 @@
+// Conformance tests driven by per-opcode JSON vectors in the SingleStepTests / ProcessorTests
+// format (https://github.com/SingleStepTests/65x02): one file per opcode, e.g. a9.json, each an
+// array of vectors giving the CPU and RAM state before and after a single instruction, and the
+// bus cycles in between.
+//
+// The vectors aren't included here; point SINGLE_STEP_TESTS_DIR at a directory of them, e.g.
+// 65x02/wdc65c02/v1, and set SINGLE_STEP_TESTS_VARIANT to nmos, rockwell or wdc (the default)
+// to match. SINGLE_STEP_TESTS_OPCODES optionally limits the run to a comma-separated list of
+// opcodes in hex.
+//
+//     SINGLE_STEP_TESTS_DIR=../../65x02/wdc65c02/v1 cargo test --test single_step -- --nocapture
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use pda6502v2emu::bus::{AccessKind, Bus};
+use pda6502v2emu::cpu::{stat, Cpu};
+use pda6502v2emu::dec::Decoder;
+use pda6502v2emu::isa::Variant;
+
+#[derive(Deserialize)]
+struct Vector {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    #[serde(default)]
+    cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Deserialize)]
+struct State {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+// Bits 4 (B) and 5 of P only exist when P is pushed, so they aren't compared.
+const P_MASK: u8 = 0b11001111;
+
+// Results for the vectors of one opcode.
+#[derive(Default)]
+struct Summary {
+    passed: usize,
+    failed: usize,
+    cycles_checked: usize,
+    cycles_failed: usize,
+    failures: Vec<String>, // descriptions of the first few failures
+}
+
+const MAX_REPORTED_FAILURES: usize = 3;
+
+impl Summary {
+    fn record(&mut self, name: &str, mismatches: Vec<String>, cycles_ok: Option<bool>) {
+        if let Some(ok) = cycles_ok {
+            self.cycles_checked += 1;
+            if !ok {
+                self.cycles_failed += 1;
+            }
+        }
+        if mismatches.is_empty() {
+            self.passed += 1;
+        } else {
+            self.failed += 1;
+            if self.failures.len() < MAX_REPORTED_FAILURES {
+                self.failures
+                    .push(format!("\"{name}\": {}", mismatches.join(", ")));
+            }
+        }
+    }
+}
+
+// Set up the initial state of a vector, step once, and return a description of each way the
+// result differs from the expected state. Whether the bus cycles matched is returned alongside,
+// if the vector has any.
+fn run_vector(variant: Variant, bus: &mut Bus, v: &Vector) -> (Vec<String>, Option<bool>) {
+    let mut cpu = Cpu::with_variant(variant);
+    cpu.cycle_accurate = true;
+    cpu.pc = v.initial.pc;
+    cpu.s = v.initial.s;
+    cpu.a = v.initial.a;
+    cpu.x = v.initial.x;
+    cpu.y = v.initial.y;
+    cpu.p = v.initial.p;
+    for &(addr, data) in &v.initial.ram {
+        bus.write(addr, data);
+    }
+
+    bus.set_trace(true);
+    let result = cpu.step(bus);
+    let trace = bus.take_trace();
+    bus.set_trace(false);
+
+    let mut mismatches = Vec::new();
+    let info = match result {
+        Ok(info) => info,
+        Err(e) => return (vec![e.to_string()], None),
+    };
+    let e = &v.expected;
+    for (reg, got, want) in [
+        ("S", cpu.s, e.s),
+        ("A", cpu.a, e.a),
+        ("X", cpu.x, e.x),
+        ("Y", cpu.y, e.y),
+    ] {
+        if got != want {
+            mismatches.push(format!("{reg}:{got:02X} want {want:02X}"));
+        }
+    }
+    if cpu.pc != e.pc {
+        mismatches.push(format!("PC:{:04X} want {:04X}", cpu.pc, e.pc));
+    }
+    if cpu.p & P_MASK != e.p & P_MASK {
+        mismatches.push(format!("P:{} want {}", stat(&cpu.p), stat(&e.p)));
+    }
+    for &(addr, want) in &e.ram {
+        let got = bus.read(addr);
+        if got != want {
+            mismatches.push(format!("${addr:04X}:{got:02X} want {want:02X}"));
+        }
+    }
+
+    if v.cycles.is_empty() {
+        return (mismatches, None);
+    }
+    if info.cycles as usize != v.cycles.len() {
+        mismatches.push(format!("cycles:{} want {}", info.cycles, v.cycles.len()));
+    }
+    let got: Vec<String> = trace
+        .iter()
+        .map(|a| {
+            let kind = match a.kind {
+                AccessKind::Read => "read",
+                AccessKind::Write => "write",
+            };
+            format!("{:04X} {:02X} {kind}", a.addr, a.data)
+        })
+        .collect();
+    let want: Vec<String> = v
+        .cycles
+        .iter()
+        .map(|(addr, data, kind)| format!("{addr:04X} {data:02X} {kind}"))
+        .collect();
+    let cycles_ok = got == want;
+    if !cycles_ok {
+        mismatches.push(format!(
+            "bus [{}] want [{}]",
+            got.join(", "),
+            want.join(", ")
+        ));
+    }
+    (mismatches, Some(cycles_ok))
+}
+
+fn run_file(variant: Variant, path: &Path) -> Result<Summary, Box<dyn Error>> {
+    let vectors: Vec<Vector> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let bus = &mut Bus::new_ram_only();
+    let mut summary = Summary::default();
+    for v in &vectors {
+        let (mismatches, cycles_ok) = run_vector(variant, bus, v);
+        summary.record(&v.name, mismatches, cycles_ok);
+    }
+    Ok(summary)
+}
+
+fn variant_from_env() -> Variant {
+    match env::var("SINGLE_STEP_TESTS_VARIANT").as_deref() {
+        Ok("nmos") => Variant::Nmos6502,
+        Ok("rockwell") => Variant::Rockwell65C02,
+        Ok("wdc") | Err(_) => Variant::Wdc65C02S,
+        Ok(other) => panic!("unknown SINGLE_STEP_TESTS_VARIANT: {other}"),
+    }
+}
+
+#[test]
+fn test_single_step_vectors() -> Result<(), Box<dyn Error>> {
+    let Ok(dir) = env::var("SINGLE_STEP_TESTS_DIR") else {
+        println!("SINGLE_STEP_TESTS_DIR not set; skipping single-step vectors");
+        return Ok(());
+    };
+    let variant = variant_from_env();
+    let only: Option<Vec<u8>> = env::var("SINGLE_STEP_TESTS_OPCODES").ok().map(|list| {
+        list.split(',')
+            .map(|code| u8::from_str_radix(code.trim(), 16).unwrap())
+            .collect()
+    });
+    let decoder = Decoder::new(variant);
+
+    let mut failed_opcodes = Vec::new();
+    for code in 0..=255u8 {
+        if only.as_ref().is_some_and(|only| !only.contains(&code)) {
+            continue;
+        }
+        let path = Path::new(&dir).join(format!("{code:02x}.json"));
+        if !path.exists() {
+            continue;
+        }
+        let summary = run_file(variant, &path)?;
+        let mnemonic = decoder
+            .opcode(code)
+            .map(|op| format!("{:?}", op.mnemonic).to_uppercase())
+            .unwrap_or("???".to_string());
+        println!(
+            "${code:02X} {mnemonic:<4} {:>6}/{} passed, bus cycles {}/{} matched",
+            summary.passed,
+            summary.passed + summary.failed,
+            summary.cycles_checked - summary.cycles_failed,
+            summary.cycles_checked
+        );
+        for failure in &summary.failures {
+            println!("    {failure}");
+        }
+        if summary.failed > 0 {
+            failed_opcodes.push(format!("${code:02X}"));
+        }
+    }
+
+    assert!(
+        failed_opcodes.is_empty(),
+        "{} opcodes failed: {}",
+        failed_opcodes.len(),
+        failed_opcodes.join(" ")
+    );
+    Ok(())
+}
+
+// The harness itself, against a hand-made vector in the same format: LDA ($10),Y across a page.
+#[test]
+fn test_single_step_sample_vector() -> Result<(), Box<dyn Error>> {
+    let sample = r#"[{
+        "name": "b1 10 00",
+        "initial": {"pc": 512, "s": 253, "a": 0, "x": 0, "y": 32, "p": 38,
+                    "ram": [[512, 177], [513, 16], [514, 0], [16, 240], [17, 18], [4880, 128]]},
+        "final": {"pc": 514, "s": 253, "a": 128, "x": 0, "y": 32, "p": 164,
+                  "ram": [[512, 177], [513, 16], [514, 0], [16, 240], [17, 18], [4880, 128]]},
+        "cycles": [[512, 177, "read"], [513, 16, "read"], [16, 240, "read"], [17, 18, "read"],
+                   [513, 16, "read"], [4880, 128, "read"]]
+    }]"#;
+    let vectors: Vec<Vector> = serde_json::from_str(sample)?;
+    let bus = &mut Bus::new_ram_only();
+    let mut summary = Summary::default();
+    for v in &vectors {
+        let (mismatches, cycles_ok) = run_vector(Variant::Wdc65C02S, bus, v);
+        summary.record(&v.name, mismatches, cycles_ok);
+    }
+    assert_eq!(summary.failures, Vec::<String>::new());
+    assert_eq!((summary.passed, summary.cycles_checked), (1, 1));
+
+    // a mismatch is reported with the register, what it was and what was expected
+    let mut wrong: Vec<Vector> = serde_json::from_str(sample)?;
+    wrong[0].expected.a = 0x81;
+    let (mismatches, _) = run_vector(Variant::Wdc65C02S, bus, &wrong[0]);
+    assert_eq!(mismatches, vec!["A:80 want 81"]);
+    Ok(())
+}
@@ -0,0 +1,315 @@
+use std::cell::Cell;
+use std::fs;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use pda6502v2emu::asm::{branch, val, Assembler, Operand};
+use pda6502v2emu::bus::{ProtectedWrite, Protection};
+use pda6502v2emu::cpu::RunState;
+use pda6502v2emu::device::Device;
+use pda6502v2emu::isa::Variant;
+use pda6502v2emu::memconf::Area;
+use pda6502v2emu::sidlog::{self, SidWrite};
+use pda6502v2emu::sys::{Os, Pin, Sys, SysError};
+
+mod common;
+
+// Count at $10 forever: INC (5 cycles) and BRA (3), after CLI or SEI (2) if given. Whichever
+// interrupt comes stops the CPU at $0405.
+fn counter(setup: Option<fn(&mut Assembler) -> &mut Assembler>) -> Sys {
+    let mut asm = Assembler::new();
+    asm.org(0x0400);
+    if let Some(setup) = setup {
+        setup(&mut asm);
+    }
+    asm.label("loop")
+        .inc(Operand::Z(0x10))
+        .bra(Operand::Rel(branch("loop")))
+        .stp();
+    let mut sys = common::sys(asm.assemble().unwrap());
+    let handler = 0x0400 + setup.map_or(0, |_| 1) + 4;
+    sys.bus.load(0xFFFA, vec![handler as u8, 0x04]).unwrap();
+    sys.bus.load(0xFFFE, vec![handler as u8, 0x04]).unwrap();
+    sys
+}
+
+#[test]
+fn test_cycles() {
+    let code = Assembler::new()
+        .org(0x0400)
+        .lda(Operand::Imm(0x01)) // 2 cycles
+        .sta(Operand::Z(0x10)) // 3
+        .ldx(Operand::Imm(0xFF)) // 2
+        .txs() // 2
+        .stp() // 3
+        .assemble()
+        .unwrap();
+    let mut sys = common::sys(code);
+    sys.run().unwrap();
+    assert_eq!(sys.cycles(), 12);
+    assert_eq!(sys.bus.cycles(), 12);
+
+    // stopped, the clock keeps running a cycle a step
+    assert_eq!(sys.step(), Ok(RunState::Stopped));
+    assert_eq!(sys.cycles(), 13);
+    assert_eq!(sys.bus.cycles(), 13);
+}
+
+#[test]
+fn test_resb() {
+    let mut sys = counter(None);
+    let code = Assembler::new()
+        .org(0x0500)
+        .lda(Operand::Imm(0xAA))
+        .sta(Operand::Z(0x11))
+        .stp()
+        .assemble()
+        .unwrap();
+    sys.bus.load(0x0500, code).unwrap();
+    sys.bus.load(0xFFFC, vec![0x00, 0x05]).unwrap();
+    for _ in 0..10 {
+        sys.step().unwrap();
+    }
+    let count = sys.bus.peek(0x0010);
+    assert_eq!(count, 5);
+
+    // held in reset, the CPU does nothing while the clock runs
+    sys.set_pin(Pin::Resb, true);
+    let cycles = sys.cycles();
+    for _ in 0..5 {
+        sys.step().unwrap();
+    }
+    assert_eq!(sys.cycles(), cycles + 5);
+    assert_eq!(sys.bus.peek(0x0010), count);
+
+    // released, it starts from the reset vector, with RAM and the clock carrying on
+    sys.set_pin(Pin::Resb, false);
+    sys.run().unwrap();
+    assert_eq!(sys.bus.peek(0x0011), 0xAA);
+    assert_eq!(sys.bus.peek(0x0010), count);
+    assert!(sys.cycles() > cycles + 5);
+}
+
+#[test]
+fn test_scheduled_interrupts() {
+    // instructions start at cycles 2 + 8n and 7 + 8n, so an interrupt due at 100 is taken at
+    // 103, after 13 INCs, then takes 7 cycles, and the handler's STP 3
+    let mut sys = counter(Some(Assembler::cli));
+    sys.set_pin_at(100, Pin::Irqb, true);
+    sys.run().unwrap();
+    assert_eq!(sys.bus.peek(0x0010), 13);
+    assert_eq!(sys.cycles(), 113);
+
+    // NMI, despite SEI
+    let mut sys = counter(Some(Assembler::sei));
+    sys.set_pin_at(100, Pin::Nmib, true);
+    sys.run().unwrap();
+    assert_eq!(sys.bus.peek(0x0010), 13);
+    assert_eq!(sys.cycles(), 113);
+
+    // IRQ, masked by SEI, is ignored
+    let mut sys = counter(Some(Assembler::sei));
+    sys.set_pin_at(100, Pin::Irqb, true);
+    while sys.cycles() < 200 {
+        assert_eq!(sys.step(), Ok(RunState::Running));
+    }
+}
+
+#[test]
+fn test_rdy_stall() {
+    // instructions start at cycles 8n and 5 + 8n: 13 INCs start before cycle 100
+    let mut sys = counter(None);
+    while sys.cycles() < 100 {
+        sys.step().unwrap();
+    }
+    assert_eq!(sys.bus.peek(0x0010), 13);
+
+    // stalled from the BRA at 21 until 50, a cycle at a time, there's time for 9
+    let mut sys = counter(None);
+    sys.set_pin_at(20, Pin::Rdy, true);
+    sys.set_pin_at(50, Pin::Rdy, false);
+    while sys.cycles() < 30 {
+        sys.step().unwrap();
+    }
+    let count = sys.bus.peek(0x0010);
+    sys.step().unwrap();
+    assert_eq!(sys.cycles(), 31);
+    assert_eq!(sys.bus.peek(0x0010), count);
+    while sys.cycles() < 100 {
+        sys.step().unwrap();
+    }
+    assert_eq!(sys.bus.peek(0x0010), 9);
+}
+
+// WAI takes no time of its own: the CPU wakes the cycle the timer interrupts, without a step
+// per cycle waited.
+#[test]
+fn test_wai_until_timer_irq() {
+    let code = Assembler::new()
+        .org(0x0400)
+        .lda(Operand::Imm(0xC0)) // 2 cycles: VIA2 IER, T1
+        .sta(Operand::Abs(val(0xDC1E))) // 4
+        .lda(Operand::Imm(0x50)) // 2: T1 one-shot, $C350 = 50,000 cycles
+        .sta(Operand::Abs(val(0xDC14))) // 4
+        .lda(Operand::Imm(0xC3)) // 2
+        .sta(Operand::Abs(val(0xDC15))) // 4: T1 starts
+        .cli() // 2
+        .wai() // 3
+        .stp()
+        .assemble()
+        .unwrap();
+    let mut sys = common::sys(code);
+    sys.bus.load(0xFFFE, vec![0x11, 0x04]).unwrap();
+    let mut steps = 0;
+    while sys.step().unwrap() != RunState::Stopped && steps < 1000 {
+        steps += 1;
+    }
+
+    // T1 times out 50,001 cycles after starting, counting from the start of the STA that
+    // started it, as devices are stepped after each instruction; then the IRQ takes 7 cycles,
+    // and STP 3
+    assert_eq!(sys.cycles(), 14 + 50_001 + 7 + 3);
+    assert!(steps < 100, "{steps} steps");
+}
+
+// A device counting how often it's stepped.
+struct Steps(Rc<Cell<u32>>);
+
+impl Device for Steps {
+    fn name(&self) -> &str {
+        "STEPS"
+    }
+
+    fn read(&mut self, _reg: u16) -> u8 {
+        0x00
+    }
+
+    fn write(&mut self, _reg: u16, _data: u8) {}
+
+    fn peek(&self, _reg: u16) -> u8 {
+        0x00
+    }
+
+    fn step(&mut self, _cycles: u8) {
+        self.0.set(self.0.get() + 1);
+    }
+
+    fn step_cycles(&mut self, _cycles: u32) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+// Waiting with no interrupt due, devices are stepped once per step however many cycles it
+// runs, and the host CPU is given up for as long as those cycles take.
+#[test]
+fn test_wai_idle() {
+    let code = Assembler::new().org(0x0400).wai().assemble().unwrap();
+    let mut sys = common::sys(code);
+    let steps = Rc::new(Cell::new(0));
+    sys.bus.map(0xDD00..=0xDD0F, Steps(steps.clone())).unwrap();
+    assert_eq!(sys.step(), Ok(RunState::Waiting));
+    assert_eq!((sys.cycles(), steps.get()), (3, 1));
+
+    let start = Instant::now();
+    for _ in 0..5 {
+        assert_eq!(sys.step(), Ok(RunState::Waiting));
+    }
+    let waited = sys.cycles() - 3;
+    assert!(waited >= 5 * 1000, "{waited} cycles");
+    assert_eq!(steps.get(), 1 + 5);
+    assert!(start.elapsed() >= Duration::from_micros(waited));
+}
+
+// Recordings are flushed every 100,000 cycles as they're made, and once more when asked.
+#[test]
+fn test_recording_flushes() {
+    let dir = std::env::temp_dir().join("pda6502v2emu-sys-recording");
+    fs::create_dir_all(&dir).unwrap();
+    let wav = dir.join("out.wav");
+    let log = dir.join("out.sidw");
+
+    // a SID write, then another after 100 x 256 x 5 cycles
+    let code = Assembler::new()
+        .org(0x0400)
+        .lda(Operand::Imm(0x0F)) // 2 cycles
+        .sta(Operand::Abs(val(0xD418))) // 4
+        .ldy(Operand::Imm(100))
+        .label("outer")
+        .ldx(Operand::Imm(0))
+        .label("inner")
+        .dex()
+        .bne(Operand::Rel(branch("inner")))
+        .dey()
+        .bne(Operand::Rel(branch("outer")))
+        .sta(Operand::Abs(val(0xD401)))
+        .stp()
+        .assemble()
+        .unwrap();
+    let mut sys = common::sys(code);
+    sys.record_wav(wav.to_str().unwrap(), 44100).unwrap();
+    sys.record_sid_log(log.to_str().unwrap()).unwrap();
+    sys.run().unwrap();
+    assert!(sys.cycles() > 100_000);
+
+    let first = SidWrite {
+        cycle: 2,
+        reg: 0x18,
+        data: 0x0F,
+    };
+    let flushed = sidlog::decode(&fs::read(&log).unwrap()).unwrap();
+    assert_eq!(flushed, [first]);
+    let wav_len = fs::metadata(&wav).unwrap().len();
+    assert!(wav_len > 44 + 2 * 44100 * 90_000 / 1_000_000);
+
+    sys.flush_sid_log().unwrap();
+    sys.flush_wav().unwrap();
+    let flushed = sidlog::decode(&fs::read(&log).unwrap()).unwrap();
+    assert_eq!(flushed.len(), 2);
+    assert_eq!(flushed[0], first);
+    assert_eq!((flushed[1].reg, flushed[1].data), (0x01, 0x0F));
+    assert!(fs::metadata(&wav).unwrap().len() > wav_len);
+}
+
+#[test]
+fn test_break_on_protected_write() {
+    let os = || Os {
+        areas: vec![Area {
+            name: "os".to_string(),
+            start: 0xF000,
+            size: 0x1000,
+            file: None,
+            protection: Some(Protection::ReadOnly),
+        }],
+        ..Os::default()
+    };
+    let code = Assembler::new()
+        .org(0x0400)
+        .lda(Operand::Imm(0x42))
+        .sta(Operand::Abs(val(0xF000)))
+        .stp()
+        .assemble()
+        .unwrap();
+
+    // reported, but carrying on
+    let mut sys = Sys::with_os(Variant::default(), os());
+    sys.load(&common::image(code.clone())).unwrap();
+    sys.run().unwrap();
+    assert_eq!(sys.bus.peek(0xF000), 0x00);
+
+    // stopping at the store
+    let mut sys = Sys::with_os(Variant::default(), os());
+    sys.load(&common::image(code)).unwrap();
+    sys.set_break_on_protected_write(true);
+    assert_eq!(
+        sys.run(),
+        Err(SysError::ProtectedWrite {
+            pc: 0x0402,
+            write: ProtectedWrite {
+                addr: 0xF000,
+                data: 0x42,
+                protection: Protection::ReadOnly,
+            },
+        })
+    );
+    assert_eq!(sys.bus.peek(0xF000), 0x00);
+}
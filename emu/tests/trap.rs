@@ -0,0 +1,150 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+
+use pda6502v2emu::asm::{branch, label, Assembler, Operand};
+use pda6502v2emu::isa::Variant;
+use pda6502v2emu::trap::{Listing, Runner, TrapError};
+
+// A tiny self-checking program in the style of the Klaus Dormann tests: it adds $40 + $02
+// and traps at "success" if the sum is $42, or at "fail" otherwise.
+fn self_test(expected: u8) -> Vec<u8> {
+    Assembler::new()
+        .org(0x0400)
+        .label("start")
+        .cld()
+        .clc()
+        .lda(Operand::Imm(0x40))
+        .adc(Operand::Imm(0x02))
+        .cmp(Operand::Imm(expected))
+        .bne(Operand::Rel(branch("fail")))
+        .label("success")
+        .jmp(Operand::Abs(label("success")))
+        .label("fail")
+        .bne(Operand::Rel(branch("fail")))
+        .assemble()
+        .unwrap()
+}
+
+// start at $0400; success at $040A; fail at $040D
+const SUCCESS: u16 = 0x040A;
+const FAIL: u16 = 0x040D;
+
+fn listing() -> Listing {
+    let lines = [
+        ("0400 : d8", "start   cld"),
+        ("0401 : 18", "        clc"),
+        ("040a : 4c0a04", "success jmp success"),
+        ("0405 : 6902", ">       adc #2"),
+        (
+            "040d : d0fe",
+            "fail    bne fail        ;failed not equal (non zero)",
+        ),
+    ];
+    let text: Vec<String> = lines
+        .iter()
+        .map(|(code, source)| format!("{code:<24}{source}"))
+        .collect();
+    Listing::parse(&text.join("\n"))
+}
+
+#[test]
+fn test_trap_success() -> Result<(), Box<dyn Error>> {
+    let mut runner = Runner::new(Variant::default(), &self_test(0x42), 0x0400, 0x0400)?;
+    let report = runner.success(SUCCESS).listing(listing()).run()?;
+    println!("{report}");
+    assert!(report.passed);
+    assert_eq!(report.pc, SUCCESS);
+    assert_eq!(report.instructions, 7);
+    assert_eq!(report.label, Some(("success".to_string(), 0)));
+    assert_eq!(report.source.as_deref(), Some("success jmp success"));
+    Ok(())
+}
+
+#[test]
+fn test_trap_failure() -> Result<(), Box<dyn Error>> {
+    let mut runner = Runner::new(Variant::default(), &self_test(0x43), 0x0400, 0x0400)?;
+    let report = runner.success(SUCCESS).listing(listing()).run()?;
+    println!("{report}");
+    assert!(!report.passed);
+    assert_eq!(report.pc, FAIL);
+    assert_eq!(report.label, Some(("fail".to_string(), 0)));
+    assert_eq!(
+        report.source.as_deref(),
+        Some("fail    bne fail        ;failed not equal (non zero)")
+    );
+    assert!(report.to_string().starts_with("FAIL: trap at $040D (fail)"));
+
+    // lines expanded from macros have no label, but do have source
+    let listing = listing();
+    assert_eq!(listing.nearest_label(0x0405), Some(("start", 5)));
+    assert_eq!(listing.source(0x0405), Some("adc #2"));
+    Ok(())
+}
+
+#[test]
+fn test_trap_errors() {
+    assert_eq!(
+        Runner::new(Variant::default(), &[0xEA; 0x20], 0xFFF0, 0xFFF0).err(),
+        Some(TrapError::ImageTooLarge {
+            load: 0xFFF0,
+            len: 0x20
+        })
+    );
+
+    // a loop that never traps
+    let image = Assembler::new()
+        .org(0x0400)
+        .label("loop")
+        .inx()
+        .bne(Operand::Rel(branch("loop")))
+        .iny()
+        .bra(Operand::Rel(branch("loop")))
+        .assemble()
+        .unwrap();
+    let mut runner = Runner::new(Variant::default(), &image, 0x0400, 0x0400).unwrap();
+    match runner.max_cycles(10_000).run() {
+        Err(TrapError::Timeout { cycles, .. }) => assert!(cycles >= 10_000),
+        other => panic!("expected timeout, got {other:?}"),
+    }
+}
+
+// Klaus Dormann's 6502/65C02 functional, extended opcode and decimal tests, from
+// https://github.com/Klaus2m5/6502_65C02_functional_tests, assembled with as65. The binaries
+// aren't included here; configure one with environment variables, e.g.
+//
+//     KLAUS_TEST_BIN=bin_files/6502_functional_test.bin KLAUS_TEST_SUCCESS=3469 \
+//     KLAUS_TEST_LISTING=bin_files/6502_functional_test.lst \
+//         cargo test --release --test trap -- --nocapture
+//
+// KLAUS_TEST_LOAD (default 0), KLAUS_TEST_START (default 400) and KLAUS_TEST_SUCCESS are hex
+// addresses; KLAUS_TEST_VARIANT is nmos, rockwell or wdc (the default).
+#[test]
+fn test_klaus_dormann_binary() -> Result<(), Box<dyn Error>> {
+    let Ok(path) = env::var("KLAUS_TEST_BIN") else {
+        println!("KLAUS_TEST_BIN not set; skipping Klaus Dormann test binary");
+        return Ok(());
+    };
+    let hex = |name: &str, default: &str| -> Result<u16, Box<dyn Error>> {
+        let val = env::var(name).unwrap_or(default.to_string());
+        Ok(u16::from_str_radix(&val, 16)?)
+    };
+    let variant = match env::var("KLAUS_TEST_VARIANT").as_deref() {
+        Ok("nmos") => Variant::Nmos6502,
+        Ok("rockwell") => Variant::Rockwell65C02,
+        _ => Variant::Wdc65C02S,
+    };
+
+    let image = fs::read(path)?;
+    let load = hex("KLAUS_TEST_LOAD", "0")?;
+    let start = hex("KLAUS_TEST_START", "400")?;
+    let mut runner = Runner::new(variant, &image, load, start)?;
+    runner.success(hex("KLAUS_TEST_SUCCESS", "")?);
+    if let Ok(path) = env::var("KLAUS_TEST_LISTING") {
+        runner.listing(Listing::parse(&fs::read_to_string(path)?));
+    }
+    let report = runner.run()?;
+    println!("{report}");
+    assert!(report.passed, "{report}");
+    Ok(())
+}
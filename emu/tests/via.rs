@@ -0,0 +1,339 @@
+use pda6502v2emu::asm::{branch, label, val, Assembler, Operand};
+use pda6502v2emu::bus::Bus;
+use pda6502v2emu::device::Device;
+use pda6502v2emu::via::Via;
+
+mod common;
+
+const ORB: u16 = 0x0;
+const ORA: u16 = 0x1;
+const DDRB: u16 = 0x2;
+const DDRA: u16 = 0x3;
+const T1CL: u16 = 0x4;
+const T1CH: u16 = 0x5;
+const T1LL: u16 = 0x6;
+const T2CL: u16 = 0x8;
+const T2CH: u16 = 0x9;
+const SR: u16 = 0xA;
+const ACR: u16 = 0xB;
+const PCR: u16 = 0xC;
+const IFR: u16 = 0xD;
+const IER: u16 = 0xE;
+
+#[test]
+fn test_interrupt_flags_and_enables() {
+    let mut via = Via::new("VIA1");
+    assert_eq!(via.read(IER), 0x80);
+
+    // bit 7 set: enable the 1 bits; clear: disable them
+    via.write(IER, 0x80 | Via::IRQ_T1 | Via::IRQ_T2);
+    assert_eq!(via.read(IER), 0x80 | Via::IRQ_T1 | Via::IRQ_T2);
+    via.write(IER, Via::IRQ_T2);
+    assert_eq!(via.read(IER), 0x80 | Via::IRQ_T1);
+
+    // a flag shows in IFR whether or not it's enabled, but only enabled flags interrupt
+    via.write(T2CL, 0x00);
+    via.write(T2CH, 0x00);
+    via.step(1);
+    assert_eq!(via.read(IFR), Via::IRQ_T2);
+    assert!(!via.irq());
+
+    via.write(T1CL, 0x00);
+    via.write(T1CH, 0x00);
+    via.step(1);
+    assert_eq!(via.read(IFR), 0x80 | Via::IRQ_T1 | Via::IRQ_T2);
+    assert!(via.irq());
+
+    // writing 1 to a flag clears it
+    via.write(IFR, 0x80 | Via::IRQ_T1);
+    assert_eq!(via.read(IFR), Via::IRQ_T2);
+    assert!(!via.irq());
+
+    // reset clears flags and enables
+    via.write(IER, 0xFF);
+    via.reset();
+    assert_eq!(via.read(IFR), 0x00);
+    assert_eq!(via.read(IER), 0x80);
+}
+
+#[test]
+fn test_timer1() {
+    let mut via = Via::new("VIA1");
+    via.write(IER, 0x80 | Via::IRQ_T1);
+
+    // one-shot: times out N+1 cycles after writing T1CH, then only counts down
+    via.write(T1CL, 0x10);
+    assert_eq!(via.read(IFR), 0x00);
+    via.write(T1CH, 0x00);
+    assert_eq!(via.read(T1CL), 0x10);
+    via.step(0x10);
+    assert_eq!(via.read(T1CL), 0x00);
+    assert!(!via.irq());
+    via.step(1);
+    assert!(via.irq());
+    assert_eq!(via.peek(T1CL), 0xFF);
+    assert_eq!(via.peek(T1CH), 0xFF);
+
+    // reading T1CL clears the flag, and it stays clear
+    via.read(T1CL);
+    assert!(!via.irq());
+    via.step(255);
+    via.step(255);
+    assert!(!via.irq());
+
+    // free-running: interrupts every N+2 cycles, reloading from the latch, toggling PB7
+    via.write(ACR, 0xC0);
+    via.write(T1CL, 0x10);
+    via.write(T1CH, 0x00);
+    assert_eq!(via.port_b() & 0x80, 0x00);
+    via.step(0x11);
+    assert!(via.irq());
+    assert_eq!(via.port_b() & 0x80, 0x80);
+    via.write(IFR, Via::IRQ_T1);
+    via.step(1);
+    assert_eq!(via.peek(T1CL), 0x10);
+
+    // the latch can change without restarting the count
+    via.write(T1LL, 0x20);
+    via.step(0x10);
+    assert!(!via.irq());
+    via.step(1);
+    assert!(via.irq());
+    assert_eq!(via.port_b() & 0x80, 0x00);
+    via.read(T1CL);
+    via.step(0x22);
+    assert!(via.irq());
+}
+
+// How far ahead the timers can be stepped before they interrupt.
+#[test]
+fn test_next_interrupt() {
+    let mut via = Via::new("VIA1");
+    via.write(T1CL, 0x10);
+    via.write(T1CH, 0x00);
+    via.write(T2CL, 0x40);
+    via.write(T2CH, 0x00);
+    assert_eq!(via.next_interrupt(), None);
+
+    // only enabled interrupts count, the soonest first
+    via.write(IER, 0x80 | Via::IRQ_T2);
+    assert_eq!(via.next_interrupt(), Some(0x41));
+    via.write(IER, 0x80 | Via::IRQ_T1);
+    assert_eq!(via.next_interrupt(), Some(0x11));
+    via.step(0x10);
+    assert_eq!(via.next_interrupt(), Some(1));
+    via.step(1);
+    assert!(via.irq());
+    assert_eq!(via.next_interrupt(), Some(0));
+
+    // free-running, the counter reloads from the latch first
+    via.write(ACR, 0x40);
+    via.write(T1CH, 0x00);
+    via.step(0x11);
+    via.write(IFR, Via::IRQ_T1);
+    assert_eq!(via.next_interrupt(), Some(0x12));
+    via.step(0x11);
+    assert!(!via.irq());
+    via.step(1);
+    assert!(via.irq());
+}
+
+#[test]
+fn test_timer2() {
+    let mut via = Via::new("VIA1");
+    via.write(IER, 0x80 | Via::IRQ_T2);
+
+    // one-shot, counting cycles
+    via.write(T2CL, 0x08);
+    via.write(T2CH, 0x00);
+    via.step(8);
+    assert!(!via.irq());
+    via.step(1);
+    assert!(via.irq());
+    via.read(T2CL);
+    assert!(!via.irq());
+
+    // counting pulses on PB6, down to zero
+    via.write(ACR, 0x20);
+    via.write(T2CL, 0x03);
+    via.write(T2CH, 0x00);
+    via.step(100);
+    assert_eq!(via.peek(T2CL), 0x03);
+    for _ in 0..3 {
+        assert!(!via.irq());
+        via.set_port_b(0xBF);
+        via.set_port_b(0xFF);
+    }
+    assert!(via.irq());
+    assert_eq!(via.peek(T2CL), 0x00);
+}
+
+#[test]
+fn test_ports() {
+    let mut via = Via::new("VIA1");
+
+    // DDR selects output pins, driven by ORA/ORB; the others read from outside
+    via.write(DDRA, 0xF0);
+    via.write(ORA, 0xAA);
+    via.set_port_a(0x55);
+    assert_eq!(via.port_a(), 0xA5);
+    assert_eq!(via.read(ORA), 0xA5);
+
+    via.write(DDRB, 0x0F);
+    via.write(ORB, 0x33);
+    via.set_port_b(0xC0);
+    assert_eq!(via.port_b(), 0xC3);
+    assert_eq!(via.read(ORB), 0xC3);
+
+    // with latching enabled, port A reads as it was on the active CA1 edge
+    via.write(ACR, 0x01);
+    via.write(PCR, 0x01); // CA1 positive edge
+    via.set_ca1(false);
+    via.set_port_a(0x0F);
+    via.set_ca1(true);
+    via.set_port_a(0x00);
+    assert_eq!(via.read(IFR), Via::IRQ_CA1);
+    assert_eq!(via.read(ORA), 0xAF);
+
+    // reading IRA cleared the CA1 flag
+    assert_eq!(via.read(IFR), 0x00);
+}
+
+#[test]
+fn test_handshake() {
+    let mut via = Via::new("VIA1");
+
+    // CA1 negative edge; CA2 handshake output: low on an ORA access, high on CA1's edge
+    via.write(PCR, 0b1000);
+    assert!(via.ca2());
+    via.write(ORA, 0x42);
+    assert!(!via.ca2());
+    via.set_ca1(false);
+    assert!(via.ca2());
+    assert_eq!(via.read(IFR), Via::IRQ_CA1);
+
+    // CA2 pulse output: low for a cycle
+    via.write(PCR, 0b1010);
+    via.read(ORA);
+    assert!(!via.ca2());
+    via.step(1);
+    assert!(via.ca2());
+
+    // CB2 manual outputs, and handshake on ORB writes
+    via.write(PCR, 0b1100_0000);
+    assert!(!via.cb2());
+    via.write(PCR, 0b1110_0000);
+    assert!(via.cb2());
+    via.write(PCR, 0b1000_0000);
+    via.write(ORB, 0x00);
+    assert!(!via.cb2());
+    via.set_cb1(false);
+    assert!(via.cb2());
+
+    // CB2 as an input, positive edge, and independent: ORB access doesn't clear it
+    via.write(PCR, 0b0110_0000);
+    via.set_cb2(false);
+    via.set_cb2(true);
+    via.write(IFR, Via::IRQ_CB1);
+    assert_eq!(via.read(IFR), Via::IRQ_CB2);
+    via.read(ORB);
+    assert_eq!(via.read(IFR), Via::IRQ_CB2);
+
+    // but does in the plain input modes
+    via.write(PCR, 0b0100_0000);
+    via.read(ORB);
+    assert_eq!(via.read(IFR), 0x00);
+}
+
+#[test]
+fn test_shift_register() {
+    let mut via = Via::new("VIA1");
+    via.write(IER, 0x80 | Via::IRQ_SR);
+
+    // shift out under Φ2: a bit every 2 cycles onto CB2, clocked on CB1
+    via.write(ACR, 0b110 << 2);
+    via.write(SR, 0b1011_0010);
+    let mut bits = Vec::new();
+    for _ in 0..8 {
+        via.step(1);
+        assert!(!via.cb1());
+        bits.push(via.cb2() as u8);
+        via.step(1);
+        assert!(via.cb1());
+    }
+    assert_eq!(bits, [1, 0, 1, 1, 0, 0, 1, 0]);
+    assert!(via.irq());
+    assert_eq!(via.read(SR), 0b1011_0010);
+    assert!(!via.irq());
+
+    // shift in under an external clock on CB1, sampling CB2 on its rising edge
+    via.write(ACR, 0b011 << 2);
+    via.read(SR);
+    for bit in [0, 1, 1, 0, 1, 0, 0, 1] {
+        via.set_cb1(false);
+        via.set_cb2(bit == 1);
+        via.set_cb1(true);
+    }
+    assert!(via.irq());
+    assert_eq!(via.read(SR), 0b0110_1001);
+
+    // shift in under T2: an edge every N+2 cycles
+    via.write(ACR, 0b001 << 2);
+    via.write(T2CL, 0x02);
+    via.set_cb2(true);
+    via.read(SR);
+    via.step(8 * 2 * 4 - 1);
+    assert!(!via.irq());
+    via.step(1);
+    assert!(via.irq());
+    assert_eq!(via.read(SR), 0xFF);
+}
+
+#[test]
+fn test_register_names() {
+    let bus = Bus::new();
+    assert_eq!(bus.name_for_write(0xDC10), "VIA2:ORB");
+    assert_eq!(bus.name_for_read(0xDC1D), "VIA2:IFR");
+    assert_eq!(bus.name_for_read(0xDC0F), "VIA1:IRAH");
+}
+
+// Like os.s and sid.s: VIA2 T1 free-running interrupts call SidTick through the IRQ handler.
+#[test]
+fn test_timer_interrupts_drive_handler() {
+    let code = Assembler::new()
+        .org(0x0400)
+        .lda(Operand::Imm(0xC0)) // enable T1 interrupts
+        .sta(Operand::Abs(val(0xDC1E)))
+        .lda(Operand::Imm(0x40)) // T1 free-running
+        .sta(Operand::Abs(val(0xDC1B)))
+        .lda(Operand::Imm(0xE6)) // every 1000 cycles
+        .sta(Operand::Abs(val(0xDC14)))
+        .lda(Operand::Imm(0x03))
+        .sta(Operand::Abs(val(0xDC15)))
+        .cli()
+        .label("idle")
+        .bra(Operand::Rel(branch("idle")))
+        .label("irq")
+        .pha()
+        .bit(Operand::Abs(val(0xDC1D)))
+        .bvs(Operand::Rel(branch("v2t1")))
+        .pla()
+        .rti()
+        .label("v2t1")
+        .jsr(Operand::Abs(label("tick")))
+        .pla()
+        .rti()
+        .label("tick")
+        .lda(Operand::Imm(0x40)) // clear the T1 flag
+        .sta(Operand::Abs(val(0xDC1D)))
+        .inc(Operand::Z(0x10))
+        .rts()
+        .assemble()
+        .unwrap();
+    let mut sys = common::sys(code);
+    sys.bus.load(0xFFFE, vec![0x17, 0x04]).unwrap();
+    while sys.cycles() < 10_000 {
+        sys.step().unwrap();
+    }
+    assert_eq!(sys.bus.peek(0x0010), 9);
+}